@@ -2,54 +2,41 @@ use log::info;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-  models::{app_db_state::AppDbState, app_state::AppState, cloud_sync::CloudSync},
-  utils::{
-    error::error_to_string, sync_state_manager::check_cloud_or_fs_is_syncing,
-    window_event_manager::WindowEventManager,
+  models::{
+    app_db_state::AppDbState,
+    app_state::AppState,
+    cloud_sync::CloudSync,
+    cloud_sync_config::CloudSyncConfig,
+    command_error::{CommandError, ErrorCode},
+    git_auth::{GitAuthMethod, GitAuthSecret},
+    merge_conflict::{ConflictResolution, MergeConflict},
+    sync_job::{SyncJob, SyncJobKind},
   },
+  utils::{git_utils::GitUtils, lock_manager::LockMode},
 };
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TestGitCloneSshResponse {
-  status: bool,
-  message: String,
-}
-
 /// Command to Test Git clone via ssh
 #[tauri::command]
-pub async fn test_git_clone_ssh(
-  state: tauri::State<'_, AppState>,
-  db_state: tauri::State<'_, AppDbState>,
-) -> Result<TestGitCloneSshResponse, String> {
+pub async fn test_git_clone_ssh() -> Result<(), CommandError> {
   info!("Testing git clone via ssh...");
   std::fs::remove_dir_all("/tmp/git2-rs").ok();
-  CloudSync::test_git_clone_ssh().map_err(error_to_string)?;
+  GitUtils::test_git_clone_ssh()?;
   info!("Done!");
-  Ok(TestGitCloneSshResponse {
-    status: true,
-    message: "Success".to_string(),
-  })
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SetupGitCloudSyncResponse {
-  /// `true` for success, `false` for failure
-  status: bool,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
+  Ok(())
 }
 
 /// # Command to setup Git Cloud Sync
 ///
 /// - Get the repo url, set it in DB + State.
+/// - Persist the auth method (defaults to `ssh-agent`) in DB,
+/// the `git_sync_secret` (access token/key passphrase) is only kept in State.
+/// - `git_sync_remote_name`/`git_sync_branch`: remote and branch to sync with
+/// (default `origin`/`main`, an existing repo keeps its branch by default).
 /// - Clone/Pull the repo in the `app_dir`.
 /// - Add the required files/dir to sync.
 /// - Push the changes to repo origin.
+/// - Runs as a background job, returns its job id (the outcome
+/// is reported by the job, see `get_sync_job_status`).
 #[tauri::command]
 pub async fn setup_git_cloud_sync(
   state: tauri::State<'_, AppState>,
@@ -58,89 +45,327 @@ pub async fn setup_git_cloud_sync(
   git_sync_repo_url: String,
   git_sync_user_name: String,
   git_sync_user_email: String,
-) -> Result<SetupGitCloudSyncResponse, String> {
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(SetupGitCloudSyncResponse {
-      status: false,
-      retry: true,
-      message: "Sync is already in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(SetupGitCloudSyncResponse {
-      status: false,
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
-  let mut db = db_state.db.lock().map_err(error_to_string)?;
-  let wem = WindowEventManager::new(&window);
-  let cloud_sync =
-    CloudSync::new(state.inner().to_owned(), &mut db, &wem).map_err(error_to_string)?;
-  cloud_sync
-    .setup(
-      state.inner().to_owned(),
-      &mut db,
-      &git_sync_repo_url,
-      &git_sync_user_name,
-      &git_sync_user_email,
-    )
-    .map_err(error_to_string)?;
-  Ok(SetupGitCloudSyncResponse {
-    status: true,
-    retry: false,
-    message: "Success".to_string(),
-  })
+  git_sync_auth_method: Option<GitAuthMethod>,
+  git_sync_secret: Option<String>,
+  git_sync_remote_name: Option<String>,
+  git_sync_branch: Option<String>,
+) -> Result<String, CommandError> {
+  let lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "setup_git_cloud_sync")?;
+  let db = db_state.db.clone();
+  let job_state = state.inner().to_owned();
+  let job_id = state.sync_jobs.spawn(
+    state.inner().to_owned(),
+    window,
+    lock,
+    SyncJobKind::Setup,
+    move |wem, cancel_flag| {
+      CloudSync::new(wem)?.with_cancel_flag(cancel_flag).setup(
+        job_state,
+        &db,
+        &git_sync_repo_url,
+        &git_sync_user_name,
+        &git_sync_user_email,
+        git_sync_auth_method.unwrap_or_default(),
+        git_sync_secret.map(GitAuthSecret),
+        git_sync_remote_name.as_deref(),
+        git_sync_branch.as_deref(),
+      )
+    },
+  )?;
+  Ok(job_id)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SyncToGitCloudResponse {
-  /// `true` for success, `false` for failure
-  status: bool,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
+/// # Set Git Cloud Sync Secret
+///
+/// Command to set the cloud sync auth secret (access token/key passphrase).
+/// - Secrets are only kept in memory, hence need to be set again after restart.
+#[tauri::command]
+pub async fn set_git_cloud_sync_secret(
+  state: tauri::State<'_, AppState>,
+  git_sync_secret: Option<String>,
+) -> Result<(), CommandError> {
+  *state.git_auth_secret.lock()? = git_sync_secret.map(GitAuthSecret);
+  Ok(())
 }
 
 /// # Sync to Git Cloud
 ///
 /// Command to Sync documents to Git Cloud
-///
+/// - Runs as a background job, returns its job id. The outcome
+/// (`pushed`, `upToDate`, `rejected` or `conflicted` with the conflicts)
+/// is reported by the job, see `get_sync_job_status`.
 #[tauri::command]
 pub async fn sync_to_git_cloud(
   state: tauri::State<'_, AppState>,
   db_state: tauri::State<'_, AppDbState>,
   window: tauri::Window,
-) -> Result<SyncToGitCloudResponse, String> {
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(SyncToGitCloudResponse {
-      status: false,
-      retry: true,
-      message: "Sync is already in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(SyncToGitCloudResponse {
-      status: false,
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
-  let mut db = db_state.db.lock().map_err(error_to_string)?;
-  let wem = WindowEventManager::new(&window);
-  let cloud_sync =
-    CloudSync::new(state.inner().to_owned(), &mut db, &wem).map_err(error_to_string)?;
-  cloud_sync
-    .sync(state.inner().to_owned(), &mut db)
-    .map_err(error_to_string)?;
-  Ok(SyncToGitCloudResponse {
-    status: true,
-    retry: false,
-    message: "Success".to_string(),
-  })
+) -> Result<String, CommandError> {
+  let lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "sync_to_git_cloud")?;
+  let db = db_state.db.clone();
+  let job_state = state.inner().to_owned();
+  let job_id = state.sync_jobs.spawn(
+    state.inner().to_owned(),
+    window,
+    lock,
+    SyncJobKind::Sync,
+    move |wem, cancel_flag| {
+      CloudSync::new(wem)?
+        .with_cancel_flag(cancel_flag)
+        .sync(job_state, &db)
+    },
+  )?;
+  Ok(job_id)
+}
+
+/// # Preview Cloud Sync
+///
+/// Command to preview (dry run) a sync to Git Cloud
+/// - Only fetches the remote, nothing is merged, committed or pushed.
+/// - Reports the local and remote changes, if a fast-forward is
+/// possible and the documents that would conflict.
+/// - Runs as a background job, returns its job id (the `preview`
+/// is reported by the job, see `get_sync_job_status`).
+#[tauri::command]
+pub async fn preview_cloud_sync(
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+  window: tauri::Window,
+) -> Result<String, CommandError> {
+  info!("preview_cloud_sync()");
+  let lock = state
+    .locks
+    .try_cloud_sync(LockMode::Read, "preview_cloud_sync")?;
+  let db = db_state.db.clone();
+  let job_state = state.inner().to_owned();
+  let job_id = state.sync_jobs.spawn(
+    state.inner().to_owned(),
+    window,
+    lock,
+    SyncJobKind::Preview,
+    move |wem, cancel_flag| {
+      CloudSync::new(wem)?
+        .with_cancel_flag(cancel_flag)
+        .preview(job_state, &db)
+    },
+  )?;
+  Ok(job_id)
+}
+
+/// # Get Cloud Sync Conflicts
+///
+/// Command to get the conflicted documents (with their ancestor,
+/// ours and theirs content) of the in-progress cloud sync merge.
+#[tauri::command]
+pub async fn get_cloud_sync_conflicts(
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<MergeConflict>, CommandError> {
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Read, "get_cloud_sync_conflicts")?;
+  let conflicts = CloudSync::conflicts(state.inner().to_owned())?;
+  Ok(conflicts)
+}
+
+/// # Resolve Cloud Sync Conflict
+///
+/// Command to resolve a conflicted document (on `relative_path`) as
+/// ours, theirs or hand-merged content.
+#[tauri::command]
+pub async fn resolve_cloud_sync_conflict(
+  state: tauri::State<'_, AppState>,
+  relative_path: String,
+  resolution: ConflictResolution,
+) -> Result<(), CommandError> {
+  info!(
+    "resolve_cloud_sync_conflict() -> relative_path: {}",
+    relative_path
+  );
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "resolve_cloud_sync_conflict")?;
+  CloudSync::resolve_conflict(state.inner().to_owned(), &relative_path, resolution)?;
+  Ok(())
+}
+
+/// # Complete Cloud Sync Merge
+///
+/// Command to create the merge commit once all conflicts are
+/// resolved and push it to the remote.
+/// - Runs as a background job, returns its job id (the outcome
+/// is reported by the job, see `get_sync_job_status`).
+#[tauri::command]
+pub async fn complete_cloud_sync_merge(
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+  window: tauri::Window,
+) -> Result<String, CommandError> {
+  let lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "complete_cloud_sync_merge")?;
+  let db = db_state.db.clone();
+  let job_state = state.inner().to_owned();
+  let job_id = state.sync_jobs.spawn(
+    state.inner().to_owned(),
+    window,
+    lock,
+    SyncJobKind::CompleteMerge,
+    move |wem, cancel_flag| {
+      CloudSync::new(wem)?
+        .with_cancel_flag(cancel_flag)
+        .complete_merge(job_state, &db)
+    },
+  )?;
+  Ok(job_id)
+}
+
+/// # Cancel Cloud Sync
+///
+/// Command to cancel the cloud sync job `job_id` (the running job if not given).
+/// - The job stops at its next step (fetches are aborted midway), its final
+/// status is reported via `get_sync_job_status`/the `cloud_sync_job` event.
+/// - Returns the job being cancelled, fails with `NOT_FOUND` if no such job is running.
+#[tauri::command]
+pub async fn cancel_cloud_sync(
+  state: tauri::State<'_, AppState>,
+  job_id: Option<String>,
+) -> Result<SyncJob, CommandError> {
+  info!("cancel_cloud_sync() -> job_id: {:?}", job_id);
+  state
+    .sync_jobs
+    .cancel(job_id.as_deref())?
+    .ok_or_else(|| CommandError::user(ErrorCode::NotFound, "No sync in progress!"))
+}
+
+/// # Get Sync Job Status
+///
+/// Command to get the status (and outcome once finished) of the cloud
+/// sync job `job_id` (the most recent job if not given).
+#[tauri::command]
+pub async fn get_sync_job_status(
+  state: tauri::State<'_, AppState>,
+  job_id: Option<String>,
+) -> Result<SyncJob, CommandError> {
+  state
+    .sync_jobs
+    .get(job_id.as_deref())?
+    .ok_or_else(|| CommandError::user(ErrorCode::NotFound, "Sync job not found!"))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCloudSyncConfigResponse {
+  /// Current config, `None` if cloud sync is not setup
+  config: Option<CloudSyncConfig>,
+  /// `true` if an auth secret is set (for the current session)
+  has_secret: bool,
+}
+
+/// # Get Cloud Sync Config
+///
+/// Command to get the persisted cloud sync config
+#[tauri::command]
+pub async fn get_cloud_sync_config(
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<GetCloudSyncConfigResponse, CommandError> {
+  let db = db_state.db.lock()?;
+  let config = CloudSyncConfig::load(&db);
+  let has_secret = state.git_auth_secret.lock()?.is_some();
+  Ok(GetCloudSyncConfigResponse { config, has_secret })
+}
+
+/// # Update Cloud Sync Config
+///
+/// Command to update the cloud sync config without re-running the setup.
+/// - Only the given values are updated.
+/// - `commit_message_template`: template of the sync commit messages
+/// (empty to reset to the default).
+/// - Returns the updated config.
+#[tauri::command]
+pub async fn update_cloud_sync_config(
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+  git_sync_repo_url: Option<String>,
+  git_sync_user_name: Option<String>,
+  git_sync_user_email: Option<String>,
+  git_sync_auth_method: Option<GitAuthMethod>,
+  git_sync_secret: Option<String>,
+  auto_sync_enabled: Option<bool>,
+  auto_sync_interval_secs: Option<u64>,
+  auto_sync_debounce_secs: Option<u64>,
+  commit_message_template: Option<String>,
+) -> Result<CloudSyncConfig, CommandError> {
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "update_cloud_sync_config")?;
+  let mut db = db_state.db.lock()?;
+  let config = CloudSync::update_config(
+    state.inner().to_owned(),
+    &mut db,
+    git_sync_repo_url,
+    git_sync_user_name,
+    git_sync_user_email,
+    git_sync_auth_method,
+    git_sync_secret.map(GitAuthSecret),
+    auto_sync_enabled,
+    auto_sync_interval_secs,
+    auto_sync_debounce_secs,
+    commit_message_template,
+  )?;
+  Ok(config)
+}
+
+/// # Switch Cloud Sync Branch
+///
+/// Command to switch the branch synced with the remote.
+/// - Local changes are committed to the current branch first.
+/// - Documents are replaced by the ones of the branch
+/// (created from the remote/current branch if missing).
+/// - Runs as a background job, returns its job id (the updated
+/// `config` is reported by the job, see `get_sync_job_status`).
+#[tauri::command]
+pub async fn switch_cloud_sync_branch(
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+  window: tauri::Window,
+  branch: String,
+) -> Result<String, CommandError> {
+  info!("switch_cloud_sync_branch() -> branch: {}", branch);
+  let lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "switch_cloud_sync_branch")?;
+  let db = db_state.db.clone();
+  let job_state = state.inner().to_owned();
+  let job_id = state.sync_jobs.spawn(
+    state.inner().to_owned(),
+    window,
+    lock,
+    SyncJobKind::SwitchBranch,
+    move |wem, cancel_flag| {
+      CloudSync::new(wem)?
+        .with_cancel_flag(cancel_flag)
+        .switch_branch(job_state, &db, &branch)
+    },
+  )?;
+  Ok(job_id)
+}
+
+/// # Disconnect Cloud Sync
+///
+/// Command to disconnect cloud sync (local documents and history are kept).
+#[tauri::command]
+pub async fn disconnect_cloud_sync(
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<(), CommandError> {
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "disconnect_cloud_sync")?;
+  let mut db = db_state.db.lock()?;
+  CloudSync::disconnect(state.inner().to_owned(), &mut db)?;
+  Ok(())
 }
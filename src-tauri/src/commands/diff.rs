@@ -0,0 +1,96 @@
+use log::info;
+
+use crate::{
+  models::{
+    app_db_state::AppDbState,
+    app_state::AppState,
+    cloud_sync::CloudSync,
+    command_error::CommandError,
+    diff::{DiffMode, FileDiff},
+    document_diff::DocumentDiff,
+  },
+  utils::{document_path::DocumentPath, lock_manager::LockMode},
+};
+
+/// # Diff Working Changes
+///
+/// Get the changes of the documents since the last cloud sync commit
+/// (what the next sync would commit).
+/// - `mode`: `line` (default) or `word` for inline word changes.
+#[tauri::command]
+pub async fn diff_working_changes(
+  mode: Option<DiffMode>,
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<FileDiff>, CommandError> {
+  info!("diff_working_changes() -> mode: {:?}", mode);
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Read, "diff_working_changes")?;
+  let file_diffs = DocumentDiff::working_changes(state.inner(), mode.unwrap_or_default())?;
+  Ok(file_diffs)
+}
+
+/// # Diff Remote Changes
+///
+/// Get the changes of the documents on the cloud remote not yet synced locally.
+/// - `fetch`: fetch the remote first (default `true`), else use the last fetched state.
+/// - `mode`: `line` (default) or `word` for inline word changes.
+#[tauri::command]
+pub async fn diff_remote_changes(
+  fetch: Option<bool>,
+  mode: Option<DiffMode>,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<Vec<FileDiff>, CommandError> {
+  info!(
+    "diff_remote_changes() -> fetch: {:?}, mode: {:?}",
+    fetch, mode
+  );
+  // fetching updates the remote refs, block other cloud sync operations meanwhile
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Write, "diff_remote_changes")?;
+  let credentials = {
+    let db = db_state.db.lock()?;
+    CloudSync::credentials(state.inner(), &db)?
+  };
+  let file_diffs = DocumentDiff::remote_changes(
+    state.inner(),
+    credentials,
+    fetch.unwrap_or(true),
+    mode.unwrap_or_default(),
+  )?;
+  Ok(file_diffs)
+}
+
+/// # Diff Document Revisions
+///
+/// Get the changes of the document on the specified relative path between
+/// the commits `old_commit_id` and `new_commit_id` (defaults to the working copy).
+/// - `mode`: `line` (default) or `word` for inline word changes.
+/// - Returns `None` if the document is unchanged.
+#[tauri::command]
+pub async fn diff_document_revisions(
+  relative_path: String,
+  old_commit_id: String,
+  new_commit_id: Option<String>,
+  mode: Option<DiffMode>,
+  state: tauri::State<'_, AppState>,
+) -> Result<Option<FileDiff>, CommandError> {
+  info!(
+    "diff_document_revisions() -> relative_path: {}, old_commit_id: {}, new_commit_id: {:?}",
+    relative_path, old_commit_id, new_commit_id
+  );
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Read, "diff_document_revisions")?;
+  let document_path = DocumentPath::resolve(&state.dir_paths.documents, &relative_path)?;
+  let file_diff = DocumentDiff::revisions(
+    state.inner(),
+    &document_path,
+    &old_commit_id,
+    new_commit_id.as_deref(),
+    mode.unwrap_or_default(),
+  )?;
+  Ok(file_diff)
+}
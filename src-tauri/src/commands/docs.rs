@@ -1,147 +1,75 @@
-use log::{debug, info};
-use relative_path::RelativePath;
+use std::path::Path;
+
+use log::info;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{
-  models::app_state::AppState,
-  utils::{error::error_to_string, fsutils, sync_state_manager::check_cloud_or_fs_is_syncing},
+  models::{
+    app_db_state::AppDbState,
+    app_state::AppState,
+    command_error::{CommandError, ErrorCode},
+    relocation::{Relocation, RelocationReport},
+    trash::{Trash, TrashEntry},
+  },
+  utils::{
+    document_path::DocumentPath,
+    front_matter::{set_front_matter_keys, FrontMatter},
+    fsutils,
+    lock_manager::DOCUMENTS_ROOT,
+    search_index::update_search_index,
+  },
 };
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct FetchDocInfoResponse {
-  file_meta_info: Option<fsutils::FileMetaInfo>,
-  /// `true` for success, `false` for failure
-  status: bool,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
-}
-
 /// Fetch Document info from document relative path (ie. relative to app root dir)
 #[tauri::command]
 pub async fn fetch_doc_info(
   relative_path: String,
   state: tauri::State<'_, AppState>,
-) -> Result<FetchDocInfoResponse, String> {
+) -> Result<fsutils::FileMetaInfo, CommandError> {
   info!("fetch_doc_info() -> relative_path: {}", relative_path);
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(FetchDocInfoResponse {
-      file_meta_info: None,
-      status: false,
-      retry: true,
-      message: "Cloud Sync in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(FetchDocInfoResponse {
-      file_meta_info: None,
-      status: false,
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
   let documents_dir = &state.dir_paths.documents;
-  // Using Relative path in an effort to achieve cross platform compatible/portable path resolution
-  let file_path = RelativePath::new(relative_path.as_str())
-    .normalize()
-    .to_path(documents_dir)
-    .to_owned();
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = true;
-  let file_meta_info =
-    fsutils::get_file_meta_from_path(documents_dir, &file_path).map_err(error_to_string)?;
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = false;
-  Ok(FetchDocInfoResponse {
-    file_meta_info: Some(file_meta_info),
-    status: true,
-    retry: false,
-    message: "Success".to_string(),
-  })
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct FetchAllDocsInfoResponse {
-  files_meta_info: Option<Vec<fsutils::FileMetaInfo>>,
-  /// `true` for success, `false` for failure
-  status: bool,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
+  let document_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let _lock = state
+    .locks
+    .try_read(&document_path.relative_path, "fetch_doc_info")?;
+  let file_meta_info = fsutils::get_file_meta_from_path(documents_dir, &document_path.path)?;
+  Ok(file_meta_info)
 }
 
 /// Fetch Documents info from app root dir
 #[tauri::command]
 pub async fn fetch_all_docs_info(
   state: tauri::State<'_, AppState>,
-) -> Result<FetchAllDocsInfoResponse, String> {
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(FetchAllDocsInfoResponse {
-      files_meta_info: None,
-      status: false,
-      retry: true,
-      message: "Cloud Sync in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(FetchAllDocsInfoResponse {
-      files_meta_info: None,
-      status: false,
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
+) -> Result<Vec<fsutils::FileMetaInfo>, CommandError> {
   let documents_dir = &state.dir_paths.documents;
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = true;
-  let files_meta_info =
-    fsutils::get_all_files_meta_from_path(documents_dir.as_path()).map_err(error_to_string)?;
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = false;
-  Ok(FetchAllDocsInfoResponse {
-    files_meta_info: Some(files_meta_info),
-    status: true,
-    retry: false,
-    message: "Success".to_string(),
-  })
+  let _lock = state
+    .locks
+    .try_read(DOCUMENTS_ROOT, "fetch_all_docs_info")?;
+  let files_meta_info = fsutils::get_all_files_meta_from_path(documents_dir.as_path())?;
+  Ok(files_meta_info)
+}
+
+/// Fetch the nested tree of all folders (including empty ones) and documents
+#[tauri::command]
+pub async fn fetch_docs_tree(
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<fsutils::DocsTreeNode>, CommandError> {
+  let documents_dir = &state.dir_paths.documents;
+  let _lock = state.locks.try_read(DOCUMENTS_ROOT, "fetch_docs_tree")?;
+  let docs_tree = fsutils::get_docs_tree_from_path(documents_dir.as_path())?;
+  Ok(docs_tree)
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReadDocumentResponse {
-  /// `true` for success, `false` for failure
-  status: bool,
   /// Document Response content
   content: String,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
+  /// Modified time of the document (pass to `write_document` as `expected_modified`)
+  modified: Option<String>,
+  /// SHA-256 hash of the content (pass to `write_document` as `expected_content_hash`)
+  content_hash: String,
 }
 
 /// Read Document on the specified relative path
@@ -149,233 +77,252 @@ pub struct ReadDocumentResponse {
 pub async fn read_document(
   relative_path: String,
   state: tauri::State<'_, AppState>,
-) -> Result<ReadDocumentResponse, String> {
+) -> Result<ReadDocumentResponse, CommandError> {
   info!("read_document() -> relative_path: {}", relative_path);
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(ReadDocumentResponse {
-      status: false,
-      content: "".to_string(),
-      retry: true,
-      message: "Cloud Sync in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(ReadDocumentResponse {
-      status: false,
-      content: "".to_string(),
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
   let documents_dir = &state.dir_paths.documents;
-  // Using Relative path in an effort to achieve cross platform compatible/portable path resolution
-  let file_path = RelativePath::new(relative_path.as_str())
-    .normalize()
-    .to_path(documents_dir)
-    .to_owned();
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = true;
-  let content = fsutils::read_from_path(file_path).map_err(error_to_string)?;
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = false;
+  let document_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let file_path = document_path.path.clone();
+  let _lock = state
+    .locks
+    .try_read(&document_path.relative_path, "read_document")?;
+  let modified = fsutils::get_modified_from_path(&file_path);
+  let content = fsutils::read_from_path(&file_path)?;
+  let content_hash = fsutils::get_content_hash(&content);
   Ok(ReadDocumentResponse {
-    status: true,
     content,
-    retry: false,
-    message: "Success".to_string(),
+    modified,
+    content_hash,
   })
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WriteDocumentResponse {
-  /// `true` for success, `false` for failure
-  status: bool,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
+  /// Modified time of the written document
+  modified: Option<String>,
+  /// SHA-256 hash of the written content
+  content_hash: String,
 }
 
 /// Write Document to the specified relative path
+/// - `expected_modified`/`expected_content_hash` (from `read_document`):
+///   when set, the document is only written if it did not change on disk,
+///   otherwise fails with `WRITE_CONFLICT` (details: `fsutils::WriteConflict`).
 #[tauri::command]
 pub async fn write_document(
   relative_path: String,
   content: String,
+  expected_modified: Option<String>,
+  expected_content_hash: Option<String>,
   state: tauri::State<'_, AppState>,
-) -> Result<WriteDocumentResponse, String> {
+) -> Result<WriteDocumentResponse, CommandError> {
   info!("write_document() -> relative_path: {}", relative_path);
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(WriteDocumentResponse {
-      status: false,
-      retry: true,
-      message: "Cloud Sync in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(WriteDocumentResponse {
-      status: false,
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
   let documents_dir = &state.dir_paths.documents;
-  // Using Relative path in an effort to achieve cross platform compatible/portable path resolution
-  let file_path = RelativePath::new(relative_path.as_str())
-    .normalize()
-    .to_path(documents_dir)
-    .to_owned();
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = true;
-  fsutils::write_to_path(file_path.as_path(), content).map_err(error_to_string)?;
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = false;
+  let document_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let file_path = document_path.path.clone();
+  let _lock = state
+    .locks
+    .try_write(&document_path.relative_path, "write_document")?;
+  let content_hash = fsutils::get_content_hash(&content);
+  let conflict = fsutils::check_write_preconditions(
+    &file_path,
+    expected_modified.as_deref(),
+    expected_content_hash.as_deref(),
+  )?;
+  if let Some(conflict) = conflict {
+    info!("write_document() -> conflict: document changed on disk!");
+    return Err(conflict.into());
+  }
+  fsutils::write_to_path(file_path.as_path(), content)?;
+  update_search_index(&state.search_index, |index| {
+    index.index_file(documents_dir, document_path.relative_path.as_str())
+  });
+  state.sync_scheduler.notify_document_changed();
   Ok(WriteDocumentResponse {
-    status: true,
-    retry: false,
-    message: "Success".to_string(),
+    modified: fsutils::get_modified_from_path(&file_path),
+    content_hash,
   })
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct RemoveDocumentResponse {
-  /// `true` for success, `false` for failure
-  status: bool,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
+pub struct UpdateFrontMatterResponse {
+  /// Front matter after the update
+  front_matter: FrontMatter,
+  /// Modified time of the written document
+  modified: Option<String>,
+  /// SHA-256 hash of the written content
+  content_hash: String,
+}
+
+/// Update the Front Matter of the Document on the specified relative path
+/// - `updates`: keys to set (`null` removes the key), other keys and the body are kept.
+/// - A YAML front matter is added if the document has none.
+#[tauri::command]
+pub async fn update_front_matter(
+  relative_path: String,
+  updates: Map<String, Value>,
+  state: tauri::State<'_, AppState>,
+) -> Result<UpdateFrontMatterResponse, CommandError> {
+  info!(
+    "update_front_matter() -> relative_path: {}, keys: {:?}",
+    relative_path,
+    updates.keys().collect::<Vec<_>>()
+  );
+  let documents_dir = &state.dir_paths.documents;
+  let document_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let file_path = document_path.path.clone();
+  let _lock = state
+    .locks
+    .try_write(&document_path.relative_path, "update_front_matter")?;
+  let content = fsutils::read_from_path(&file_path)?;
+  let (content, front_matter) = set_front_matter_keys(&content, updates)
+    .map_err(|e| CommandError::user(ErrorCode::InvalidInput, format!("{:#}", e)))?;
+  let content_hash = fsutils::get_content_hash(&content);
+  fsutils::write_to_path(file_path.as_path(), content)?;
+  update_search_index(&state.search_index, |index| {
+    index.index_file(documents_dir, document_path.relative_path.as_str())
+  });
+  state.sync_scheduler.notify_document_changed();
+  Ok(UpdateFrontMatterResponse {
+    front_matter,
+    modified: fsutils::get_modified_from_path(&file_path),
+    content_hash,
+  })
 }
 
 /// Remove/Delete Document on the specified relative path
+/// - The document is moved to the trash (see `list_trash`, `restore_document`).
+/// - Returns the trash entry of the removed document (use to restore it).
 #[tauri::command]
 pub async fn remove_document(
   relative_path: String,
   state: tauri::State<'_, AppState>,
-) -> Result<RemoveDocumentResponse, String> {
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<TrashEntry, CommandError> {
   info!("remove_document() -> relative_path: {}", relative_path);
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(RemoveDocumentResponse {
-      status: false,
-      retry: true,
-      message: "Cloud Sync in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(RemoveDocumentResponse {
-      status: false,
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
   let documents_dir = &state.dir_paths.documents;
-  // Using Relative path in an effort to achieve cross platform compatible/portable path resolution
-  let file_path = RelativePath::new(relative_path.as_str())
-    .normalize()
-    .to_path(documents_dir)
-    .to_owned();
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = true;
-  fsutils::remove_from_path(file_path.as_path()).map_err(error_to_string)?;
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = false;
-  Ok(RemoveDocumentResponse {
-    status: true,
-    retry: false,
-    message: "Success".to_string(),
-  })
+  let document_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let _lock = state
+    .locks
+    .try_write(&document_path.relative_path, "remove_document")?;
+  let mut db = db_state.db.lock()?;
+  let trash_entry = Trash::trash_document(&state.dir_paths, &mut db, &document_path)?;
+  update_search_index(&state.search_index, |index| {
+    index.remove_document(document_path.relative_path.as_str());
+    Ok(())
+  });
+  state.sync_scheduler.notify_document_changed();
+  Ok(trash_entry)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct RenameDocumentResponse {
-  /// `true` for success, `false` for failure
-  status: bool,
-  /// Available to Retry the API. Use this to
-  /// allow multiple retries from the client.
-  retry: bool,
-  /// Success/Error message
-  message: String,
+/// Preview (`preview`) or apply the `relocation` of a document/folder
+/// - All documents are locked as links may be rewritten in any of them.
+pub(crate) fn relocate(
+  relocation: Relocation,
+  preview: bool,
+  owner: &str,
+  state: &AppState,
+  db_state: &AppDbState,
+) -> Result<RelocationReport, CommandError> {
+  if preview {
+    let _lock = state.locks.try_read(DOCUMENTS_ROOT, owner)?;
+    return Ok(relocation.preview(&state.dir_paths.documents)?);
+  }
+  let _lock = state.locks.try_write(DOCUMENTS_ROOT, owner)?;
+  let mut db = db_state.db.lock()?;
+  Ok(relocation.apply(state, &mut db)?)
+}
+
+/// Resolve the document on `relative_path`, fails if there is no such document
+fn resolve_document(
+  documents_dir: &Path,
+  relative_path: &str,
+) -> Result<DocumentPath, CommandError> {
+  let document_path = DocumentPath::resolve(documents_dir, relative_path)?;
+  if !document_path.path.is_file() {
+    return Err(CommandError::user(
+      ErrorCode::NotFound,
+      format!("`{}` is not a document!", relative_path),
+    ));
+  }
+  Ok(document_path)
 }
 
 /// Rename Document on the specified relative path
+/// - Links to the document are rewritten in all documents.
+/// - `preview`: only report the links that would be rewritten (default `false`).
+/// - Returns the report, its `undoId` undoes the rename (see `undo_relocation`).
 #[tauri::command]
 pub async fn rename_document(
   relative_path: String,
   new_document_name: String,
+  preview: Option<bool>,
   state: tauri::State<'_, AppState>,
-) -> Result<RenameDocumentResponse, String> {
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<RelocationReport, CommandError> {
   info!(
     "rename_document() -> relative_path: {}, new_document_name: {}",
     relative_path, new_document_name
   );
-  let (cloud_sync_is_syncing, fs_sync_is_syncing) =
-    check_cloud_or_fs_is_syncing(state.inner().to_owned()).map_err(error_to_string)?;
-  if cloud_sync_is_syncing {
-    return Ok(RenameDocumentResponse {
-      status: false,
-      retry: true,
-      message: "Cloud Sync in progress, Please re-try after some time!".to_string(),
-    });
-  } else if fs_sync_is_syncing {
-    return Ok(RenameDocumentResponse {
-      status: false,
-      retry: true,
-      message: "FileSystem sync in progress, Please re-try after some time!".to_string(),
-    });
-  }
   let documents_dir = &state.dir_paths.documents;
-  // Using Relative path in an effort to achieve cross platform compatible/portable path resolution
-  let file_path = RelativePath::new(relative_path.as_str())
-    .normalize()
-    .to_path(documents_dir)
-    .to_owned();
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = true;
-  fsutils::rename_file(file_path.as_path(), new_document_name).map_err(error_to_string)?;
-  *state
-    .inner()
-    .to_owned()
-    .fs_sync_is_syncing
-    .lock()
-    .map_err(error_to_string)? = false;
-  Ok(RenameDocumentResponse {
-    status: true,
-    retry: false,
-    message: "Success".to_string(),
-  })
+  let document_path = resolve_document(documents_dir, &relative_path)?;
+  let new_document_path = document_path.with_file_name(documents_dir, &new_document_name)?;
+  relocate(
+    Relocation::new(document_path, new_document_path),
+    preview.unwrap_or(false),
+    "rename_document",
+    &state,
+    &db_state,
+  )
+}
+
+/// Move Document on the specified relative path into the `target_folder`
+/// - `target_folder`: relative path of the folder to move into (`None` for the documents root)
+/// - Links to the document (and relative links in it) are rewritten in all documents.
+/// - `preview`: only report the links that would be rewritten (default `false`).
+/// - Returns the report (`newRelativePath`: path of the document after the move).
+#[tauri::command]
+pub async fn move_document(
+  relative_path: String,
+  target_folder: Option<String>,
+  preview: Option<bool>,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<RelocationReport, CommandError> {
+  info!(
+    "move_document() -> relative_path: {}, target_folder: {:?}",
+    relative_path, target_folder
+  );
+  let documents_dir = &state.dir_paths.documents;
+  let document_path = resolve_document(documents_dir, &relative_path)?;
+  let new_document_path = document_path.moved_to(documents_dir, target_folder.as_deref())?;
+  relocate(
+    Relocation::new(document_path, new_document_path),
+    preview.unwrap_or(false),
+    "move_document",
+    &state,
+    &db_state,
+  )
+}
+
+/// Undo a rename/move of a document/folder (by the `undo_id` of its report)
+/// - Moves it back and restores the rewritten links, documents edited
+///   since are not restored (`skippedFiles`).
+#[tauri::command]
+pub async fn undo_relocation(
+  undo_id: String,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<RelocationReport, CommandError> {
+  info!("undo_relocation() -> undo_id: {}", undo_id);
+  let _lock = state.locks.try_write(DOCUMENTS_ROOT, "undo_relocation")?;
+  let mut db = db_state.db.lock()?;
+  if !Relocation::can_undo(&db, &undo_id) {
+    return Err(CommandError::user(
+      ErrorCode::NotFound,
+      format!("Relocation `{}` can not be undone!", undo_id),
+    ));
+  }
+  Ok(Relocation::undo(&state, &mut db, &undo_id)?)
 }
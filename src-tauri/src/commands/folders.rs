@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use log::info;
+
+use crate::{
+  commands::docs::relocate,
+  models::{
+    app_db_state::AppDbState,
+    app_state::AppState,
+    command_error::{CommandError, ErrorCode},
+    relocation::{Relocation, RelocationReport},
+    trash::{Trash, TrashEntry},
+  },
+  utils::{document_path::DocumentPath, fsutils, search_index::update_search_index},
+};
+
+/// Create Folder on the specified relative path
+/// - `recursive`: also create missing parent folders (default `false`)
+#[tauri::command]
+pub async fn create_folder(
+  relative_path: String,
+  recursive: Option<bool>,
+  state: tauri::State<'_, AppState>,
+) -> Result<(), CommandError> {
+  info!("create_folder() -> relative_path: {}", relative_path);
+  let documents_dir = &state.dir_paths.documents;
+  let folder_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let _lock = state
+    .locks
+    .try_write(&folder_path.relative_path, "create_folder")?;
+  fsutils::create_dir(folder_path.path.as_path(), recursive.unwrap_or(false))?;
+  Ok(())
+}
+
+/// Resolve the folder on `relative_path`, fails if there is no such folder
+fn resolve_folder(documents_dir: &Path, relative_path: &str) -> Result<DocumentPath, CommandError> {
+  let folder_path = DocumentPath::resolve(documents_dir, relative_path)?;
+  if !folder_path.path.is_dir() {
+    return Err(CommandError::user(
+      ErrorCode::NotFound,
+      format!("`{}` is not a folder!", relative_path),
+    ));
+  }
+  Ok(folder_path)
+}
+
+/// Rename Folder on the specified relative path
+/// - Links to its documents/files are rewritten in all documents.
+/// - `preview`: only report the links that would be rewritten (default `false`).
+/// - Returns the report, its `undoId` undoes the rename (see `undo_relocation`).
+#[tauri::command]
+pub async fn rename_folder(
+  relative_path: String,
+  new_folder_name: String,
+  preview: Option<bool>,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<RelocationReport, CommandError> {
+  info!(
+    "rename_folder() -> relative_path: {}, new_folder_name: {}",
+    relative_path, new_folder_name
+  );
+  let documents_dir = &state.dir_paths.documents;
+  let folder_path = resolve_folder(documents_dir, &relative_path)?;
+  let new_folder_path = folder_path.with_file_name(documents_dir, &new_folder_name)?;
+  relocate(
+    Relocation::new(folder_path, new_folder_path),
+    preview.unwrap_or(false),
+    "rename_folder",
+    &state,
+    &db_state,
+  )
+}
+
+/// Move Folder on the specified relative path (with all its contents) into the `target_folder`
+/// - `target_folder`: relative path of the folder to move into (`None` for the documents root)
+/// - Links to its documents/files (and relative links in them) are rewritten in all documents.
+/// - `preview`: only report the links that would be rewritten (default `false`).
+/// - Returns the report (`newRelativePath`: path of the folder after the move).
+#[tauri::command]
+pub async fn move_folder(
+  relative_path: String,
+  target_folder: Option<String>,
+  preview: Option<bool>,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<RelocationReport, CommandError> {
+  info!(
+    "move_folder() -> relative_path: {}, target_folder: {:?}",
+    relative_path, target_folder
+  );
+  let documents_dir = &state.dir_paths.documents;
+  let folder_path = resolve_folder(documents_dir, &relative_path)?;
+  let new_folder_path = folder_path.moved_to(documents_dir, target_folder.as_deref())?;
+  relocate(
+    Relocation::new(folder_path, new_folder_path),
+    preview.unwrap_or(false),
+    "move_folder",
+    &state,
+    &db_state,
+  )
+}
+
+/// Remove/Delete Folder on the specified relative path
+/// - `recursive`: move the folder with all its documents/folders to the trash
+///   (restorable as a whole, see `restore_document`), else only an empty folder is removed
+/// - Returns the trash entry of the removed folder (`None` for an empty folder, removed for good)
+#[tauri::command]
+pub async fn remove_folder(
+  relative_path: String,
+  recursive: bool,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<Option<TrashEntry>, CommandError> {
+  info!(
+    "remove_folder() -> relative_path: {}, recursive: {}",
+    relative_path, recursive
+  );
+  let documents_dir = &state.dir_paths.documents;
+  let folder_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let _lock = state
+    .locks
+    .try_write(&folder_path.relative_path, "remove_folder")?;
+  let trash_entry = if recursive {
+    let mut db = db_state.db.lock()?;
+    Some(Trash::trash_folder(
+      &state.dir_paths,
+      &mut db,
+      &folder_path,
+    )?)
+  } else {
+    fsutils::remove_dir(&folder_path.path, false)?;
+    None
+  };
+  update_search_index(&state.search_index, |index| {
+    index.remove_folder(folder_path.relative_path.as_str());
+    Ok(())
+  });
+  state.sync_scheduler.notify_document_changed();
+  Ok(trash_entry)
+}
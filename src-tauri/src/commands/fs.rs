@@ -1,34 +1,28 @@
-use relative_path::RelativePath;
-use serde::{Deserialize, Serialize};
+use log::info;
 
 use crate::{
-  models::app_state::AppState,
-  utils::{error::error_to_string, fsutils},
+  models::{app_state::AppState, command_error::CommandError},
+  utils::{document_path::DocumentPath, fsutils, search_index::update_search_index},
 };
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SaveFileToResponse {
-  status: bool,
-  message: String,
-}
-
 /// Save File to Command
+/// - Locks and indexes the file like `write_document` (and triggers the auto-sync).
 #[tauri::command]
 pub fn save_file_to(
   save_path: String,
   file_data: String,
   state: tauri::State<'_, AppState>,
-) -> Result<SaveFileToResponse, String> {
+) -> Result<(), CommandError> {
+  info!("save_file_to() -> save_path: {}", save_path);
   let documents_dir = &state.dir_paths.documents;
-  // Using Relative path in an effort to achieve cross platform compatible/portable path resolution
-  let save_path = RelativePath::new(save_path.as_str())
-    .normalize()
-    .to_path(documents_dir)
-    .to_owned();
-  fsutils::write_to_path(save_path.as_path(), file_data).map_err(error_to_string)?;
-  Ok(SaveFileToResponse {
-    status: true,
-    message: "Success".to_string(),
-  })
+  let document_path = DocumentPath::resolve(documents_dir, &save_path)?;
+  let _lock = state
+    .locks
+    .try_write(&document_path.relative_path, "save_file_to")?;
+  fsutils::write_to_path(document_path.path.as_path(), file_data)?;
+  update_search_index(&state.search_index, |index| {
+    index.index_file(documents_dir, document_path.relative_path.as_str())
+  });
+  state.sync_scheduler.notify_document_changed();
+  Ok(())
 }
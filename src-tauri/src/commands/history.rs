@@ -0,0 +1,88 @@
+use log::info;
+
+use crate::{
+  models::{
+    app_state::AppState,
+    command_error::CommandError,
+    document_history::DocumentHistory,
+    document_revision::{DocumentAtRevision, DocumentRevision},
+  },
+  utils::{
+    document_path::DocumentPath, fsutils, lock_manager::LockMode, search_index::update_search_index,
+  },
+};
+
+/// Default max number of revisions returned
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// # Document History
+///
+/// List the commits that changed the document on the specified relative path (newest first).
+/// - Renames are followed (see `relativePath` of each revision).
+#[tauri::command]
+pub async fn document_history(
+  relative_path: String,
+  limit: Option<usize>,
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<DocumentRevision>, CommandError> {
+  info!("document_history() -> relative_path: {}", relative_path);
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Read, "document_history")?;
+  let document_path = DocumentPath::resolve(&state.dir_paths.documents, &relative_path)?;
+  let revisions = DocumentHistory::revisions(
+    state.inner(),
+    &document_path,
+    limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+  )?;
+  Ok(revisions)
+}
+
+/// # Document At Revision
+///
+/// Get the content of the document on the specified relative path in the commit `commit_id`.
+#[tauri::command]
+pub async fn document_at_revision(
+  relative_path: String,
+  commit_id: String,
+  state: tauri::State<'_, AppState>,
+) -> Result<DocumentAtRevision, CommandError> {
+  info!(
+    "document_at_revision() -> relative_path: {}, commit_id: {}",
+    relative_path, commit_id
+  );
+  let _lock = state
+    .locks
+    .try_cloud_sync(LockMode::Read, "document_at_revision")?;
+  let document_path = DocumentPath::resolve(&state.dir_paths.documents, &relative_path)?;
+  let document = DocumentHistory::content_at(state.inner(), &document_path, &commit_id)?;
+  Ok(document)
+}
+
+/// # Restore Document Revision
+///
+/// Write the content of the document in the commit `commit_id` back to the
+/// document on the specified relative path (as a new change, history is kept).
+#[tauri::command]
+pub async fn restore_document_revision(
+  relative_path: String,
+  commit_id: String,
+  state: tauri::State<'_, AppState>,
+) -> Result<(), CommandError> {
+  info!(
+    "restore_document_revision() -> relative_path: {}, commit_id: {}",
+    relative_path, commit_id
+  );
+  let documents_dir = &state.dir_paths.documents;
+  let document_path = DocumentPath::resolve(documents_dir, &relative_path)?;
+  let _lock = state
+    .locks
+    .try_write(&document_path.relative_path, "restore_document_revision")?;
+  let document = DocumentHistory::content_at(state.inner(), &document_path, &commit_id)?;
+  fsutils::write_to_path(document_path.path.as_path(), document.content)?;
+  update_search_index(&state.search_index, |index| {
+    index.index_file(documents_dir, document_path.relative_path.as_str())
+  });
+  state.sync_scheduler.notify_document_changed();
+  Ok(())
+}
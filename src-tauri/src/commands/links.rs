@@ -0,0 +1,71 @@
+use log::info;
+
+use crate::{
+  models::{
+    app_state::AppState,
+    command_error::{CommandError, ErrorCode},
+  },
+  utils::{document_path::DocumentPath, link_graph::ResolvedLink},
+};
+
+/// Resolve the document on `relative_path`, fails if there is no such document
+fn resolve_document(state: &AppState, relative_path: &str) -> Result<DocumentPath, CommandError> {
+  let document_path = DocumentPath::resolve(&state.dir_paths.documents, relative_path)?;
+  if !document_path.path.is_file() {
+    return Err(CommandError::user(
+      ErrorCode::NotFound,
+      format!("`{}` is not a document!", relative_path),
+    ));
+  }
+  Ok(document_path)
+}
+
+/// # Get Backlinks
+///
+/// Links (wiki/markdown) from other documents to the document on `relative_path`.
+/// - Sorted by linking document, then line.
+#[tauri::command]
+pub async fn get_backlinks(
+  relative_path: String,
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<ResolvedLink>, CommandError> {
+  info!("get_backlinks() -> relative_path: {}", relative_path);
+  let document_path = resolve_document(&state, &relative_path)?;
+  let backlinks = state
+    .search_index
+    .lock()?
+    .links()
+    .backlinks(document_path.relative_path.as_str());
+  Ok(backlinks)
+}
+
+/// # Get Outgoing Links
+///
+/// Document links in the document on `relative_path`, in document order
+/// (`resolvedPath` is `null` for links to missing documents).
+#[tauri::command]
+pub async fn get_outgoing_links(
+  relative_path: String,
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<ResolvedLink>, CommandError> {
+  info!("get_outgoing_links() -> relative_path: {}", relative_path);
+  let document_path = resolve_document(&state, &relative_path)?;
+  let outgoing_links = state
+    .search_index
+    .lock()?
+    .links()
+    .outgoing_links(document_path.relative_path.as_str());
+  Ok(outgoing_links)
+}
+
+/// # Get Unresolved Links
+///
+/// Document links (in all documents) to documents that don't exist.
+/// - Sorted by linking document, then line.
+#[tauri::command]
+pub async fn get_unresolved_links(
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<ResolvedLink>, CommandError> {
+  let unresolved_links = state.search_index.lock()?.links().unresolved_links();
+  Ok(unresolved_links)
+}
@@ -0,0 +1,14 @@
+use crate::{
+  models::{app_state::AppState, command_error::CommandError},
+  utils::lock_manager::LockHolder,
+};
+
+/// # Get Lock Holders
+///
+/// Diagnostics: list the held document/cloud sync locks and who holds them.
+#[tauri::command]
+pub async fn get_lock_holders(
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<LockHolder>, CommandError> {
+  Ok(state.locks.holders())
+}
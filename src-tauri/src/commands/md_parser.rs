@@ -1,28 +1,361 @@
-use comrak::{markdown_to_html, ComrakOptions};
+use comrak::{markdown_to_html_with_plugins, ComrakOptions, ComrakPlugins};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+  models::{
+    app_state::AppState,
+    command_error::{CommandError, ErrorCode},
+  },
+  utils::{
+    code_highlighter::{
+      code_theme_css, code_theme_names, is_highlight_style, CodeHighlightMode, CodeHighlighter,
+      DEFAULT_CODE_THEME,
+    },
+    document_path::DocumentPath,
+    front_matter::{document_body, parse_front_matter, FrontMatter},
+    fsutils,
+    html::escape_html,
+    link_graph::{parse_links, Link, LinkKind, LinkResolver},
+    outline::{heading_slug, Heading, Outline},
+  },
+};
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MdResponse {
   markup: String,
+  /// Front matter of the document (not rendered), `None` if missing/invalid
+  front_matter: Option<FrontMatter>,
 }
 
 /// Parse/Convert Markdown string into HTML Markup string
+/// - Fenced code blocks are syntax highlighted with the `code_theme`
+/// (see `get_code_themes`), styled inline or with CSS classes
+/// (`code_highlight`, see `get_code_theme_css`).
+/// - YAML (`---`)/TOML (`+++`) front matter is returned as JSON instead of being rendered.
+/// - Wiki links and relative `.md` links become in-app links to the linked document,
+///   resolved from the document on `relative_path` (see `link_documents`).
+/// - Headings get an `id` (their slug, see `get_document_outline`).
+/// - With `insert_toc`, `[TOC]` lines are replaced by a table of contents
+///   (`<nav class="toc">` with nested lists of links to the headings).
 #[tauri::command]
-pub async fn parse_md_to_mu(md_string: String) -> MdResponse {
+pub async fn parse_md_to_mu(
+  md_string: String,
+  code_theme: Option<String>,
+  code_highlight: Option<CodeHighlightMode>,
+  relative_path: Option<String>,
+  insert_toc: Option<bool>,
+  state: tauri::State<'_, AppState>,
+) -> Result<MdResponse, CommandError> {
+  let front_matter = parse_front_matter(&md_string).unwrap_or_else(|e| {
+    warn!("parse_md_to_mu() -> invalid front matter: {:#}", e);
+    None
+  });
+  let source = match relative_path {
+    Some(relative_path) => DocumentPath::resolve(&state.dir_paths.documents, &relative_path)?
+      .relative_path
+      .to_string(),
+    None => String::new(),
+  };
+  let md_string = {
+    let search_index = state.search_index.lock()?;
+    link_documents(&md_string, &source, &search_index.links().resolver())
+  };
+  let comrak_options = comrak_options();
+  let md_string = if insert_toc.unwrap_or(false) {
+    insert_table_of_contents(&md_string, &Outline::parse(&md_string, &comrak_options))
+  } else {
+    md_string
+  };
+  let code_highlighter =
+    CodeHighlighter::new(code_highlight.unwrap_or_default(), code_theme.as_deref());
+  let mut comrak_plugins = ComrakPlugins::default();
+  comrak_plugins.render.codefence_syntax_highlighter = Some(&code_highlighter);
+  let unsafe_mu_string =
+    markdown_to_html_with_plugins(document_body(&md_string), &comrak_options, &comrak_plugins);
+  let safe_mu_string = sanitize_html(&unsafe_mu_string);
+  Ok(MdResponse {
+    markup: safe_mu_string,
+    front_matter,
+  })
+}
+
+/// Remove the unsafe tags/attributes from the rendered `html`
+/// - Only keeps the `style` attributes generated by the code highlighter.
+fn sanitize_html(html: &str) -> String {
+  ammonia::Builder::new()
+    .add_tag_attributes("code", &["class"]) // Allow class on <code> tag (needed for code syntax highlighting)
+    .add_tag_attributes("pre", &["class", "style"]) // Highlighted code block background
+    .add_tag_attributes("span", &["class", "style"]) // Highlighted code tokens
+    .add_tag_attributes(
+      "a",
+      &[
+        "class",
+        "data-document-path",
+        "data-anchor",
+        "data-link-target",
+        "id",
+        "aria-hidden",
+      ],
+    ) // Document links, heading anchors
+    .add_tag_attributes("nav", &["class"]) // Table of contents
+    .attribute_filter(|_element, attribute, value| match attribute {
+      "style" if !is_highlight_style(value) => None, // Only keep the highlighter's colors/fonts
+      _ => Some(value.into()),
+    })
+    .clean(html)
+    .to_string()
+}
+
+/// Options to parse/render the documents with
+fn comrak_options() -> ComrakOptions {
   let mut comrak_options = ComrakOptions::default();
   comrak_options.extension.autolink = true; // Auto detect links
   comrak_options.extension.table = true; // Detect tables
   comrak_options.extension.tasklist = true; // Detect Checklist
-  comrak_options.extension.front_matter_delimiter = Some("---".to_owned()); // Ignore front-mater starting with '---'
+  comrak_options.extension.header_ids = Some(String::new()); // Heading anchors (no id prefix)
   comrak_options.render.unsafe_ = true;
   comrak_options.render.hardbreaks = true;
-  let unsafe_mu_string = markdown_to_html(&md_string, &comrak_options);
-  let safe_mu_string = ammonia::Builder::new()
-    .add_tag_attributes("code", &["class"]) // Allow class on <code> tag (needed for code syntax highlighting)
-    .clean(&unsafe_mu_string)
-    .to_string();
-  MdResponse {
-    markup: safe_mu_string,
+  comrak_options
+}
+
+/// Replace the document links (wiki links, relative `.md` links) in `md_string`
+/// with in-app links (see `render_document_link`)
+/// - `source`: relative path of the document (links are resolved from it)
+fn link_documents(md_string: &str, source: &str, resolver: &LinkResolver) -> String {
+  let mut linked = String::with_capacity(md_string.len());
+  let mut cursor = 0;
+  for link in parse_links(md_string) {
+    let is_rendered_link = matches!(
+      link.kind,
+      LinkKind::Wiki | LinkKind::WikiEmbed | LinkKind::Markdown
+    );
+    // Links nested in an already replaced link are part of its label
+    if !is_rendered_link || !link.is_document_link() || link.range.start < cursor {
+      continue;
+    }
+    linked.push_str(&md_string[cursor..link.range.start]);
+    linked.push_str(&render_document_link(
+      &link,
+      resolver.resolve(source, &link).as_deref(),
+    ));
+    cursor = link.range.end;
+  }
+  linked.push_str(&md_string[cursor..]);
+  linked
+}
+
+/// Render a document link as an inline HTML link
+/// - Resolved: `<a class="document-link" data-document-path="notes/a.md" data-anchor="heading">`
+/// - `data-anchor` is the slug of the linked heading (its id once rendered)
+/// - Unresolved: `<a class="document-link unresolved" data-link-target="Missing note">`
+fn render_document_link(link: &Link, resolved_path: Option<&str>) -> String {
+  let label = match (link.kind, &link.label) {
+    // Markdown labels are still rendered as markdown (eg. `**bold**`)
+    (LinkKind::Markdown, Some(label)) => label.clone(),
+    (_, Some(label)) => escape_html(label),
+    (_, None) => escape_html(&match (&link.anchor, link.target.is_empty()) {
+      (Some(anchor), true) => anchor.clone(),
+      (Some(anchor), false) => format!("{} > {}", link.target, anchor),
+      (None, _) => link.target.clone(),
+    }),
+  };
+  let anchor = link.anchor.as_deref().map(heading_slug);
+  let anchor_attribute = anchor
+    .as_ref()
+    .map(|anchor| format!(r#" data-anchor="{}""#, escape_html(anchor)))
+    .unwrap_or_default();
+  match resolved_path {
+    Some(resolved_path) => {
+      let href = match &anchor {
+        Some(anchor) => format!("{}#{}", resolved_path, anchor),
+        None => resolved_path.to_string(),
+      };
+      format!(
+        r#"<a class="document-link" href="{}" data-document-path="{}"{}>{}</a>"#,
+        escape_html(&href),
+        escape_html(resolved_path),
+        anchor_attribute,
+        label
+      )
+    }
+    None => format!(
+      r##"<a class="document-link unresolved" href="#" data-link-target="{}"{}>{}</a>"##,
+      escape_html(&link.target),
+      anchor_attribute,
+      label
+    ),
+  }
+}
+
+/// Replace the `[TOC]` lines of `md_string` with the table of contents of its `outline`
+fn insert_table_of_contents(md_string: &str, outline: &Outline) -> String {
+  if outline.toc_lines.is_empty() {
+    return md_string.to_string();
+  }
+  let toc = format!(
+    r#"<nav class="toc">{}</nav>"#,
+    render_toc_list(&outline.headings)
+  );
+  md_string
+    .split_inclusive('\n')
+    .enumerate()
+    .map(|(index, line)| {
+      if outline.toc_lines.contains(&(index + 1)) {
+        format!("{}\n\n", toc) // The blank line ends the HTML block
+      } else {
+        line.to_string()
+      }
+    })
+    .collect()
+}
+
+/// Nested list of links to the `headings`
+fn render_toc_list(headings: &[Heading]) -> String {
+  if headings.is_empty() {
+    return String::new();
+  }
+  let items: String = headings
+    .iter()
+    .map(|heading| {
+      format!(
+        r##"<li><a href="#{}">{}</a>{}</li>"##,
+        escape_html(&heading.slug),
+        escape_html(&heading.text),
+        render_toc_list(&heading.children)
+      )
+    })
+    .collect();
+  format!("<ul>{}</ul>", items)
+}
+
+/// # Get Document Outline
+///
+/// Heading tree of the document on `relative_path` (for the sidebar outline).
+/// - `slug` is the id of the heading in `parse_md_to_mu`'s markup (to jump to the section).
+/// - `line` is the line of the heading in the document.
+#[tauri::command]
+pub async fn get_document_outline(
+  relative_path: String,
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<Heading>, CommandError> {
+  info!("get_document_outline() -> relative_path: {}", relative_path);
+  let document_path = DocumentPath::resolve(&state.dir_paths.documents, &relative_path)?;
+  if !document_path.path.is_file() {
+    return Err(CommandError::user(
+      ErrorCode::NotFound,
+      format!("`{}` is not a document!", relative_path),
+    ));
+  }
+  let content = {
+    let _lock = state
+      .locks
+      .try_read(&document_path.relative_path, "get_document_outline")?;
+    fsutils::read_from_path(&document_path.path)?
+  };
+  // Linked the same way as `parse_md_to_mu` so that the heading slugs match
+  let md_string = {
+    let search_index = state.search_index.lock()?;
+    link_documents(
+      &content,
+      document_path.relative_path.as_str(),
+      &search_index.links().resolver(),
+    )
+  };
+  Ok(Outline::parse(&md_string, &comrak_options()).headings)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeThemesResponse {
+  themes: Vec<String>,
+  default_theme: String,
+}
+
+/// Get the code themes available for `parse_md_to_mu`
+#[tauri::command]
+pub async fn get_code_themes() -> CodeThemesResponse {
+  CodeThemesResponse {
+    themes: code_theme_names(),
+    default_theme: DEFAULT_CODE_THEME.to_string(),
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeThemeCssResponse {
+  css: String,
+}
+
+/// Get the CSS of the `code_theme` for code highlighted with CSS classes
+/// (include it in the preview/exported HTML)
+#[tauri::command]
+pub async fn get_code_theme_css(code_theme: Option<String>) -> CodeThemeCssResponse {
+  CodeThemeCssResponse {
+    css: code_theme_css(code_theme.as_deref()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Render `md_string` with its code blocks highlighted in `mode` (not sanitized)
+  fn render(md_string: &str, mode: CodeHighlightMode) -> String {
+    let code_highlighter = CodeHighlighter::new(mode, None);
+    let mut comrak_plugins = ComrakPlugins::default();
+    comrak_plugins.render.codefence_syntax_highlighter = Some(&code_highlighter);
+    markdown_to_html_with_plugins(md_string, &comrak_options(), &comrak_plugins)
+  }
+
+  #[test]
+  fn keeps_highlighted_code() {
+    let md_string = "```rust\nfn main() {\n  let x = 1 < 2 && true;\n}\n```\n";
+    let inline = render(md_string, CodeHighlightMode::Inline);
+    assert!(
+      inline.starts_with("<pre style=\"background-color:#"),
+      "{}",
+      inline
+    );
+    assert!(
+      inline.contains("<span style=\"font-weight:bold;"),
+      "{}",
+      inline
+    );
+    assert_eq!(sanitize_html(&inline), inline);
+    let classes = render(md_string, CodeHighlightMode::Classes);
+    assert!(classes.starts_with("<pre class=\"code\">"), "{}", classes);
+    assert!(
+      classes.contains("<span class=\"source rust\">"),
+      "{}",
+      classes
+    );
+    assert_eq!(sanitize_html(&classes), classes);
+  }
+
+  #[test]
+  fn strips_other_styles() {
+    let html = concat!(
+      r#"<p style="color:#323232;">a</p>"#,
+      r#"<span style="color:red;position:fixed;top:0">b</span>"#,
+      r#"<pre style="background:url(x)">c</pre>"#,
+      r#"<span style="color:expression(alert(1))">d</span>"#,
+      r#"<span style="color:#323232;font-weight:bold;">e</span>"#,
+      r#"<script>alert(1)</script>"#,
+    );
+    assert_eq!(
+      sanitize_html(html),
+      concat!(
+        "<p>a</p><span>b</span><pre>c</pre><span>d</span>",
+        r#"<span style="color:#323232;font-weight:bold;">e</span>"#,
+      )
+    );
+    // raw HTML in the markdown goes through the same filter
+    let md_string = "<span style=\"position:fixed\">x</span> <span style=\"color:#fff\">y</span>\n";
+    assert_eq!(
+      sanitize_html(&render(md_string, CodeHighlightMode::Inline)),
+      "<p><span>x</span> <span style=\"color:#fff\">y</span></p>\n"
+    );
   }
 }
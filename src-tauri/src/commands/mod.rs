@@ -1,6 +1,13 @@
 pub mod cloud_sync;
+pub mod diff;
 pub mod docs;
 pub mod env;
+pub mod folders;
 pub mod fs;
+pub mod history;
+pub mod links;
+pub mod locks;
 pub mod md_parser;
+pub mod search;
 pub mod test_commands;
+pub mod trash;
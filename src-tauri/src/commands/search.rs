@@ -0,0 +1,30 @@
+use log::info;
+
+use crate::{
+  models::{app_state::AppState, command_error::CommandError},
+  utils::{lock_manager::DOCUMENTS_ROOT, search_index::SearchResult},
+};
+
+/// Default max number of search results
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// # Search Documents
+///
+/// Full-text search over all documents using the search index.
+/// - Words: `note`, prefixes: `not*`, phrases: `"my note"`
+/// - Filters: `dir:projects/work`, `modified:>2021-01-31` (an invalid filter is an `INVALID_INPUT` error)
+#[tauri::command]
+pub async fn search_documents(
+  query: String,
+  limit: Option<usize>,
+  state: tauri::State<'_, AppState>,
+) -> Result<Vec<SearchResult>, CommandError> {
+  info!("search_documents() -> query: {}", query);
+  let _lock = state.locks.try_read(DOCUMENTS_ROOT, "search_documents")?;
+  let results = state.search_index.lock()?.search(
+    &state.dir_paths.documents,
+    &query,
+    limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+  )?;
+  Ok(results)
+}
@@ -1,7 +1,10 @@
 use log::debug;
 use serde::{Deserialize, Serialize};
 
-use crate::models::{app_db_state::AppDbState, app_state::AppState, cloud_sync::CloudSync};
+use crate::{
+  models::{app_db_state::AppDbState, app_state::AppState},
+  utils::git_utils::GitUtils,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -23,7 +26,7 @@ pub fn my_custom_command(
   }
   debug!("Database: {:?}", db.get::<String>("message").unwrap());
   debug!("Git clone ssh...");
-  CloudSync::test_git_clone_ssh().unwrap();
+  GitUtils::test_git_clone_ssh().unwrap();
   debug!("Git clone ssh: Done!");
   Response { message }
 }
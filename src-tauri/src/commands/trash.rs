@@ -0,0 +1,113 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  models::{
+    app_db_state::AppDbState,
+    app_state::AppState,
+    command_error::{CommandError, ErrorCode},
+    trash::{Trash, TrashEntry},
+  },
+  utils::{
+    document_path::DocumentPath, lock_manager::DOCUMENTS_ROOT, search_index::update_search_index,
+  },
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTrashResponse {
+  trash_entries: Vec<TrashEntry>,
+  /// Number of days trashed documents are kept (`0` to keep them forever)
+  retention_days: u64,
+}
+
+/// List all trashed documents
+/// - Entries older than the retention policy are purged first.
+#[tauri::command]
+pub async fn list_trash(
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<ListTrashResponse, CommandError> {
+  let mut db = db_state.db.lock()?;
+  Trash::purge_expired(&state.dir_paths, &mut db)?;
+  Ok(ListTrashResponse {
+    trash_entries: Trash::entries(&db),
+    retention_days: Trash::retention_days(&db),
+  })
+}
+
+/// Restore the trashed document (or folder) with the `trash_id`
+/// - `relative_path`: where to restore to (defaults to the original path of the document)
+/// - Returns the relative path the document was restored to.
+#[tauri::command]
+pub async fn restore_document(
+  trash_id: String,
+  relative_path: Option<String>,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<String, CommandError> {
+  info!(
+    "restore_document() -> trash_id: {}, relative_path: {:?}",
+    trash_id, relative_path
+  );
+  let documents_dir = &state.dir_paths.documents;
+  let target_path = match relative_path {
+    Some(relative_path) => Some(DocumentPath::resolve(documents_dir, &relative_path)?),
+    None => None,
+  };
+  let _lock = state.locks.try_write(
+    target_path
+      .as_ref()
+      .map_or(DOCUMENTS_ROOT, |path| path.relative_path.as_str()),
+    "restore_document",
+  )?;
+  let mut db = db_state.db.lock()?;
+  if !Trash::entries(&db).iter().any(|entry| entry.id == trash_id) {
+    return Err(CommandError::user(
+      ErrorCode::NotFound,
+      format!("Trash entry `{}` not found!", trash_id),
+    ));
+  }
+  let document_path = Trash::restore(&state.dir_paths, &mut db, &trash_id, target_path)?;
+  update_search_index(&state.search_index, |index| {
+    if document_path.path.is_dir() {
+      index.refresh(documents_dir) // index all documents of the restored folder
+    } else {
+      index.index_file(documents_dir, document_path.relative_path.as_str())
+    }
+  });
+  state.sync_scheduler.notify_document_changed();
+  Ok(document_path.relative_path.to_string())
+}
+
+/// Permanently delete the trashed documents with the `trash_ids` (empty the trash if `None`)
+/// - Returns the purged entries.
+#[tauri::command]
+pub async fn purge_trash(
+  trash_ids: Option<Vec<String>>,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<Vec<TrashEntry>, CommandError> {
+  info!("purge_trash() -> trash_ids: {:?}", trash_ids);
+  let mut db = db_state.db.lock()?;
+  let purged_entries = Trash::purge(&state.dir_paths, &mut db, trash_ids.as_deref())?;
+  Ok(purged_entries)
+}
+
+/// Set the number of days trashed documents are kept (`0` to keep them forever)
+/// - Entries older than the new retention policy are purged right away.
+#[tauri::command]
+pub async fn set_trash_retention(
+  retention_days: u64,
+  state: tauri::State<'_, AppState>,
+  db_state: tauri::State<'_, AppDbState>,
+) -> Result<(), CommandError> {
+  info!(
+    "set_trash_retention() -> retention_days: {}",
+    retention_days
+  );
+  let mut db = db_state.db.lock()?;
+  Trash::set_retention_days(&mut db, retention_days)?;
+  Trash::purge_expired(&state.dir_paths, &mut db)?;
+  Ok(())
+}
@@ -0,0 +1,11 @@
+/// DB key for the cloud sync config (secrets are never stored).
+pub const CLOUD_SYNC_CONFIG_KEY: &str = "cloud_sync_config";
+
+/// DB key for the list of trashed documents.
+pub const TRASH_ENTRIES_KEY: &str = "trash_entries";
+
+/// DB key for the number of days trashed documents are kept.
+pub const TRASH_RETENTION_DAYS_KEY: &str = "trash_retention_days";
+
+/// DB key for the applied renames/moves (to undo them).
+pub const RELOCATIONS_KEY: &str = "relocations";
@@ -1,2 +1,3 @@
+pub mod app_metadata;
+pub mod db_keys;
 pub mod paths;
-pub mod app_metadata;
\ No newline at end of file
@@ -8,3 +8,7 @@ pub const APP_DB_FILE_NAME: &str = "store.db.json";
 pub const USER_DOCS_DIR_NAME: &str = "documents";
 /// Application Logs dir name
 pub const APP_LOGS_DIR_NAME: &str = "logs";
+/// Search index file name (stored in the db dir).
+pub const SEARCH_INDEX_FILE_NAME: &str = "search_index.json";
+/// Trash dir name (deleted documents, outside the synced documents dir).
+pub const APP_TRASH_DIR_NAME: &str = ".trash";
@@ -3,14 +3,32 @@
   windows_subsystem = "windows"
 )]
 
-use std::{process::exit, sync::{Arc, Mutex}};
+use std::{
+  process::exit,
+  sync::{Arc, Mutex},
+  thread,
+};
 
 use log::{error, info};
+use tauri::Manager;
 
 use crate::{
-  constants::paths::{APP_DB_DIR_NAME, APP_DB_FILE_NAME, APP_LOGS_DIR_NAME, USER_DOCS_DIR_NAME},
-  models::{app_db_state::AppDbState, app_dir_paths::AppDirPaths, app_state::AppState},
-  utils::{fsutils::get_app_root_dir_path, logger::MediocreLogger},
+  constants::paths::{
+    APP_DB_DIR_NAME, APP_DB_FILE_NAME, APP_LOGS_DIR_NAME, APP_TRASH_DIR_NAME,
+    SEARCH_INDEX_FILE_NAME, USER_DOCS_DIR_NAME,
+  },
+  models::{
+    app_db_state::AppDbState, app_dir_paths::AppDirPaths, app_state::AppState, trash::Trash,
+  },
+  utils::{
+    fs_watcher::DocumentsWatcher,
+    fsutils::get_app_root_dir_path,
+    lock_manager::LockManager,
+    logger::MediocreLogger,
+    search_index::{start_search_index_saver, update_search_index, SearchIndex},
+    sync_jobs::SyncJobs,
+    sync_scheduler::{SyncScheduler, SyncSchedulerHandle},
+  },
 };
 
 mod commands;
@@ -35,6 +53,7 @@ fn main() {
     documents: app_root_dir_path.join(USER_DOCS_DIR_NAME),
     db: app_root_dir_path.join(APP_DB_DIR_NAME),
     logs: app_root_dir_path.join(APP_LOGS_DIR_NAME),
+    trash: app_root_dir_path.join(APP_TRASH_DIR_NAME),
   };
 
   // Setup
@@ -49,30 +68,126 @@ fn main() {
   // Init logger
   MediocreLogger::init(&app_dir_paths.logs.join("app.log")).expect("failed to init logger!");
 
+  let (sync_scheduler, sync_scheduler_receiver) = SyncSchedulerHandle::new();
+  let search_index = Arc::new(Mutex::new(SearchIndex::load(
+    &app_dir_paths.db.join(SEARCH_INDEX_FILE_NAME),
+  )));
+  let app_state = AppState {
+    dir_paths: app_dir_paths.clone(),
+    locks: LockManager::new(),
+    git_auth_secret: Arc::new(Mutex::new(None)),
+    sync_scheduler: sync_scheduler.clone(),
+    search_index: search_index.clone(),
+    sync_jobs: SyncJobs::new(),
+  };
+  let app_db_state = AppDbState::new(&app_dir_paths.db.join(APP_DB_FILE_NAME));
+
+  // Purge trashed documents older than the retention policy
+  match app_db_state.db.lock() {
+    Ok(mut db) => {
+      if let Err(e) = Trash::purge_expired(&app_dir_paths, &mut db) {
+        error!("failed to purge expired trash entries: {:?}", e);
+      }
+    }
+    Err(e) => error!("db lock error: {}", e),
+  }
+
+  // Start background auto-sync scheduler
+  SyncScheduler::start(
+    sync_scheduler_receiver,
+    app_state.clone(),
+    app_db_state.db.clone(),
+  )
+  .expect("failed to start sync scheduler!");
+
+  // Save the search index updates in the background
+  start_search_index_saver(search_index.clone());
+
+  // Catch up the search index with changes made while the app was closed
+  let documents_dir = app_dir_paths.documents.clone();
+  thread::spawn(move || {
+    update_search_index(&search_index, |index| index.refresh(&documents_dir));
+  });
+
+  // Watch documents dir for external changes
+  let documents_watcher = match DocumentsWatcher::start(app_dir_paths.documents.clone()) {
+    Ok(handle) => Some(handle),
+    Err(e) => {
+      error!("failed to start documents watcher: {:?}", e);
+      None
+    }
+  };
+
   // Start Tauri
   info!("Starting Tauri backend...");
   tauri::Builder::default()
-    .manage(AppState {
-      dir_paths: app_dir_paths.clone(),
-      cloud_sync_is_syncing: Arc::new(Mutex::new(false)),
-      fs_sync_is_syncing: Arc::new(Mutex::new(false)),
+    .manage(app_state)
+    .manage(app_db_state)
+    .setup(move |app| {
+      // Scheduler + watcher send events via the main window
+      match app.get_window("main") {
+        Some(window) => {
+          if let Some(documents_watcher) = &documents_watcher {
+            documents_watcher.attach_window(window.clone());
+          }
+          sync_scheduler.attach_window(window);
+        }
+        None => error!("main window not found, auto-sync + documents watcher disabled!"),
+      }
+      Ok(())
     })
-    .manage(AppDbState::new(&app_dir_paths.db.join(APP_DB_FILE_NAME)))
     // This is where you pass in your commands
     .invoke_handler(tauri::generate_handler![
       commands::test_commands::my_custom_command,
       commands::md_parser::parse_md_to_mu,
+      commands::md_parser::get_code_themes,
+      commands::md_parser::get_code_theme_css,
+      commands::md_parser::get_document_outline,
       commands::env::get_env,
       commands::fs::save_file_to,
       commands::docs::fetch_doc_info,
       commands::docs::fetch_all_docs_info,
+      commands::docs::fetch_docs_tree,
       commands::docs::read_document,
       commands::docs::write_document,
+      commands::docs::update_front_matter,
       commands::docs::remove_document,
       commands::docs::rename_document,
+      commands::docs::move_document,
+      commands::docs::undo_relocation,
+      commands::folders::create_folder,
+      commands::folders::rename_folder,
+      commands::folders::move_folder,
+      commands::folders::remove_folder,
+      commands::history::document_history,
+      commands::history::document_at_revision,
+      commands::history::restore_document_revision,
+      commands::diff::diff_working_changes,
+      commands::diff::diff_remote_changes,
+      commands::diff::diff_document_revisions,
+      commands::trash::list_trash,
+      commands::trash::restore_document,
+      commands::trash::purge_trash,
+      commands::trash::set_trash_retention,
+      commands::search::search_documents,
+      commands::links::get_backlinks,
+      commands::links::get_outgoing_links,
+      commands::links::get_unresolved_links,
       commands::cloud_sync::test_git_clone_ssh,
       commands::cloud_sync::setup_git_cloud_sync,
+      commands::cloud_sync::set_git_cloud_sync_secret,
       commands::cloud_sync::sync_to_git_cloud,
+      commands::cloud_sync::preview_cloud_sync,
+      commands::cloud_sync::get_cloud_sync_conflicts,
+      commands::cloud_sync::resolve_cloud_sync_conflict,
+      commands::cloud_sync::complete_cloud_sync_merge,
+      commands::cloud_sync::cancel_cloud_sync,
+      commands::cloud_sync::get_sync_job_status,
+      commands::cloud_sync::get_cloud_sync_config,
+      commands::cloud_sync::update_cloud_sync_config,
+      commands::cloud_sync::switch_cloud_sync_branch,
+      commands::cloud_sync::disconnect_cloud_sync,
+      commands::locks::get_lock_holders,
     ])
     .run(tauri::generate_context!())
     .expect("failed to run app");
@@ -15,4 +15,7 @@ pub struct AppDirPaths {
   /// Path to the logs dir.
   /// - Usually set to: `~/.mediocre/logs`
   pub logs: PathBuf,
+  /// Path to the trash dir (deleted documents).
+  /// - Usually set to: `~/.mediocre/.trash`
+  pub trash: PathBuf,
 }
@@ -1,12 +1,26 @@
 use std::sync::{Arc, Mutex};
 
-use super::app_dir_paths::AppDirPaths;
+use crate::utils::{
+  lock_manager::LockManager, search_index::SearchIndex, sync_jobs::SyncJobs,
+  sync_scheduler::SyncSchedulerHandle,
+};
+
+use super::{app_dir_paths::AppDirPaths, git_auth::GitAuthSecret};
 
 /// State of the Application
 #[derive(Debug, Clone)]
 pub struct AppState {
   /// File System Paths pointing to app specific dirs
   pub dir_paths: AppDirPaths,
-  pub cloud_sync_is_syncing: Arc<Mutex<bool>>,
-  pub fs_sync_is_syncing: Arc<Mutex<bool>>,
+  /// Read/write locks on the documents (file system & cloud sync)
+  pub locks: LockManager,
+  /// Cloud sync auth secret (access token / key passphrase).
+  /// - Only kept in memory, never persisted.
+  pub git_auth_secret: Arc<Mutex<Option<GitAuthSecret>>>,
+  /// Handle to notify the background auto-sync scheduler
+  pub sync_scheduler: SyncSchedulerHandle,
+  /// Full-text search index over all documents
+  pub search_index: Arc<Mutex<SearchIndex>>,
+  /// Background cloud sync jobs (cancellable)
+  pub sync_jobs: SyncJobs,
 }
@@ -1,18 +1,33 @@
-use std::path::Path;
+use std::{
+  path::Path,
+  sync::{atomic::AtomicBool, Arc, Mutex, MutexGuard},
+};
 
 use anyhow::Result;
-use chrono::{SecondsFormat, Utc};
-use git2::{Cred, RemoteCallbacks};
-use log::debug;
+use log::warn;
 use pickledb::PickleDb;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::utils::{
-  git_utils::GitUtils,
+  commit_message::{generate_commit_message, DEFAULT_COMMIT_MESSAGE_TEMPLATE},
+  document_path::DocumentPath,
+  git_credentials::GitCredentialProvider,
+  git_utils::{GitUtils, MergeStatus, PushStatus, DEFAULT_BRANCH_NAME, DEFAULT_REMOTE_NAME},
+  search_index::update_search_index,
+  sync_progress_reporter::SyncProgressReporter,
   window_event_manager::{WindowEvent, WindowEventManager, WindowEventType},
 };
 
-use super::app_state::AppState;
+use super::{
+  app_state::AppState,
+  cloud_sync_config::{
+    CloudSyncConfig, DEFAULT_AUTO_SYNC_DEBOUNCE_SECS, DEFAULT_AUTO_SYNC_INTERVAL_SECS,
+  },
+  diff::FileChange,
+  git_auth::{GitAuthMethod, GitAuthSecret},
+  merge_conflict::{ConflictResolution, MergeConflict},
+  sync_preview::SyncPreview,
+};
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -20,32 +35,156 @@ pub struct CloudSyncPayload {
   pub message: &'static str,
 }
 
+/// Max number of push attempts when the remote rejects the push
+/// (eg. another device pushed meanwhile), new remote commits are pulled between attempts.
+const MAX_PUSH_ATTEMPTS: usize = 3;
+
+/// # Cloud Sync Status
+///
+/// Outcome of a cloud sync run.
+/// - `Pushed`: changes were pulled, committed and pushed.
+/// - `UpToDate`: changes were pulled, there was nothing to push.
+/// - `Rejected`: the remote kept rejecting the push (after pulling and retrying).
+/// - `Conflicted`: sync stopped on merge conflicts, these need to be
+/// resolved before the sync can complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CloudSyncStatus {
+  Pushed,
+  UpToDate,
+  Rejected,
+  Conflicted,
+}
+
 /// For syncing files/configs to cloud
 pub struct CloudSync<'weml> {
   wem: &'weml WindowEventManager<'weml>,
+  /// Set to `true` to abort the sync (see `with_cancel_flag`)
+  cancel_flag: Arc<AtomicBool>,
 }
 
 impl<'cs> CloudSync<'cs> {
   /// # New `CloudSync`
   /// Create a new `CloudSync` instance
   ///
-  /// - Using the given `git_sync_repo_url`.
-  /// - Also sets the url in the `DB` and `state`.
-  pub fn new(state: AppState, db: &mut PickleDb, wem: &'cs WindowEventManager) -> Result<Self> {
-    Ok(CloudSync { wem })
+  /// - Sync progress/events are sent via `wem`.
+  pub fn new(wem: &'cs WindowEventManager) -> Result<Self> {
+    Ok(CloudSync {
+      wem,
+      cancel_flag: Arc::new(AtomicBool::new(false)),
+    })
+  }
+
+  /// # With Cancel Flag
+  ///
+  /// Abort the sync with `SyncCancelled` once `cancel_flag` is set.
+  /// - Checked between the sync steps, fetch transfers are aborted midway.
+  /// - Already created commits are kept (pushed on the next sync).
+  pub fn with_cancel_flag(mut self, cancel_flag: Arc<AtomicBool>) -> Self {
+    self.cancel_flag = cancel_flag;
+    self
+  }
+
+  /// Lock the DB, only held briefly (never during network calls)
+  fn lock_db(db: &Mutex<PickleDb>) -> Result<MutexGuard<PickleDb>> {
+    db.lock().map_err(|e| anyhow::anyhow!(e.to_string()))
+  }
+
+  /// Load the repo with the credentials, progress reporter and cancel flag of the sync
+  fn load_git_utils(&self, state: &AppState, db: &Mutex<PickleDb>) -> Result<GitUtils> {
+    let credentials = Self::credentials(state, &*Self::lock_db(db)?)?;
+    Ok(
+      GitUtils::load(&state.dir_paths.root, credentials)?
+        .with_progress(self.progress_reporter())
+        .with_cancel_flag(self.cancel_flag.clone()),
+    )
+  }
+
+  /// # Credentials
+  ///
+  /// Get the credential provider for the auth method of the persisted
+  /// `CloudSyncConfig` (defaults to `ssh-agent`) and the in-memory auth secret.
+  pub fn credentials(state: &AppState, db: &PickleDb) -> Result<GitCredentialProvider> {
+    let auth_method = CloudSyncConfig::load(db)
+      .map(|config| config.auth_method)
+      .unwrap_or_default();
+    let auth_secret = state
+      .git_auth_secret
+      .lock()
+      .map_err(|e| anyhow::anyhow!(e.to_string()))?
+      .clone();
+    Ok(GitCredentialProvider::new(auth_method, auth_secret))
   }
 
   /// # Setup
   ///
   /// Setup sync with git remote
+  /// - Persists the `CloudSyncConfig` (and keeps the secret in memory) once the
+  /// repository is setup, a failed setup keeps the previous config and secret.
+  /// - Records the result of the setup in the config.
+  /// - `db` is only locked briefly, not during the network calls.
   pub fn setup(
     self,
-    mut state: AppState,
-    db: &mut PickleDb,
+    state: AppState,
+    db: &Mutex<PickleDb>,
     git_sync_repo_url: &str,
     git_sync_user_name: &str,
     git_sync_user_email: &str,
-  ) -> Result<()> {
+    git_sync_auth_method: GitAuthMethod,
+    git_sync_secret: Option<GitAuthSecret>,
+    git_sync_remote_name: Option<&str>,
+    git_sync_branch: Option<&str>,
+  ) -> Result<CloudSyncStatus> {
+    let previous_config = CloudSyncConfig::load(&*Self::lock_db(db)?);
+    let mut config = CloudSyncConfig {
+      remote_url: git_sync_repo_url.to_string(),
+      remote_name: git_sync_remote_name
+        .unwrap_or(DEFAULT_REMOTE_NAME)
+        .to_string(),
+      branch: None,
+      user_name: git_sync_user_name.to_string(),
+      user_email: git_sync_user_email.to_string(),
+      auth_method: git_sync_auth_method,
+      last_synced_at: None,
+      last_error: None,
+      auto_sync_enabled: false,
+      auto_sync_interval_secs: DEFAULT_AUTO_SYNC_INTERVAL_SECS,
+      auto_sync_debounce_secs: DEFAULT_AUTO_SYNC_DEBOUNCE_SECS,
+      // keep the template of a previous setup
+      commit_message_template: previous_config
+        .as_ref()
+        .and_then(|config| config.commit_message_template.clone()),
+    };
+    let credentials =
+      GitCredentialProvider::new(config.auth_method.clone(), git_sync_secret.clone());
+    let result = self.run_setup(&state, db, &mut config, credentials, git_sync_branch);
+    match &result {
+      // also when stopped on conflicts, they are resolved/pushed with the new config
+      Ok(_) => {
+        config.save(&mut *Self::lock_db(db)?)?;
+        *state
+          .git_auth_secret
+          .lock()
+          .map_err(|e| anyhow::anyhow!(e.to_string()))? = git_sync_secret;
+      }
+      Err(_) => {
+        if let Some(previous_config) = &previous_config {
+          Self::restore_remote(&state, previous_config);
+        }
+      }
+    }
+    CloudSyncConfig::record_sync_result(&mut *Self::lock_db(db)?, &result)?;
+    result
+  }
+
+  fn run_setup(
+    &self,
+    state: &AppState,
+    db: &Mutex<PickleDb>,
+    config: &mut CloudSyncConfig,
+    credentials: GitCredentialProvider,
+    git_sync_branch: Option<&str>,
+  ) -> Result<CloudSyncStatus> {
     self.wem.send(WindowEvent {
       name: "setup_cloud_sync",
       typ: WindowEventType::INFO,
@@ -53,16 +192,25 @@ impl<'cs> CloudSync<'cs> {
         message: "Creating new repository...",
       },
     })?;
-    *state
-      .cloud_sync_is_syncing
-      .lock()
-      .map_err(|e| anyhow::anyhow!(e.to_string()))? = true;
     let git_utils = GitUtils::new(
-      &git_sync_repo_url,
+      &config.remote_url,
       &state.dir_paths.root,
-      &git_sync_user_name,
-      &git_sync_user_email,
-    )?;
+      &config.remote_name,
+      git_sync_branch.unwrap_or(DEFAULT_BRANCH_NAME),
+      &config.user_name,
+      &config.user_email,
+      credentials,
+    )?
+    .with_progress(self.progress_reporter())
+    .with_cancel_flag(self.cancel_flag.clone());
+    // an existing repo keeps its branch unless another one is asked for
+    if let Some(branch_name) = git_sync_branch {
+      if git_utils.get_branch_name()? != branch_name {
+        Self::commit_documents(state, db, &git_utils)?;
+        git_utils.switch_branch(branch_name)?;
+      }
+    }
+    config.branch = git_utils.get_branch_name().ok();
     self.wem.send(WindowEvent {
       name: "setup_cloud_sync",
       typ: WindowEventType::INFO,
@@ -70,14 +218,11 @@ impl<'cs> CloudSync<'cs> {
         message: "Pulling changes...",
       },
     })?;
-    git_utils.pull()?; // Pull the repo
-    let mut dirs = vec![];
-    // Get relative path as only relative paths to repo root are supported
-    let document_relative_path = state
-      .dir_paths
-      .documents
-      .strip_prefix(state.dir_paths.root)?;
-    dirs.push(document_relative_path); // add documents dir to be tracked
+    if git_utils.pull()? == MergeStatus::Conflicted {
+      // Pull the repo
+      return self.stop_on_conflicts("setup_cloud_sync");
+    }
+    Self::refresh_search_index(state);
     self.wem.send(WindowEvent {
       name: "setup_cloud_sync",
       typ: WindowEventType::INFO,
@@ -85,15 +230,7 @@ impl<'cs> CloudSync<'cs> {
         message: "Adding Commits...",
       },
     })?;
-    git_utils.add_commit(
-      // Commit changes
-      dirs,
-      format!(
-        "Commit Changes, time: {}",
-        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
-      )
-      .as_str(),
-    )?;
+    Self::commit_documents(state, db, &git_utils)?;
     self.wem.send(WindowEvent {
       name: "setup_cloud_sync",
       typ: WindowEventType::INFO,
@@ -101,25 +238,48 @@ impl<'cs> CloudSync<'cs> {
         message: "Pushing changes to remote...",
       },
     })?;
-    git_utils.push()?; // Push Changes to remote
-    *state
-      .cloud_sync_is_syncing
-      .lock()
-      .map_err(|e| anyhow::anyhow!(e.to_string()))? = false;
-    self.wem.send(WindowEvent {
-      name: "setup_cloud_sync",
-      typ: WindowEventType::INFO,
-      data: CloudSyncPayload {
-        message: "Success!",
-      },
-    })?;
-    Ok(())
+    let status = self.push_with_retry(state, &git_utils, "setup_cloud_sync")?;
+    if status == CloudSyncStatus::Conflicted {
+      return Ok(status);
+    }
+    self.finish_push(status, "setup_cloud_sync")
+  }
+
+  /// Point the repository back to the remote (and author) of the `previous_config`
+  /// after a failed setup
+  fn restore_remote(state: &AppState, previous_config: &CloudSyncConfig) {
+    let restored = GitUtils::new(
+      &previous_config.remote_url,
+      &state.dir_paths.root,
+      &previous_config.remote_name,
+      previous_config
+        .branch
+        .as_deref()
+        .unwrap_or(DEFAULT_BRANCH_NAME),
+      &previous_config.user_name,
+      &previous_config.user_email,
+      GitCredentialProvider::default(),
+    );
+    if let Err(e) = restored {
+      warn!("failed to restore the previous remote: {:?}", e);
+    }
   }
 
   /// # Sync
   ///
   /// Normal Sync to git remote
-  pub fn sync(self, mut state: AppState, db: &mut PickleDb) -> Result<()> {
+  /// - Local changes are committed first, then merged with the remote changes.
+  /// - Stops with `CloudSyncStatus::Conflicted` if the merge has conflicts
+  /// (or a previous merge still has unresolved conflicts).
+  /// - Records the result of the sync in the persisted `CloudSyncConfig`.
+  /// - `db` is only locked briefly, not during the network calls.
+  pub fn sync(self, state: AppState, db: &Mutex<PickleDb>) -> Result<CloudSyncStatus> {
+    let result = self.run_sync(state, db);
+    CloudSyncConfig::record_sync_result(&mut *Self::lock_db(db)?, &result)?;
+    result
+  }
+
+  fn run_sync(&self, state: AppState, db: &Mutex<PickleDb>) -> Result<CloudSyncStatus> {
     self.wem.send(WindowEvent {
       name: "cloud_sync",
       typ: WindowEventType::INFO,
@@ -127,11 +287,19 @@ impl<'cs> CloudSync<'cs> {
         message: "Creating new repository...",
       },
     })?;
-    *state
-      .cloud_sync_is_syncing
-      .lock()
-      .map_err(|e| anyhow::anyhow!(e.to_string()))? = true;
-    let git_utils = GitUtils::load(&state.dir_paths.root)?;
+    let git_utils = self.load_git_utils(&state, db)?;
+    if git_utils.is_merging() {
+      return self.stop_on_conflicts("cloud_sync");
+    }
+    git_utils.check_cancelled()?;
+    self.wem.send(WindowEvent {
+      name: "cloud_sync",
+      typ: WindowEventType::INFO,
+      data: CloudSyncPayload {
+        message: "Adding Commits...",
+      },
+    })?;
+    Self::commit_documents(&state, db, &git_utils)?;
     self.wem.send(WindowEvent {
       name: "cloud_sync",
       typ: WindowEventType::INFO,
@@ -139,73 +307,492 @@ impl<'cs> CloudSync<'cs> {
         message: "Pulling changes...",
       },
     })?;
-    git_utils.pull()?; // Pull the repo
-    let mut dirs = vec![];
+    if git_utils.pull()? == MergeStatus::Conflicted {
+      // Pull the repo
+      return self.stop_on_conflicts("cloud_sync");
+    }
+    Self::refresh_search_index(&state);
+    self.wem.send(WindowEvent {
+      name: "cloud_sync",
+      typ: WindowEventType::INFO,
+      data: CloudSyncPayload {
+        message: "Pushing changes to remote...",
+      },
+    })?;
+    let status = self.push_with_retry(&state, &git_utils, "cloud_sync")?;
+    if status == CloudSyncStatus::Conflicted {
+      return Ok(status);
+    }
+    self.finish_push(status, "cloud_sync")
+  }
+
+  /// # Switch Branch
+  ///
+  /// Switch the synced branch to `branch_name`.
+  /// - Local changes are committed to the current branch first.
+  /// - The branch is created from the remote branch if there is one
+  /// (the remote is fetched first), else from the current branch.
+  /// - Documents are replaced by the ones of the branch.
+  /// - `db` is only locked briefly, not during the network calls.
+  pub fn switch_branch(
+    self,
+    state: AppState,
+    db: &Mutex<PickleDb>,
+    branch_name: &str,
+  ) -> Result<CloudSyncConfig> {
+    let mut config = CloudSyncConfig::load(&*Self::lock_db(db)?)
+      .ok_or(anyhow::anyhow!("Cloud sync is not setup!"))?;
+    let git_utils = self.load_git_utils(&state, db)?;
+    if git_utils.is_merging() {
+      return Err(anyhow::anyhow!(
+        "A merge is in progress, resolve its conflicts first!"
+      ));
+    }
+    if git_utils.get_branch_name()? != branch_name {
+      Self::commit_documents(&state, db, &git_utils)?;
+      if let Err(e) = git_utils.fetch() {
+        warn!("fetch before switching branch failed: {:?}", e);
+      }
+      git_utils.check_cancelled()?;
+      git_utils.switch_branch(branch_name)?;
+      Self::refresh_search_index(&state);
+    }
+    config.branch = Some(branch_name.to_string());
+    config.save(&mut *Self::lock_db(db)?)?;
+    Ok(config)
+  }
+
+  /// # Update Config
+  ///
+  /// Update the persisted `CloudSyncConfig` (and the repository remote/author)
+  /// without re-running the setup. Only the given (`Some`) values are changed.
+  /// - An empty `commit_message_template` resets it to the default.
+  pub fn update_config(
+    state: AppState,
+    db: &mut PickleDb,
+    git_sync_repo_url: Option<String>,
+    git_sync_user_name: Option<String>,
+    git_sync_user_email: Option<String>,
+    git_sync_auth_method: Option<GitAuthMethod>,
+    git_sync_secret: Option<GitAuthSecret>,
+    auto_sync_enabled: Option<bool>,
+    auto_sync_interval_secs: Option<u64>,
+    auto_sync_debounce_secs: Option<u64>,
+    commit_message_template: Option<String>,
+  ) -> Result<CloudSyncConfig> {
+    let mut config =
+      CloudSyncConfig::load(db).ok_or(anyhow::anyhow!("Cloud sync is not setup!"))?;
+    let git_utils = GitUtils::load(&state.dir_paths.root, GitCredentialProvider::default())?;
+    if let Some(remote_url) = git_sync_repo_url {
+      git_utils.set_remote_url(&remote_url)?;
+      config.remote_url = remote_url;
+    }
+    if let Some(user_name) = git_sync_user_name {
+      config.user_name = user_name;
+    }
+    if let Some(user_email) = git_sync_user_email {
+      config.user_email = user_email;
+    }
+    git_utils.set_user_config(&config.user_name, &config.user_email)?;
+    if let Some(auth_method) = git_sync_auth_method {
+      config.auth_method = auth_method;
+    }
+    if let Some(auto_sync_enabled) = auto_sync_enabled {
+      config.auto_sync_enabled = auto_sync_enabled;
+    }
+    config.set_auto_sync_delays(auto_sync_interval_secs, auto_sync_debounce_secs)?;
+    if let Some(commit_message_template) = commit_message_template {
+      config.commit_message_template = match commit_message_template.trim() {
+        "" => None,
+        _ => Some(commit_message_template),
+      };
+    }
+    if git_sync_secret.is_some() {
+      *state
+        .git_auth_secret
+        .lock()
+        .map_err(|e| anyhow::anyhow!(e.to_string()))? = git_sync_secret;
+    }
+    config.save(db)?;
+    Ok(config)
+  }
+
+  /// # Disconnect
+  ///
+  /// Disconnect cloud sync.
+  /// - Removes the persisted `CloudSyncConfig`, the in-memory secret and
+  /// the repository remote. Local documents and history are kept.
+  pub fn disconnect(state: AppState, db: &mut PickleDb) -> Result<()> {
+    if let Ok(git_utils) = GitUtils::load(&state.dir_paths.root, GitCredentialProvider::default()) {
+      if let Err(e) = git_utils.remove_remote() {
+        warn!("failed to remove remote: {:?}", e);
+      }
+    }
+    *state
+      .git_auth_secret
+      .lock()
+      .map_err(|e| anyhow::anyhow!(e.to_string()))? = None;
+    CloudSyncConfig::remove(db)?;
+    Ok(())
+  }
+
+  /// # Commit Documents
+  ///
+  /// Commit all changes under the documents dir with a message generated from
+  /// the changes using the configured template (see `CloudSyncConfig`).
+  /// - Nothing is committed if no document changed, returns `false` then.
+  fn commit_documents(
+    state: &AppState,
+    db: &Mutex<PickleDb>,
+    git_utils: &GitUtils,
+  ) -> Result<bool> {
     // Get relative path as only relative paths to repo root are supported
     let document_relative_path = state
       .dir_paths
       .documents
-      .strip_prefix(state.dir_paths.root)?;
-    dirs.push(document_relative_path); // add documents dir to be tracked
+      .strip_prefix(&state.dir_paths.root)?;
+    let template = CloudSyncConfig::load(&*Self::lock_db(db)?)
+      .and_then(|config| config.commit_message_template)
+      .unwrap_or_else(|| DEFAULT_COMMIT_MESSAGE_TEMPLATE.to_string());
+    git_utils.add_commit(vec![document_relative_path], |changes| {
+      // list paths relative to the documents dir
+      let to_document_path = |path: &str| {
+        Path::new(path)
+          .strip_prefix(document_relative_path)
+          .unwrap_or_else(|_| Path::new(path))
+          .to_string_lossy()
+          .to_string()
+      };
+      let changes = changes
+        .iter()
+        .map(|change| FileChange {
+          relative_path: to_document_path(&change.relative_path),
+          old_relative_path: change.old_relative_path.as_deref().map(to_document_path),
+          status: change.status,
+        })
+        .collect::<Vec<_>>();
+      generate_commit_message(&template, &changes)
+    })
+  }
+
+  /// Catch up the search index with the documents changed by a pull/merge
+  fn refresh_search_index(state: &AppState) {
+    update_search_index(&state.search_index, |index| {
+      index.refresh(&state.dir_paths.documents)
+    });
+  }
+
+  /// # Progress Reporter
+  ///
+  /// Sends the fetch/checkout/push progress to the window
+  /// as (throttled) `cloud_sync_progress` events.
+  fn progress_reporter(&self) -> SyncProgressReporter {
+    let window = self.wem.window.clone();
+    SyncProgressReporter::new(move |progress| {
+      let wem = WindowEventManager::new(&window);
+      if let Err(e) = wem.send(WindowEvent {
+        name: "cloud_sync_progress",
+        typ: WindowEventType::INFO,
+        data: progress.clone(),
+      }) {
+        warn!("failed to send sync progress: {:?}", e);
+      }
+    })
+  }
+
+  /// # Push With Retry
+  ///
+  /// Push to the remote. If the remote rejects the push (eg. another device
+  /// pushed meanwhile), the new remote commits are fetched + merged and the
+  /// push is retried, at most `MAX_PUSH_ATTEMPTS` times.
+  /// - Stops with `CloudSyncStatus::Conflicted` if merging the new commits conflicts.
+  fn push_with_retry(
+    &self,
+    state: &AppState,
+    git_utils: &GitUtils,
+    event_name: &'static str,
+  ) -> Result<CloudSyncStatus> {
+    let status = Self::retry_push(
+      git_utils,
+      || {
+        self.wem.send(WindowEvent {
+          name: event_name,
+          typ: WindowEventType::INFO,
+          data: CloudSyncPayload {
+            message: "Push rejected, pulling new remote changes...",
+          },
+        })
+      },
+      || Self::refresh_search_index(state),
+    )?;
+    match status {
+      CloudSyncStatus::Conflicted => self.stop_on_conflicts(event_name),
+      status => Ok(status),
+    }
+  }
+
+  /// # Retry Push
+  ///
+  /// Push/pull loop of `push_with_retry`.
+  /// - `on_rejected` is called before pulling the new remote commits,
+  /// `on_pulled` once they are merged.
+  /// - Returns `CloudSyncStatus::Conflicted` if merging the new commits conflicts.
+  fn retry_push<R, P>(
+    git_utils: &GitUtils,
+    mut on_rejected: R,
+    mut on_pulled: P,
+  ) -> Result<CloudSyncStatus>
+  where
+    R: FnMut() -> Result<()>,
+    P: FnMut(),
+  {
+    for attempt in 1..=MAX_PUSH_ATTEMPTS {
+      match git_utils.push()? {
+        PushStatus::Pushed => return Ok(CloudSyncStatus::Pushed),
+        PushStatus::UpToDate => return Ok(CloudSyncStatus::UpToDate),
+        PushStatus::Rejected(reason) => {
+          warn!(
+            "push rejected (attempt {}/{}): {}",
+            attempt, MAX_PUSH_ATTEMPTS, reason
+          );
+          if attempt == MAX_PUSH_ATTEMPTS {
+            break;
+          }
+          git_utils.check_cancelled()?;
+          on_rejected()?;
+          if git_utils.pull()? == MergeStatus::Conflicted {
+            return Ok(CloudSyncStatus::Conflicted);
+          }
+          on_pulled();
+        }
+      }
+    }
+    Ok(CloudSyncStatus::Rejected)
+  }
+
+  /// # Finish Push
+  ///
+  /// End the sync after pushing and notify the window of the outcome.
+  fn finish_push(
+    &self,
+    status: CloudSyncStatus,
+    event_name: &'static str,
+  ) -> Result<CloudSyncStatus> {
+    let (typ, message) = match status {
+      CloudSyncStatus::Rejected => (
+        WindowEventType::ERROR,
+        "Push rejected by the remote, Please re-try after some time!",
+      ),
+      _ => (WindowEventType::INFO, "Success!"),
+    };
     self.wem.send(WindowEvent {
-      name: "cloud_sync",
-      typ: WindowEventType::INFO,
+      name: event_name,
+      typ,
+      data: CloudSyncPayload { message },
+    })?;
+    Ok(status)
+  }
+
+  /// # Stop on Conflicts
+  ///
+  /// Stop the sync (without committing/pushing) and notify
+  /// the window that merge conflicts need to be resolved.
+  fn stop_on_conflicts(&self, event_name: &'static str) -> Result<CloudSyncStatus> {
+    self.wem.send(WindowEvent {
+      name: event_name,
+      typ: WindowEventType::ERROR,
       data: CloudSyncPayload {
-        message: "Adding Commits...",
+        message: "Merge conflicts detected, resolve them to complete the sync!",
       },
     })?;
-    git_utils.add_commit(
-      // Commit changes
-      dirs,
-      format!(
-        "Commit Changes, time: {}",
-        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+    Ok(CloudSyncStatus::Conflicted)
+  }
+
+  /// # Preview
+  ///
+  /// Dry run of `sync`: fetch the remote (no merge, commit or push) and report
+  /// the local/remote documents changes and the expected merge outcome.
+  /// - Paths are relative to the documents dir.
+  /// - `db` is only locked briefly, not during the network calls.
+  pub fn preview(self, state: AppState, db: &Mutex<PickleDb>) -> Result<SyncPreview> {
+    let git_utils = self.load_git_utils(&state, db)?;
+    let document_relative_path = state
+      .dir_paths
+      .documents
+      .strip_prefix(&state.dir_paths.root)?;
+    let mut preview = git_utils.preview_sync(vec![document_relative_path])?;
+    let to_document_path = |path: &str| -> Result<String> {
+      Ok(
+        Path::new(path)
+          .strip_prefix(document_relative_path)?
+          .to_string_lossy()
+          .to_string(),
       )
-      .as_str(),
-    )?;
+    };
+    for change in preview
+      .local_changes
+      .iter_mut()
+      .chain(preview.remote_changes.iter_mut())
+    {
+      change.relative_path = to_document_path(&change.relative_path)?;
+      change.old_relative_path = match &change.old_relative_path {
+        Some(path) => Some(to_document_path(path)?),
+        None => None,
+      };
+    }
+    for conflict in preview.conflicts.iter_mut() {
+      *conflict = to_document_path(conflict)?;
+    }
+    Ok(preview)
+  }
+
+  /// # Conflicts
+  ///
+  /// Get the conflicted documents of the in-progress merge.
+  /// - `relative_path` of each conflict is relative to the documents dir.
+  pub fn conflicts(state: AppState) -> Result<Vec<MergeConflict>> {
+    let git_utils = GitUtils::load(&state.dir_paths.root, GitCredentialProvider::default())?;
+    let document_relative_path = state
+      .dir_paths
+      .documents
+      .strip_prefix(&state.dir_paths.root)?;
+    let mut conflicts = git_utils.get_conflicts()?;
+    for conflict in conflicts.iter_mut() {
+      conflict.relative_path = Path::new(&conflict.relative_path)
+        .strip_prefix(document_relative_path)?
+        .to_string_lossy()
+        .to_string();
+    }
+    Ok(conflicts)
+  }
+
+  /// # Resolve Conflict
+  ///
+  /// Resolve a conflicted document (`relative_path` relative to the documents dir).
+  pub fn resolve_conflict(
+    state: AppState,
+    relative_path: &str,
+    resolution: ConflictResolution,
+  ) -> Result<()> {
+    let git_utils = GitUtils::load(&state.dir_paths.root, GitCredentialProvider::default())?;
+    let document_path = DocumentPath::resolve(&state.dir_paths.documents, relative_path)?;
+    // Get relative path as only relative paths to repo root are supported
+    let repo_relative_path = document_path.path.strip_prefix(&state.dir_paths.root)?;
+    git_utils.resolve_conflict(repo_relative_path, resolution)?;
+    Ok(())
+  }
+
+  /// # Complete Merge
+  ///
+  /// Create the merge commit once all conflicts are resolved and
+  /// push it to the remote.
+  /// - `db` is only locked briefly, not during the network calls.
+  pub fn complete_merge(self, state: AppState, db: &Mutex<PickleDb>) -> Result<CloudSyncStatus> {
+    let git_utils = self.load_git_utils(&state, db)?;
+    git_utils.check_cancelled()?;
     self.wem.send(WindowEvent {
       name: "cloud_sync",
       typ: WindowEventType::INFO,
       data: CloudSyncPayload {
-        message: "Pushing changes to remote...",
+        message: "Committing merge...",
       },
     })?;
-    git_utils.push()?; // Push Changes to remote
-    *state
-      .cloud_sync_is_syncing
-      .lock()
-      .map_err(|e| anyhow::anyhow!(e.to_string()))? = false;
+    git_utils.complete_merge()?;
+    Self::refresh_search_index(&state);
     self.wem.send(WindowEvent {
       name: "cloud_sync",
       typ: WindowEventType::INFO,
       data: CloudSyncPayload {
-        message: "Success!",
+        message: "Pushing changes to remote...",
       },
     })?;
-    Ok(())
+    let status = self.push_with_retry(&state, &git_utils, "cloud_sync")?;
+    if status == CloudSyncStatus::Conflicted {
+      return Ok(status);
+    }
+    self.finish_push(status, "cloud_sync")
   }
+}
 
-  pub fn test_git_clone_ssh() -> Result<(), git2::Error> {
-    // Prepare callbacks.
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(|_url, username_from_url, _allowed_types| {
-      debug!("username_from_url: {:?}", username_from_url);
-      Cred::ssh_key_from_agent(username_from_url.unwrap())
-    });
+#[cfg(test)]
+mod tests {
+  use std::{cell::Cell, fs};
 
-    // Prepare fetch options.
-    let mut fo = git2::FetchOptions::new();
-    fo.remote_callbacks(callbacks);
+  use super::*;
+  use crate::utils::test_utils::{commit_documents, temp_synced_repos};
 
-    // Prepare builder.
-    let mut builder = git2::build::RepoBuilder::new();
-    builder.fetch_options(fo);
+  #[test]
+  fn pulls_and_retries_rejected_pushes() {
+    let ((root_a, a), (root_b, b)) = temp_synced_repos("push_retry", &[("a.md", "# A\n")]);
+    fs::write(root_a.join("documents/a.md"), "# A\n\nfrom a\n").unwrap();
+    assert!(commit_documents(&a));
+    fs::write(root_b.join("documents/b.md"), "# B\n").unwrap();
+    assert!(commit_documents(&b));
+    assert_eq!(
+      CloudSync::retry_push(&a, || Ok(()), || {}).unwrap(),
+      CloudSyncStatus::Pushed
+    );
 
-    // Clone the project.
-    builder.clone(
-      "git@github.com:rust-lang/git2-rs.git",
-      Path::new("/tmp/git2-rs"),
-    )?;
-    Ok(())
+    let (rejected, pulled) = (Cell::new(0), Cell::new(0));
+    let status = CloudSync::retry_push(
+      &b,
+      || {
+        rejected.set(rejected.get() + 1);
+        Ok(())
+      },
+      || pulled.set(pulled.get() + 1),
+    )
+    .unwrap();
+    assert_eq!(status, CloudSyncStatus::Pushed);
+    assert_eq!((rejected.get(), pulled.get()), (1, 1));
+    assert_eq!(
+      fs::read_to_string(root_b.join("documents/a.md")).unwrap(),
+      "# A\n\nfrom a\n"
+    );
+    assert_eq!(
+      CloudSync::retry_push(&b, || Ok(()), || {}).unwrap(),
+      CloudSyncStatus::UpToDate
+    );
+  }
+
+  #[test]
+  fn gives_up_after_max_push_attempts() {
+    let ((root_a, a), (root_b, b)) = temp_synced_repos("push_rejected", &[("a.md", "# A\n")]);
+    fs::write(root_b.join("documents/b.md"), "# B\n").unwrap();
+    assert!(commit_documents(&b));
+    // the other device pushes again before each retry
+    let push_from_a = |n: usize| {
+      fs::write(root_a.join(format!("documents/a{}.md", n)), "# A\n").unwrap();
+      assert!(commit_documents(&a));
+      assert_eq!(a.push().unwrap(), PushStatus::Pushed);
+    };
+    push_from_a(0);
+    let pulled = Cell::new(0);
+    let status = CloudSync::retry_push(
+      &b,
+      || Ok(()),
+      || {
+        pulled.set(pulled.get() + 1);
+        push_from_a(pulled.get());
+      },
+    )
+    .unwrap();
+    assert_eq!(status, CloudSyncStatus::Rejected);
+    assert_eq!(pulled.get(), MAX_PUSH_ATTEMPTS - 1);
+  }
+
+  #[test]
+  fn stops_retrying_on_conflicts() {
+    let ((root_a, a), (root_b, b)) = temp_synced_repos("push_conflict", &[("a.md", "# A\n")]);
+    fs::write(root_a.join("documents/a.md"), "# A\n\nfrom a\n").unwrap();
+    assert!(commit_documents(&a));
+    assert_eq!(a.push().unwrap(), PushStatus::Pushed);
+    fs::write(root_b.join("documents/a.md"), "# A\n\nfrom b\n").unwrap();
+    assert!(commit_documents(&b));
+    let pulled = Cell::new(false);
+    let status = CloudSync::retry_push(&b, || Ok(()), || pulled.set(true)).unwrap();
+    assert_eq!(status, CloudSyncStatus::Conflicted);
+    assert!(!pulled.get());
+    assert!(b.is_merging());
   }
 }
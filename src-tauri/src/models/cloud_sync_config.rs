@@ -0,0 +1,148 @@
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use pickledb::PickleDb;
+use serde::{Deserialize, Serialize};
+
+use crate::{constants::db_keys::CLOUD_SYNC_CONFIG_KEY, utils::git_utils::DEFAULT_REMOTE_NAME};
+
+use super::{cloud_sync::CloudSyncStatus, git_auth::GitAuthMethod, server_error::ServerError};
+
+/// Default interval between scheduled auto-syncs (5 mins)
+pub const DEFAULT_AUTO_SYNC_INTERVAL_SECS: u64 = 300;
+/// Default delay after the last document change before an auto-sync
+pub const DEFAULT_AUTO_SYNC_DEBOUNCE_SECS: u64 = 30;
+/// Shortest allowed interval between scheduled auto-syncs
+pub const MIN_AUTO_SYNC_INTERVAL_SECS: u64 = 60;
+/// Shortest allowed delay after the last document change before an auto-sync
+pub const MIN_AUTO_SYNC_DEBOUNCE_SECS: u64 = 5;
+
+/// # Cloud Sync Config
+///
+/// Cloud sync setup persisted in the DB (`store.db.json`).
+/// - Never holds any auth secrets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudSyncConfig {
+  /// Url of the git remote
+  pub remote_url: String,
+  /// Name of the git remote
+  #[serde(default = "default_remote_name")]
+  pub remote_name: String,
+  /// Branch synced with the remote
+  pub branch: Option<String>,
+  /// Commit author name
+  pub user_name: String,
+  /// Commit author email
+  pub user_email: String,
+  /// Method used to auth with the remote
+  pub auth_method: GitAuthMethod,
+  /// Time of the last successful sync (RFC 3339)
+  pub last_synced_at: Option<String>,
+  /// Error of the last failed sync (cleared on success)
+  pub last_error: Option<String>,
+  /// Run sync in the background (on interval + after document changes)
+  #[serde(default)]
+  pub auto_sync_enabled: bool,
+  /// Interval between scheduled auto-syncs
+  #[serde(default = "default_auto_sync_interval_secs")]
+  pub auto_sync_interval_secs: u64,
+  /// Delay after the last document change before an auto-sync
+  #[serde(default = "default_auto_sync_debounce_secs")]
+  pub auto_sync_debounce_secs: u64,
+  /// Template of the sync commit messages (`None` for the default,
+  /// see `DEFAULT_COMMIT_MESSAGE_TEMPLATE` for the placeholders)
+  #[serde(default)]
+  pub commit_message_template: Option<String>,
+}
+
+fn default_remote_name() -> String {
+  DEFAULT_REMOTE_NAME.to_string()
+}
+
+fn default_auto_sync_interval_secs() -> u64 {
+  DEFAULT_AUTO_SYNC_INTERVAL_SECS
+}
+
+fn default_auto_sync_debounce_secs() -> u64 {
+  DEFAULT_AUTO_SYNC_DEBOUNCE_SECS
+}
+
+impl CloudSyncConfig {
+  /// Load the config from DB (`None` if cloud sync is not setup)
+  /// - Auto-sync delays below the minimums (saved by older versions) are raised to them.
+  pub fn load(db: &PickleDb) -> Option<Self> {
+    db.get::<CloudSyncConfig>(CLOUD_SYNC_CONFIG_KEY)
+      .map(|mut config| {
+        config.auto_sync_interval_secs = config
+          .auto_sync_interval_secs
+          .max(MIN_AUTO_SYNC_INTERVAL_SECS);
+        config.auto_sync_debounce_secs = config
+          .auto_sync_debounce_secs
+          .max(MIN_AUTO_SYNC_DEBOUNCE_SECS);
+        config
+      })
+  }
+
+  /// Save the config to DB
+  pub fn save(&self, db: &mut PickleDb) -> Result<()> {
+    db.set(CLOUD_SYNC_CONFIG_KEY, self)?;
+    Ok(())
+  }
+
+  /// # Set Auto Sync Delays
+  ///
+  /// Set the auto-sync interval/debounce delay (`None` keeps the current one).
+  /// - Fails (without changing any) if a delay is below its minimum.
+  pub fn set_auto_sync_delays(
+    &mut self,
+    interval_secs: Option<u64>,
+    debounce_secs: Option<u64>,
+  ) -> Result<(), ServerError> {
+    let delays = [
+      ("interval", interval_secs, MIN_AUTO_SYNC_INTERVAL_SECS),
+      ("debounce delay", debounce_secs, MIN_AUTO_SYNC_DEBOUNCE_SECS),
+    ];
+    for (name, secs, min_secs) in delays.iter() {
+      if let Some(secs) = secs {
+        if secs < min_secs {
+          return Err(ServerError::UserError {
+            message: format!("Auto-sync {} must be at least {} seconds!", name, min_secs),
+          });
+        }
+      }
+    }
+    self.auto_sync_interval_secs = interval_secs.unwrap_or(self.auto_sync_interval_secs);
+    self.auto_sync_debounce_secs = debounce_secs.unwrap_or(self.auto_sync_debounce_secs);
+    Ok(())
+  }
+
+  /// Remove the config from DB
+  pub fn remove(db: &mut PickleDb) -> Result<()> {
+    db.rem(CLOUD_SYNC_CONFIG_KEY)?;
+    Ok(())
+  }
+
+  /// # Record Sync Result
+  ///
+  /// Update `last_synced_at`/`last_error` of the saved config
+  /// with the result of a sync run.
+  pub fn record_sync_result(db: &mut PickleDb, result: &Result<CloudSyncStatus>) -> Result<()> {
+    if let Some(mut config) = Self::load(db) {
+      match result {
+        Ok(CloudSyncStatus::Pushed) | Ok(CloudSyncStatus::UpToDate) => {
+          config.last_synced_at = Some(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+          config.last_error = None;
+        }
+        Ok(CloudSyncStatus::Rejected) => {
+          config.last_error = Some("Push rejected by the remote!".to_string())
+        }
+        Ok(CloudSyncStatus::Conflicted) => {
+          config.last_error = Some("Merge conflicts need to be resolved!".to_string())
+        }
+        Err(e) => config.last_error = Some(e.to_string()),
+      }
+      config.save(db)?;
+    }
+    Ok(())
+  }
+}
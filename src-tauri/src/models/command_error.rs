@@ -0,0 +1,396 @@
+use std::{error::Error, fmt, io, sync::PoisonError};
+
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+  models::server_error::ServerError,
+  utils::{fsutils::WriteConflict, git_utils::SyncCancelled, lock_manager::LockBusy},
+};
+
+/// Stable error codes (the frontend matches on these, never rename them).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+  /// Invalid input, eg. a path outside the documents dir
+  InvalidInput,
+  NotFound,
+  AlreadyExists,
+  /// The document changed on disk since it was read
+  WriteConflict,
+  /// A document/cloud sync lock is held by another operation
+  LockBusy,
+  SyncCancelled,
+  AuthFailed,
+  NetworkError,
+  Internal,
+}
+
+/// Who/what the error is caused by (how the frontend should react).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCategory {
+  /// Invalid request, show the message to the user
+  User,
+  /// Bug/unexpected failure
+  Internal,
+  /// Concurrent change, needs to be resolved by the user
+  Conflict,
+  /// Cloud sync credentials are missing/rejected
+  Auth,
+  /// Remote unreachable
+  Network,
+  /// Another operation is in progress, retry later
+  Busy,
+}
+
+/// # Command Error
+///
+/// Error returned by commands (serialized to the frontend as is).
+/// - Converts from the errors of the models/utils (`?` in commands),
+/// anything unknown is an `Internal` error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandError {
+  pub code: ErrorCode,
+  pub category: ErrorCategory,
+  pub message: String,
+  /// `true` if the same request may succeed later
+  pub retryable: bool,
+  /// Error specific data (eg. the lock holder or the conflicting content)
+  pub details: Option<Value>,
+}
+
+impl CommandError {
+  pub fn new<M: Into<String>>(code: ErrorCode, category: ErrorCategory, message: M) -> Self {
+    Self {
+      code,
+      category,
+      message: message.into(),
+      retryable: matches!(category, ErrorCategory::Busy | ErrorCategory::Network),
+      details: None,
+    }
+  }
+
+  pub fn user<M: Into<String>>(code: ErrorCode, message: M) -> Self {
+    Self::new(code, ErrorCategory::User, message)
+  }
+
+  pub fn internal<M: Into<String>>(message: M) -> Self {
+    Self::new(ErrorCode::Internal, ErrorCategory::Internal, message)
+  }
+
+  pub fn with_details<T: Serialize>(mut self, details: T) -> Self {
+    self.details = serde_json::to_value(details).ok();
+    self
+  }
+
+  /// Error for a known error type (anywhere in the source chain of `e`)
+  fn classify(e: &(dyn Error + 'static), message: String) -> Option<Self> {
+    let mut source = Some(e);
+    while let Some(e) = source {
+      if let Some(busy) = e.downcast_ref::<LockBusy>() {
+        return Some(Self::from_lock_busy(busy, message));
+      } else if e.is::<SyncCancelled>() {
+        return Some(Self::user(ErrorCode::SyncCancelled, message));
+      } else if let Some(e) = e.downcast_ref::<ServerError>() {
+        return Some(Self::from_server_error(e, message));
+      } else if let Some(e) = e.downcast_ref::<git2::Error>() {
+        return Some(Self::from_git_error(e, message));
+      } else if let Some(e) = e.downcast_ref::<io::Error>() {
+        return Some(match e.kind() {
+          io::ErrorKind::NotFound => Self::user(ErrorCode::NotFound, message),
+          io::ErrorKind::AlreadyExists => Self::user(ErrorCode::AlreadyExists, message),
+          _ => Self::internal(message),
+        });
+      }
+      source = e.source();
+    }
+    None
+  }
+
+  fn from_lock_busy(busy: &LockBusy, message: String) -> Self {
+    Self::new(ErrorCode::LockBusy, ErrorCategory::Busy, message).with_details(&busy.holder)
+  }
+
+  fn from_server_error(e: &ServerError, message: String) -> Self {
+    match e {
+      ServerError::UserError { .. } => Self::user(ErrorCode::InvalidInput, message),
+      ServerError::NotFoundError { .. } => Self::user(ErrorCode::NotFound, message),
+      ServerError::AlreadyExistsError { .. } => Self::user(ErrorCode::AlreadyExists, message),
+      ServerError::InternalError { .. } => Self::internal(message),
+    }
+  }
+
+  fn from_git_error(e: &git2::Error, message: String) -> Self {
+    match (e.code(), e.class()) {
+      (git2::ErrorCode::Auth, _) | (git2::ErrorCode::Certificate, _) => {
+        Self::new(ErrorCode::AuthFailed, ErrorCategory::Auth, message)
+      }
+      (git2::ErrorCode::NotFound, _) => Self::user(ErrorCode::NotFound, message),
+      (git2::ErrorCode::Exists, _) => Self::user(ErrorCode::AlreadyExists, message),
+      (_, git2::ErrorClass::Net)
+      | (_, git2::ErrorClass::Http)
+      | (_, git2::ErrorClass::Ssh)
+      | (_, git2::ErrorClass::Ssl) => {
+        Self::new(ErrorCode::NetworkError, ErrorCategory::Network, message)
+      }
+      _ => Self::internal(message),
+    }
+  }
+
+  fn log(self, e: &dyn fmt::Debug) -> Self {
+    match self.category {
+      ErrorCategory::Busy => debug!("{:?}", e),
+      _ => error!("{:?}", e),
+    }
+    self
+  }
+}
+
+impl From<&anyhow::Error> for CommandError {
+  fn from(e: &anyhow::Error) -> Self {
+    // include the causes, eg. `Failed to push: authentication required`
+    let message = format!("{:#}", e);
+    Self::classify(e.as_ref(), message.clone())
+      .unwrap_or_else(|| Self::internal(message))
+      .log(e)
+  }
+}
+
+impl From<anyhow::Error> for CommandError {
+  fn from(e: anyhow::Error) -> Self {
+    Self::from(&e)
+  }
+}
+
+impl From<ServerError> for CommandError {
+  fn from(e: ServerError) -> Self {
+    Self::from_server_error(&e, e.message()).log(&e)
+  }
+}
+
+impl From<LockBusy> for CommandError {
+  fn from(e: LockBusy) -> Self {
+    Self::from_lock_busy(&e, e.to_string()).log(&e)
+  }
+}
+
+impl From<WriteConflict> for CommandError {
+  fn from(conflict: WriteConflict) -> Self {
+    Self::new(
+      ErrorCode::WriteConflict,
+      ErrorCategory::Conflict,
+      "Document changed on disk since it was read!",
+    )
+    .with_details(conflict)
+  }
+}
+
+impl From<git2::Error> for CommandError {
+  fn from(e: git2::Error) -> Self {
+    let message = e.message().to_string();
+    Self::from_git_error(&e, message).log(&e)
+  }
+}
+
+impl<T> From<PoisonError<T>> for CommandError {
+  fn from(e: PoisonError<T>) -> Self {
+    Self::internal(e.to_string()).log(&e.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use anyhow::anyhow;
+
+  use super::*;
+  use crate::utils::lock_manager::{LockHolder, LockKind, LockMode};
+
+  fn lock_busy() -> LockBusy {
+    LockBusy {
+      holder: LockHolder {
+        id: 1,
+        path: "notes".to_string(),
+        mode: LockMode::Write,
+        kind: LockKind::CloudSync,
+        owner: "sync_to_git_cloud".to_string(),
+        thread: None,
+        acquired_at: "2021-01-01T00:00:00.000Z".to_string(),
+      },
+    }
+  }
+
+  #[test]
+  fn classifies_the_source_chain() {
+    let git_error = |code, class| git2::Error::new(code, class, "remote failed");
+    for (error, code, category, retryable) in vec![
+      (
+        anyhow!(git_error(git2::ErrorCode::Auth, git2::ErrorClass::Http)),
+        ErrorCode::AuthFailed,
+        ErrorCategory::Auth,
+        false,
+      ),
+      (
+        anyhow!(git_error(
+          git2::ErrorCode::Certificate,
+          git2::ErrorClass::Ssl
+        )),
+        ErrorCode::AuthFailed,
+        ErrorCategory::Auth,
+        false,
+      ),
+      (
+        anyhow!(git_error(
+          git2::ErrorCode::GenericError,
+          git2::ErrorClass::Net
+        )),
+        ErrorCode::NetworkError,
+        ErrorCategory::Network,
+        true,
+      ),
+      (
+        anyhow!(git_error(
+          git2::ErrorCode::GenericError,
+          git2::ErrorClass::Ssh
+        )),
+        ErrorCode::NetworkError,
+        ErrorCategory::Network,
+        true,
+      ),
+      (
+        anyhow!(git_error(
+          git2::ErrorCode::NotFound,
+          git2::ErrorClass::Reference
+        )),
+        ErrorCode::NotFound,
+        ErrorCategory::User,
+        false,
+      ),
+      (
+        anyhow!(git_error(
+          git2::ErrorCode::Conflict,
+          git2::ErrorClass::Checkout
+        )),
+        ErrorCode::Internal,
+        ErrorCategory::Internal,
+        false,
+      ),
+      (
+        anyhow!(lock_busy()),
+        ErrorCode::LockBusy,
+        ErrorCategory::Busy,
+        true,
+      ),
+      (
+        anyhow!(SyncCancelled),
+        ErrorCode::SyncCancelled,
+        ErrorCategory::User,
+        false,
+      ),
+      (
+        anyhow!(ServerError::NotFoundError {
+          message: "missing".to_string()
+        }),
+        ErrorCode::NotFound,
+        ErrorCategory::User,
+        false,
+      ),
+      (
+        anyhow!(ServerError::AlreadyExistsError {
+          message: "exists".to_string()
+        }),
+        ErrorCode::AlreadyExists,
+        ErrorCategory::User,
+        false,
+      ),
+      (
+        anyhow!(ServerError::UserError {
+          message: "invalid".to_string()
+        }),
+        ErrorCode::InvalidInput,
+        ErrorCategory::User,
+        false,
+      ),
+      (
+        anyhow!(io::Error::from(io::ErrorKind::NotFound)),
+        ErrorCode::NotFound,
+        ErrorCategory::User,
+        false,
+      ),
+      (
+        anyhow!(io::Error::from(io::ErrorKind::AlreadyExists)),
+        ErrorCode::AlreadyExists,
+        ErrorCategory::User,
+        false,
+      ),
+      (
+        anyhow!(io::Error::from(io::ErrorKind::PermissionDenied)),
+        ErrorCode::Internal,
+        ErrorCategory::Internal,
+        false,
+      ),
+      (
+        anyhow!("unknown"),
+        ErrorCode::Internal,
+        ErrorCategory::Internal,
+        false,
+      ),
+    ] {
+      // the known error is found below the added contexts
+      let error = error.context("Failed to sync").context("Sync job failed");
+      let message = format!("{:#}", error);
+      let e = CommandError::from(error);
+      assert_eq!(
+        (e.code, e.category, e.retryable),
+        (code, category, retryable),
+        "{}",
+        message
+      );
+      assert_eq!(e.message, message);
+    }
+  }
+
+  #[test]
+  fn keeps_the_lock_holder_in_the_details() {
+    let e = CommandError::from(anyhow!(lock_busy()).context("Failed to sync"));
+    assert_eq!(e.code, ErrorCode::LockBusy);
+    let details = e.details.expect("lock holder details");
+    assert_eq!(details["owner"], "sync_to_git_cloud");
+    assert_eq!(details["kind"], "cloudSync");
+  }
+
+  #[test]
+  fn keeps_the_disk_content_of_write_conflicts() {
+    let e = CommandError::from(WriteConflict {
+      disk_content: Some("# A".to_string()),
+      disk_modified: Some("2021-01-01T00:00:00.000Z".to_string()),
+      disk_content_hash: Some("hash".to_string()),
+    });
+    assert_eq!(
+      (e.code, e.category, e.retryable),
+      (ErrorCode::WriteConflict, ErrorCategory::Conflict, false)
+    );
+    let details = e.details.expect("conflict details");
+    assert_eq!(details["diskContent"], "# A");
+    assert_eq!(details["diskModified"], "2021-01-01T00:00:00.000Z");
+    assert_eq!(details["diskContentHash"], "hash");
+  }
+
+  #[test]
+  fn serializes_the_envelope() {
+    let e =
+      CommandError::user(ErrorCode::WriteConflict, "changed on disk").with_details(vec!["content"]);
+    assert_eq!(
+      serde_json::to_value(&e).unwrap(),
+      serde_json::json!({
+        "code": "WRITE_CONFLICT",
+        "category": "user",
+        "message": "changed on disk",
+        "retryable": false,
+        "details": ["content"],
+      })
+    );
+  }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+/// # Diff Mode
+///
+/// Granularity of the changes in a diff.
+/// - `Line`: changed lines only.
+/// - `Word`: changed lines + inline word changes of each hunk (for prose).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffMode {
+  Line,
+  Word,
+}
+
+impl Default for DiffMode {
+  fn default() -> Self {
+    DiffMode::Line
+  }
+}
+
+/// # File Diff
+///
+/// Changes of a single document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+  /// Path of the document after the change.
+  pub relative_path: String,
+  /// Path of the document before the change (only for renames).
+  pub old_relative_path: Option<String>,
+  pub status: FileDiffStatus,
+  /// `true` if the document is binary (no hunks).
+  pub binary: bool,
+  pub hunks: Vec<DiffHunk>,
+}
+
+/// # File Change
+///
+/// A changed document, without the changes themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChange {
+  /// Path of the document after the change.
+  pub relative_path: String,
+  /// Path of the document before the change (only for renames).
+  pub old_relative_path: Option<String>,
+  pub status: FileDiffStatus,
+}
+
+/// # File Diff Status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileDiffStatus {
+  Added,
+  Modified,
+  Renamed,
+  Deleted,
+}
+
+/// # Diff Hunk
+///
+/// A block of changed lines (with surrounding context lines).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+  /// Hunk header (eg. `@@ -1,3 +1,4 @@`).
+  pub header: String,
+  pub old_start: u32,
+  pub old_lines: u32,
+  pub new_start: u32,
+  pub new_lines: u32,
+  pub lines: Vec<DiffLine>,
+  /// Inline changes of the hunk text (only in `Word` mode).
+  pub words: Option<Vec<DiffSegment>>,
+}
+
+/// # Diff Line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+  pub kind: DiffChangeKind,
+  /// Line number in the old version (`None` for added lines).
+  pub old_line_number: Option<u32>,
+  /// Line number in the new version (`None` for removed lines).
+  pub new_line_number: Option<u32>,
+  /// Line content (without the line ending).
+  pub content: String,
+}
+
+/// # Diff Segment
+///
+/// A run of text (words/whitespace/punctuation) with the same change kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSegment {
+  pub kind: DiffChangeKind,
+  pub text: String,
+}
+
+/// # Diff Change Kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffChangeKind {
+  /// Unchanged (context).
+  Equal,
+  Added,
+  Removed,
+}
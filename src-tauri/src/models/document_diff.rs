@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::utils::{
+  document_path::DocumentPath, fsutils, git_credentials::GitCredentialProvider, git_diff,
+  git_utils::GitUtils,
+};
+
+use super::{
+  app_state::AppState,
+  diff::{DiffMode, FileDiff},
+  document_history::DocumentHistory,
+};
+
+/// # Document Diff
+///
+/// Structured diffs of documents, computed from the cloud sync git repository.
+/// - Paths returned are relative to the documents dir.
+pub struct DocumentDiff;
+
+impl DocumentDiff {
+  /// # Working Changes
+  ///
+  /// Get the changes of the documents not yet committed (working copy vs HEAD).
+  pub fn working_changes(state: &AppState, mode: DiffMode) -> Result<Vec<FileDiff>> {
+    let git_utils = DocumentHistory::load_git_utils(state)?;
+    let documents_repo_path = DocumentHistory::documents_repo_path(state)?;
+    let file_diffs = git_utils.diff_workdir_to_head(documents_repo_path, mode)?;
+    Self::to_document_file_diffs(state, file_diffs)
+  }
+
+  /// # Remote Changes
+  ///
+  /// Get the changes of the documents on the remote not yet merged (HEAD vs remote).
+  /// - If `fetch` is `true` the remote is fetched first, else the last fetched state is used.
+  pub fn remote_changes(
+    state: &AppState,
+    credentials: GitCredentialProvider,
+    fetch: bool,
+    mode: DiffMode,
+  ) -> Result<Vec<FileDiff>> {
+    let git_utils = GitUtils::load(&state.dir_paths.root, credentials)
+      .context("Remote changes are only available after cloud sync is setup!")?;
+    if fetch {
+      git_utils.fetch()?;
+    }
+    let documents_repo_path = DocumentHistory::documents_repo_path(state)?;
+    let file_diffs = git_utils.diff_head_to_remote(documents_repo_path, mode)?;
+    Self::to_document_file_diffs(state, file_diffs)
+  }
+
+  /// # Revisions
+  ///
+  /// Get the changes of a document between the commits `old_commit_id` and
+  /// `new_commit_id` (`None` for the working copy).
+  /// - Renames are followed, the document may be missing in one of the versions.
+  /// - Returns `None` if the document is the same in both versions.
+  pub fn revisions(
+    state: &AppState,
+    document_path: &DocumentPath,
+    old_commit_id: &str,
+    new_commit_id: Option<&str>,
+    mode: DiffMode,
+  ) -> Result<Option<FileDiff>> {
+    let old = DocumentHistory::content_at(state, document_path, old_commit_id)
+      .ok()
+      .map(|document| (document.relative_path, document.content));
+    let new = match new_commit_id {
+      Some(commit_id) => DocumentHistory::content_at(state, document_path, commit_id)
+        .ok()
+        .map(|document| (document.relative_path, document.content)),
+      None if document_path.path.is_file() => Some((
+        document_path.relative_path.to_string(),
+        fsutils::read_from_path(&document_path.path)?,
+      )),
+      None => None,
+    };
+    if old.is_none() && new.is_none() {
+      return Err(anyhow!(
+        "`{}` not found in any of the revisions!",
+        document_path.relative_path
+      ));
+    }
+    git_diff::diff_contents(
+      old
+        .as_ref()
+        .map(|(path, content)| (path.as_str(), content.as_str())),
+      new
+        .as_ref()
+        .map(|(path, content)| (path.as_str(), content.as_str())),
+      mode,
+    )
+  }
+
+  /// Convert the repo relative paths of `file_diffs` to documents dir relative paths
+  fn to_document_file_diffs(state: &AppState, file_diffs: Vec<FileDiff>) -> Result<Vec<FileDiff>> {
+    let documents_repo_path = DocumentHistory::documents_repo_path(state)?;
+    file_diffs
+      .into_iter()
+      .map(|mut file_diff| {
+        file_diff.relative_path = DocumentHistory::to_document_relative_path(
+          documents_repo_path,
+          &file_diff.relative_path,
+        )?;
+        file_diff.old_relative_path = match &file_diff.old_relative_path {
+          Some(path) => Some(DocumentHistory::to_document_relative_path(
+            documents_repo_path,
+            path,
+          )?),
+          None => None,
+        };
+        Ok(file_diff)
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+  use crate::{
+    models::diff::{DiffChangeKind, FileDiffStatus},
+    utils::test_utils::{commit_documents, temp_app_state},
+  };
+
+  /// `(path, old path, status)` of the diffs
+  fn statuses(file_diffs: &[FileDiff]) -> Vec<(&str, Option<&str>, FileDiffStatus)> {
+    file_diffs
+      .iter()
+      .map(|file_diff| {
+        (
+          file_diff.relative_path.as_str(),
+          file_diff.old_relative_path.as_deref(),
+          file_diff.status,
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn diffs_working_changes() {
+    let (state, git_utils) = temp_app_state(
+      "diff_working",
+      &[("a.md", "# A\n\nThe quick fox.\n"), ("b.md", "# B\n")],
+    );
+    assert!(commit_documents(&git_utils));
+    let documents = &state.dir_paths.documents;
+    assert!(DocumentDiff::working_changes(&state, DiffMode::Line)
+      .unwrap()
+      .is_empty());
+    fs::write(documents.join("a.md"), "# A\n\nThe slow fox.\n").unwrap();
+    fs::remove_file(documents.join("b.md")).unwrap();
+    fs::create_dir(documents.join("notes")).unwrap();
+    fs::write(documents.join("notes/c.md"), "# C\n").unwrap();
+
+    let file_diffs = DocumentDiff::working_changes(&state, DiffMode::Word).unwrap();
+    assert_eq!(
+      statuses(&file_diffs),
+      vec![
+        ("a.md", None, FileDiffStatus::Modified),
+        ("b.md", None, FileDiffStatus::Deleted),
+        ("notes/c.md", None, FileDiffStatus::Added),
+      ]
+    );
+    let words = file_diffs[0].hunks[0].words.as_ref().unwrap();
+    let changed = words
+      .iter()
+      .filter(|segment| segment.kind != DiffChangeKind::Equal)
+      .map(|segment| (segment.kind, segment.text.as_str()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      changed,
+      vec![
+        (DiffChangeKind::Removed, "quick"),
+        (DiffChangeKind::Added, "slow")
+      ]
+    );
+    assert_eq!(file_diffs[2].hunks[0].lines[0].content, "# C");
+  }
+
+  #[test]
+  fn diffs_revisions_across_renames() {
+    let content = "# Notes\n\nfirst line\nsecond line\nthird line\n";
+    let (state, git_utils) = temp_app_state("diff_revisions", &[("a.md", content)]);
+    assert!(commit_documents(&git_utils));
+    let documents = &state.dir_paths.documents;
+    fs::rename(documents.join("a.md"), documents.join("b.md")).unwrap();
+    assert!(commit_documents(&git_utils));
+    let b = DocumentPath::resolve(documents, "b.md").unwrap();
+    let revisions = DocumentHistory::revisions(&state, &b, 10).unwrap();
+    let (renamed_id, added_id) = (&revisions[0].commit_id, &revisions[1].commit_id);
+
+    let renamed = DocumentDiff::revisions(&state, &b, added_id, Some(renamed_id), DiffMode::Line)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      statuses(&[renamed.clone()]),
+      vec![("b.md", Some("a.md"), FileDiffStatus::Renamed)]
+    );
+    assert!(renamed.hunks.is_empty());
+    assert!(
+      DocumentDiff::revisions(&state, &b, renamed_id, Some(renamed_id), DiffMode::Line)
+        .unwrap()
+        .is_none()
+    );
+
+    // against the working copy
+    fs::write(&b.path, content.replace("second", "2nd")).unwrap();
+    let modified = DocumentDiff::revisions(&state, &b, added_id, None, DiffMode::Word)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      statuses(&[modified.clone()]),
+      vec![("b.md", Some("a.md"), FileDiffStatus::Renamed)]
+    );
+    let lines = modified.hunks[0]
+      .lines
+      .iter()
+      .filter(|line| line.kind != DiffChangeKind::Equal)
+      .map(|line| (line.kind, line.content.as_str()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      lines,
+      vec![
+        (DiffChangeKind::Removed, "second line"),
+        (DiffChangeKind::Added, "2nd line")
+      ]
+    );
+    fs::remove_file(&b.path).unwrap();
+    let deleted = DocumentDiff::revisions(&state, &b, renamed_id, None, DiffMode::Line)
+      .unwrap()
+      .unwrap();
+    assert_eq!(deleted.status, FileDiffStatus::Deleted);
+
+    let unknown = DocumentPath::resolve(documents, "unknown.md").unwrap();
+    assert!(DocumentDiff::revisions(&state, &unknown, added_id, None, DiffMode::Line).is_err());
+  }
+}
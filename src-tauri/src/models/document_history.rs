@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::utils::{
+  document_path::DocumentPath, git_credentials::GitCredentialProvider, git_utils::GitUtils,
+};
+
+use super::{
+  app_state::AppState,
+  document_revision::{DocumentAtRevision, DocumentRevision},
+};
+
+/// # Document History
+///
+/// Version history of documents, read from the cloud sync git repository.
+/// - Paths taken/returned are relative to the documents dir.
+pub struct DocumentHistory;
+
+impl DocumentHistory {
+  /// # Revisions
+  ///
+  /// Get the commits (newest first, at most `limit`) that changed the document.
+  /// - Renames are followed.
+  pub fn revisions(
+    state: &AppState,
+    document_path: &DocumentPath,
+    limit: usize,
+  ) -> Result<Vec<DocumentRevision>> {
+    let git_utils = Self::load_git_utils(state)?;
+    let documents_repo_path = Self::documents_repo_path(state)?;
+    let mut revisions = git_utils.file_history(
+      &document_path.relative_path.to_path(documents_repo_path),
+      limit,
+    )?;
+    for revision in revisions.iter_mut() {
+      revision.relative_path =
+        Self::to_document_relative_path(documents_repo_path, &revision.relative_path)?;
+      revision.previous_relative_path = match &revision.previous_relative_path {
+        Some(path) => Some(Self::to_document_relative_path(documents_repo_path, path)?),
+        None => None,
+      };
+    }
+    Ok(revisions)
+  }
+
+  /// # Content At
+  ///
+  /// Get the content of the document in the commit `commit_id`.
+  /// - If the document had another name in that commit, its renames are followed.
+  pub fn content_at(
+    state: &AppState,
+    document_path: &DocumentPath,
+    commit_id: &str,
+  ) -> Result<DocumentAtRevision> {
+    let git_utils = Self::load_git_utils(state)?;
+    let documents_repo_path = Self::documents_repo_path(state)?;
+    let repo_path = document_path.relative_path.to_path(documents_repo_path);
+    // paths the document had over its history (newest first)
+    let mut candidate_paths = vec![repo_path.clone()];
+    for revision in git_utils.file_history(&repo_path, usize::MAX)? {
+      let path = PathBuf::from(&revision.relative_path);
+      if !candidate_paths.contains(&path) {
+        candidate_paths.push(path);
+      }
+    }
+    for path in candidate_paths {
+      if let Ok(content) = git_utils.file_at_revision(&path, commit_id) {
+        return Ok(DocumentAtRevision {
+          commit_id: commit_id.to_string(),
+          relative_path: Self::to_document_relative_path(
+            documents_repo_path,
+            &path.to_string_lossy(),
+          )?,
+          content,
+        });
+      }
+    }
+    Err(anyhow!(
+      "`{}` not found in revision {}!",
+      document_path.relative_path,
+      commit_id
+    ))
+  }
+
+  pub fn load_git_utils(state: &AppState) -> Result<GitUtils> {
+    GitUtils::load(&state.dir_paths.root, GitCredentialProvider::default())
+      .context("Document history is only available after cloud sync is setup!")
+  }
+
+  /// Path of the documents dir relative to the repo root
+  pub fn documents_repo_path(state: &AppState) -> Result<&Path> {
+    Ok(
+      state
+        .dir_paths
+        .documents
+        .strip_prefix(&state.dir_paths.root)?,
+    )
+  }
+
+  pub fn to_document_relative_path(documents_repo_path: &Path, repo_path: &str) -> Result<String> {
+    Ok(
+      Path::new(repo_path)
+        .strip_prefix(documents_repo_path)?
+        .to_string_lossy()
+        .to_string(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+  use crate::{
+    models::document_revision::RevisionChange,
+    utils::test_utils::{commit_documents, temp_app_state},
+  };
+
+  const V1: &str = "# Notes\n\nfirst line\nsecond line\nthird line\nfourth line\n";
+  const V2: &str = "# Notes\n\nfirst line\nsecond line (edited)\nthird line\nfourth line\n";
+  const V3: &str =
+    "# Notes\n\nfirst line\nsecond line (edited)\nthird line\nfourth line\nfifth line\n";
+
+  /// Commit `a.md`, edit it, rename it to `notes/b.md`, then edit it again
+  fn setup(name: &str) -> (AppState, DocumentPath) {
+    let (state, git_utils) = temp_app_state(name, &[("a.md", V1)]);
+    let documents = &state.dir_paths.documents;
+    assert!(commit_documents(&git_utils));
+    fs::write(documents.join("a.md"), V2).unwrap();
+    assert!(commit_documents(&git_utils));
+    fs::create_dir(documents.join("notes")).unwrap();
+    fs::rename(documents.join("a.md"), documents.join("notes/b.md")).unwrap();
+    assert!(commit_documents(&git_utils));
+    fs::write(documents.join("notes/b.md"), V3).unwrap();
+    assert!(commit_documents(&git_utils));
+    let b = DocumentPath::resolve(documents, "notes/b.md").unwrap();
+    (state, b)
+  }
+
+  #[test]
+  fn follows_renames() {
+    let (state, b) = setup("history_renames");
+    let revisions = DocumentHistory::revisions(&state, &b, 10).unwrap();
+    let changes = revisions
+      .iter()
+      .map(|r| {
+        (
+          r.change,
+          r.relative_path.as_str(),
+          r.previous_relative_path.as_deref(),
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      changes,
+      vec![
+        (RevisionChange::Modified, "notes/b.md", None),
+        (RevisionChange::Renamed, "notes/b.md", Some("a.md")),
+        (RevisionChange::Modified, "a.md", None),
+        (RevisionChange::Added, "a.md", None),
+      ]
+    );
+    assert_eq!(revisions[0].author_name, "mediocre");
+    assert_eq!(revisions[0].message, "sync");
+
+    let limited = DocumentHistory::revisions(&state, &b, 2).unwrap();
+    assert_eq!(limited.len(), 2);
+    assert_eq!(limited[1].commit_id, revisions[1].commit_id);
+    // an unknown document has no history
+    let unknown = DocumentPath::resolve(&state.dir_paths.documents, "unknown.md").unwrap();
+    assert!(DocumentHistory::revisions(&state, &unknown, 10)
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn reads_the_content_at_a_revision() {
+    let (state, b) = setup("history_content");
+    let revisions = DocumentHistory::revisions(&state, &b, 10).unwrap();
+    let contents = revisions
+      .iter()
+      .map(|r| DocumentHistory::content_at(&state, &b, &r.commit_id).unwrap())
+      .map(|document| (document.relative_path, document.content))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      contents,
+      vec![
+        ("notes/b.md".to_string(), V3.to_string()),
+        ("notes/b.md".to_string(), V2.to_string()),
+        ("a.md".to_string(), V2.to_string()),
+        ("a.md".to_string(), V1.to_string()),
+      ]
+    );
+    // short ids and other revspecs work too
+    let short_id = &revisions[3].commit_id[..7];
+    assert_eq!(
+      DocumentHistory::content_at(&state, &b, short_id)
+        .unwrap()
+        .content,
+      V1
+    );
+    let git_utils = DocumentHistory::load_git_utils(&state).unwrap();
+    assert_eq!(
+      git_utils
+        .file_at_revision(Path::new("documents/a.md"), "HEAD~2")
+        .unwrap(),
+      V2
+    );
+    assert!(git_utils
+      .file_at_revision(Path::new("documents/a.md"), "HEAD")
+      .is_err());
+    assert!(DocumentHistory::content_at(&state, &b, "unknown").is_err());
+  }
+}
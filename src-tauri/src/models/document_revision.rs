@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// # Document Revision
+///
+/// A commit that changed a document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentRevision {
+  /// Id (hash) of the commit.
+  pub commit_id: String,
+  /// Path of the document in this commit.
+  pub relative_path: String,
+  /// Path of the document before this commit (only for renames).
+  pub previous_relative_path: Option<String>,
+  /// How the document was changed.
+  pub change: RevisionChange,
+  /// Commit author name.
+  pub author_name: String,
+  /// Commit author email.
+  pub author_email: String,
+  /// Commit time (RFC 3339).
+  pub time: String,
+  /// Commit message.
+  pub message: String,
+}
+
+/// # Revision Change
+///
+/// How a document was changed by a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RevisionChange {
+  Added,
+  Modified,
+  Renamed,
+  Deleted,
+}
+
+/// # Document At Revision
+///
+/// Content of a document in a commit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentAtRevision {
+  /// Id (hash) of the commit.
+  pub commit_id: String,
+  /// Path of the document in this commit.
+  pub relative_path: String,
+  /// Content of the document in this commit.
+  pub content: String,
+}
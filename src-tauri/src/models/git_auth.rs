@@ -0,0 +1,57 @@
+use std::fmt::{self, Debug};
+
+use serde::{Deserialize, Serialize};
+
+/// # Git Auth Method
+///
+/// Method used to authenticate with the git remote.
+/// - Persisted in the DB, hence never holds any secrets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum GitAuthMethod {
+  /// Use the keys loaded in the running `ssh-agent`.
+  SshAgent,
+  /// Use an SSH key loaded from file.
+  /// - The (optional) key passphrase is the auth secret.
+  #[serde(rename_all = "camelCase")]
+  SshKey {
+    private_key_path: String,
+    public_key_path: Option<String>,
+  },
+  /// Use HTTPS with user name + personal access token.
+  /// - The token is the auth secret.
+  #[serde(rename_all = "camelCase")]
+  Https { username: String },
+}
+
+impl Default for GitAuthMethod {
+  fn default() -> Self {
+    GitAuthMethod::SshAgent
+  }
+}
+
+/// # Git Auth Secret
+///
+/// Personal access token or SSH key passphrase.
+/// - Only kept in memory (never persisted to DB).
+/// - `Debug` is redacted so the secret never ends up in logs.
+#[derive(Clone, PartialEq)]
+pub struct GitAuthSecret(pub String);
+
+impl Debug for GitAuthSecret {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "GitAuthSecret(***)")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn redacts_the_secret() {
+    let secret = GitAuthSecret("ghp_token".to_string());
+    assert_eq!(format!("{:?}", secret), "GitAuthSecret(***)");
+    assert!(!format!("{:?}", Some(secret)).contains("ghp_token"));
+  }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// # Merge Conflict
+///
+/// A document left conflicted by a cloud sync merge.
+/// - Holds the content of each side of the conflict (`None` if the
+/// document does not exist on that side, ie. added/deleted).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+  /// Path of the conflicted document.
+  pub relative_path: String,
+  /// Content of the document in the common ancestor.
+  pub ancestor: Option<String>,
+  /// Content of the document in the local commit.
+  pub ours: Option<String>,
+  /// Content of the document in the remote commit.
+  pub theirs: Option<String>,
+}
+
+/// # Conflict Resolution
+///
+/// How a conflicted document should be resolved.
+/// - `Ours`: keep the local version.
+/// - `Theirs`: keep the remote version.
+/// - `Merged`: use the hand-merged `content`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "content")]
+pub enum ConflictResolution {
+  Ours,
+  Theirs,
+  Merged(String),
+}
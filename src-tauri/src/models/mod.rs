@@ -1,5 +1,18 @@
-pub mod server_error;
+pub mod app_db_state;
 pub mod app_dir_paths;
 pub mod app_state;
-pub mod app_db_state;
 pub mod cloud_sync;
+pub mod cloud_sync_config;
+pub mod command_error;
+pub mod diff;
+pub mod document_diff;
+pub mod document_history;
+pub mod document_revision;
+pub mod git_auth;
+pub mod merge_conflict;
+pub mod relocation;
+pub mod server_error;
+pub mod sync_job;
+pub mod sync_preview;
+pub mod sync_progress;
+pub mod trash;
@@ -0,0 +1,899 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use chrono::{SecondsFormat, Utc};
+use log::{error, info, warn};
+use pickledb::PickleDb;
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+  constants::db_keys::RELOCATIONS_KEY,
+  models::{app_state::AppState, server_error::ServerError},
+  utils::{
+    document_path::DocumentPath,
+    fsutils,
+    link_graph::{
+      has_md_extension, is_attachment_name, link_name, parse_links, Link, LinkKind, LinkResolver,
+    },
+    search_index::update_search_index,
+  },
+};
+
+/// Number of relocations kept to be undone
+const MAX_UNDO_RELOCATIONS: usize = 20;
+
+/// A rewritten link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkEdit {
+  /// Line of the link (1-based)
+  pub line: usize,
+  pub old_text: String,
+  pub new_text: String,
+}
+
+/// A document with rewritten links
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedFile {
+  /// Relative path of the document (after the relocation)
+  pub relative_path: String,
+  pub edits: Vec<LinkEdit>,
+}
+
+/// # Relocation Report
+///
+/// What a rename/move (or its undo) changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocationReport {
+  /// Id to undo the relocation with (`None` for previews and undos)
+  pub undo_id: Option<String>,
+  pub old_relative_path: String,
+  pub new_relative_path: String,
+  pub is_folder: bool,
+  /// `false` for a preview (nothing was changed)
+  pub applied: bool,
+  /// Documents whose links were (or would be) rewritten
+  pub changed_files: Vec<ChangedFile>,
+  /// Documents not restored by an undo as they changed after the relocation
+  pub skipped_files: Vec<String>,
+}
+
+/// Document rewritten by a relocation (stored to undo it)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RewrittenFile {
+  /// Relative path of the document (after the relocation)
+  relative_path: String,
+  original_content: String,
+  /// SHA-256 hash of the rewritten content
+  content_hash: String,
+  edits: Vec<LinkEdit>,
+}
+
+/// Applied relocation (stored in the DB to undo it)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RelocationEntry {
+  id: String,
+  old_relative_path: String,
+  new_relative_path: String,
+  is_folder: bool,
+  /// Time of the relocation (RFC 3339)
+  relocated_at: String,
+  files: Vec<RewrittenFile>,
+}
+
+/// Planned rewrite of a document
+struct FileRewrite {
+  new_relative_path: String,
+  original_content: String,
+  content: String,
+  edits: Vec<LinkEdit>,
+}
+
+/// # Relocation
+///
+/// Rename/move of a document or folder that also rewrites the links to it
+/// (wiki links, markdown links and images) in all documents.
+/// - Relative links in the moved documents are updated as well.
+/// - Applied relocations can be undone (see `undo`).
+pub struct Relocation {
+  from: DocumentPath,
+  to: DocumentPath,
+  is_folder: bool,
+}
+
+impl Relocation {
+  pub fn new(from: DocumentPath, to: DocumentPath) -> Self {
+    let is_folder = from.path.is_dir();
+    Self {
+      from,
+      to,
+      is_folder,
+    }
+  }
+
+  /// New relative path of `key` after the relocation (`None` if it is not relocated)
+  fn relocated(&self, key: &str) -> Option<String> {
+    relocated_path(
+      self.from.relative_path.as_str(),
+      self.to.relative_path.as_str(),
+      key,
+    )
+  }
+
+  /// # Preview
+  ///
+  /// Report the links that would be rewritten, nothing is changed.
+  pub fn preview(&self, documents_dir: &Path) -> Result<RelocationReport> {
+    // Same checks as `fsutils::move_path` when applied
+    if !self.from.path.exists() {
+      return Err(user_error(format!("`{}` does not exist!", self.from.relative_path)).into());
+    }
+    if self.to.path.exists() {
+      return Err(user_error(format!("`{}` already exists!", self.to.relative_path)).into());
+    }
+    let rewrites = self.plan(documents_dir)?;
+    Ok(self.report(None, false, &rewrites))
+  }
+
+  /// # Apply
+  ///
+  /// Move the document/folder and rewrite the links to it.
+  /// - Rolled back if a document can not be written.
+  /// - Updates the search index and stores the relocation to undo it.
+  pub fn apply(self, state: &AppState, db: &mut PickleDb) -> Result<RelocationReport> {
+    let documents_dir = &state.dir_paths.documents;
+    let rewrites = self.plan(documents_dir)?;
+    fsutils::move_path(&self.from.path, &self.to.path)?;
+    let now = Utc::now();
+    let mut entry = RelocationEntry {
+      id: now.format("%Y%m%d%H%M%S%f").to_string(),
+      old_relative_path: self.from.relative_path.to_string(),
+      new_relative_path: self.to.relative_path.to_string(),
+      is_folder: self.is_folder,
+      relocated_at: now.to_rfc3339_opts(SecondsFormat::Millis, true),
+      files: vec![],
+    };
+    for rewrite in rewrites.iter() {
+      let file_path = RelativePath::new(&rewrite.new_relative_path).to_path(documents_dir);
+      if let Err(e) = fsutils::write_to_path(file_path.as_path(), rewrite.content.clone()) {
+        error!(
+          "failed to rewrite links in {}, rolling back: {}",
+          rewrite.new_relative_path, e
+        );
+        if let Err(rollback_error) = revert(documents_dir, &entry) {
+          error!("relocation rollback failed: {:?}", rollback_error);
+        }
+        return Err(e.into());
+      }
+      entry.files.push(RewrittenFile {
+        relative_path: rewrite.new_relative_path.clone(),
+        original_content: rewrite.original_content.clone(),
+        content_hash: fsutils::get_content_hash(&rewrite.content),
+        edits: rewrite.edits.clone(),
+      });
+    }
+    let mut entries = entries(db);
+    entries.push(entry.clone());
+    let excess = entries.len().saturating_sub(MAX_UNDO_RELOCATIONS);
+    entries.drain(..excess);
+    save_entries(db, &entries)?;
+    info!(
+      "relocated {} -> {}, rewrote links in {} documents",
+      entry.old_relative_path,
+      entry.new_relative_path,
+      entry.files.len()
+    );
+    update_index(state, &entry, false);
+    state.sync_scheduler.notify_document_changed();
+    Ok(self.report(Some(entry.id), true, &rewrites))
+  }
+
+  /// Check if the relocation `id` can still be undone
+  pub fn can_undo(db: &PickleDb, id: &str) -> bool {
+    entries(db).iter().any(|entry| entry.id == id)
+  }
+
+  /// # Undo
+  ///
+  /// Move the document/folder of the relocation `id` back and restore the rewritten links.
+  /// - Documents changed since the relocation are kept as is (see `skipped_files`).
+  pub fn undo(state: &AppState, db: &mut PickleDb, id: &str) -> Result<RelocationReport> {
+    let documents_dir = &state.dir_paths.documents;
+    let mut entries = entries(db);
+    let index = entries
+      .iter()
+      .position(|entry| entry.id == id)
+      .ok_or_else(|| anyhow!("Relocation `{}` not found!", id))?;
+    let entry = entries[index].clone();
+    let skipped_files = revert(documents_dir, &entry)?;
+    entries.remove(index);
+    save_entries(db, &entries)?;
+    info!(
+      "undid relocation {} -> {}",
+      entry.old_relative_path, entry.new_relative_path
+    );
+    update_index(state, &entry, true);
+    state.sync_scheduler.notify_document_changed();
+    let changed_files = entry
+      .files
+      .iter()
+      .filter(|file| !skipped_files.contains(&file.relative_path))
+      .map(|file| ChangedFile {
+        relative_path: entry.restored_path(&file.relative_path),
+        edits: file
+          .edits
+          .iter()
+          .map(|edit| LinkEdit {
+            line: edit.line,
+            old_text: edit.new_text.clone(),
+            new_text: edit.old_text.clone(),
+          })
+          .collect(),
+      })
+      .collect();
+    Ok(RelocationReport {
+      undo_id: None,
+      old_relative_path: entry.new_relative_path.clone(),
+      new_relative_path: entry.old_relative_path.clone(),
+      is_folder: entry.is_folder,
+      applied: true,
+      changed_files,
+      skipped_files,
+    })
+  }
+
+  fn report(
+    &self,
+    undo_id: Option<String>,
+    applied: bool,
+    rewrites: &[FileRewrite],
+  ) -> RelocationReport {
+    RelocationReport {
+      undo_id,
+      old_relative_path: self.from.relative_path.to_string(),
+      new_relative_path: self.to.relative_path.to_string(),
+      is_folder: self.is_folder,
+      applied,
+      changed_files: rewrites
+        .iter()
+        .map(|rewrite| ChangedFile {
+          relative_path: rewrite.new_relative_path.clone(),
+          edits: rewrite.edits.clone(),
+        })
+        .collect(),
+      skipped_files: vec![],
+    }
+  }
+
+  /// Find the links to rewrite in all documents
+  fn plan(&self, documents_dir: &Path) -> Result<Vec<FileRewrite>> {
+    let files = list_files(documents_dir);
+    let resolver = LinkResolver::for_files(&files);
+    // link name -> number of files with that name after the relocation
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for key in files.iter() {
+      let key = self.relocated(key).unwrap_or_else(|| key.clone());
+      if let Some(name) = link_name(&key) {
+        *name_counts.entry(name).or_insert(0) += 1;
+      }
+    }
+    let mut rewrites = vec![];
+    for key in files.iter().filter(|key| has_md_extension(key)) {
+      let content = fs::read_to_string(RelativePath::new(key).to_path(documents_dir))?;
+      let new_key = self.relocated(key);
+      let source = new_key.as_deref().unwrap_or(key);
+      let mut rewritten = String::with_capacity(content.len());
+      let mut edits = vec![];
+      let mut cursor = 0;
+      let mut links = parse_links(&content);
+      // Nested links (eg. an image in a link label) come before the outer target
+      links.sort_by_key(|link| link.target_range.start);
+      for link in links {
+        let target = match resolver.resolve_target(key, &link) {
+          Some(target) if !link.target.is_empty() && link.target_range.start >= cursor => target,
+          _ => continue,
+        };
+        let new_target = self.relocated(&target);
+        if new_key.is_none() && new_target.is_none() {
+          continue;
+        }
+        let old_text = &content[link.target_range.clone()];
+        let new_text = match new_target_text(
+          &link,
+          &content,
+          source,
+          (&target, new_target.as_deref()),
+          &name_counts,
+        ) {
+          Some(new_text) if new_text != old_text => new_text,
+          _ => continue,
+        };
+        rewritten.push_str(&content[cursor..link.target_range.start]);
+        rewritten.push_str(&new_text);
+        cursor = link.target_range.end;
+        edits.push(LinkEdit {
+          line: link.line,
+          old_text: content[link.range.clone()].to_string(),
+          new_text: format!(
+            "{}{}{}",
+            &content[link.range.start..link.target_range.start],
+            new_text,
+            &content[link.target_range.end..link.range.end]
+          ),
+        });
+      }
+      if edits.is_empty() {
+        continue;
+      }
+      rewritten.push_str(&content[cursor..]);
+      rewrites.push(FileRewrite {
+        new_relative_path: source.to_string(),
+        original_content: content,
+        content: rewritten,
+        edits,
+      });
+    }
+    Ok(rewrites)
+  }
+}
+
+impl RelocationEntry {
+  /// Relative path of the relocated `key` once the relocation is undone
+  fn restored_path(&self, key: &str) -> String {
+    relocated_path(&self.new_relative_path, &self.old_relative_path, key)
+      .unwrap_or_else(|| key.to_string())
+  }
+}
+
+fn user_error(message: String) -> ServerError {
+  ServerError::UserError { message }
+}
+
+/// New path of `key` when `from` is relocated to `to` (`None` if `key` is not in `from`)
+fn relocated_path(from: &str, to: &str, key: &str) -> Option<String> {
+  if key == from {
+    Some(to.to_string())
+  } else if key.starts_with(from) && key[from.len()..].starts_with('/') {
+    Some(format!("{}{}", to, &key[from.len()..]))
+  } else {
+    None
+  }
+}
+
+/// New target text of the `link` in the document `source` (path after the relocation)
+/// - `target`: (key, key after the relocation) of the linked file
+/// - Keeps the style of the link: relative/absolute path, wiki link by name/path.
+fn new_target_text(
+  link: &Link,
+  content: &str,
+  source: &str,
+  target: (&str, Option<&str>),
+  name_counts: &HashMap<String, usize>,
+) -> Option<String> {
+  let (old_target, new_target) = target;
+  let is_target_relocated = new_target.is_some();
+  let new_target = new_target.unwrap_or(old_target);
+  match link.kind {
+    LinkKind::Wiki | LinkKind::WikiEmbed => {
+      let keep_extension = has_md_extension(&link.target) || is_attachment_name(&link.target);
+      let link_path = |path: &str| match path.strip_suffix(".md") {
+        Some(path) if !keep_extension => path.to_string(),
+        _ => path.to_string(),
+      };
+      if link.target.contains('/') {
+        // Paths from the root still work when only the linking document moved
+        let is_root_path = link
+          .target
+          .trim_start_matches('/')
+          .eq_ignore_ascii_case(&link_path(old_target));
+        if !is_target_relocated && is_root_path {
+          return None;
+        }
+        return Some(link_path(new_target));
+      }
+      // Links by name only break when the file name changes
+      let old_name = RelativePath::new(old_target).file_name()?;
+      let new_name = RelativePath::new(new_target).file_name()?;
+      if old_name == new_name {
+        return None;
+      }
+      let is_ambiguous = link_name(new_target)
+        .and_then(|name| name_counts.get(&name))
+        .map_or(false, |count| *count > 1);
+      Some(link_path(if is_ambiguous { new_target } else { new_name }))
+    }
+    LinkKind::Markdown | LinkKind::Reference | LinkKind::Image => {
+      let path = if link.target.starts_with('/') {
+        format!("/{}", new_target)
+      } else {
+        let source_dir = RelativePath::new(source)
+          .parent()
+          .unwrap_or_else(|| RelativePath::new(""));
+        relative_link_path(source_dir, RelativePath::new(new_target))
+      };
+      let is_angle_bracketed =
+        link.target_range.start > 0 && content.as_bytes()[link.target_range.start - 1] == b'<';
+      Some(if is_angle_bracketed {
+        path
+      } else {
+        encode_link_path(&path)
+      })
+    }
+  }
+}
+
+/// Relative path from the folder `from_dir` to `to` (eg. `a/b`, `a/c.md` -> `../c.md`)
+fn relative_link_path(from_dir: &RelativePath, to: &RelativePath) -> String {
+  let from_parts: Vec<&str> = from_dir.iter().collect();
+  let to_parts: Vec<&str> = to.iter().collect();
+  let common = from_parts
+    .iter()
+    .zip(to_parts.iter())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let mut parts = vec![".."; from_parts.len() - common];
+  parts.extend(to_parts[common..].iter());
+  parts.join("/")
+}
+
+/// Escape the chars that end a markdown link destination
+fn encode_link_path(path: &str) -> String {
+  path
+    .replace('%', "%25")
+    .replace(' ', "%20")
+    .replace('(', "%28")
+    .replace(')', "%29")
+}
+
+/// Relative paths of all files in the documents dir (skips the git dir and temp files)
+fn list_files(documents_dir: &Path) -> Vec<String> {
+  WalkDir::new(documents_dir)
+    .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+    .into_iter()
+    .filter_entry(|entry| entry.file_name() != ".git")
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.file_type().is_file() && !fsutils::is_temp_file(entry.path()))
+    .filter_map(|entry| {
+      let relative_path = entry.path().strip_prefix(documents_dir).ok()?;
+      match RelativePathBuf::from_path(relative_path) {
+        Ok(relative_path) => Some(relative_path.normalize().to_string()),
+        Err(e) => {
+          warn!("skipping {:?}: {}", relative_path, e);
+          None
+        }
+      }
+    })
+    .collect()
+}
+
+/// Move the relocated document/folder back and restore the rewritten documents.
+/// Returns the documents (relative path after the relocation) that changed since.
+fn revert(documents_dir: &Path, entry: &RelocationEntry) -> Result<Vec<String>> {
+  let from = DocumentPath::resolve(documents_dir, &entry.new_relative_path)?;
+  let to = DocumentPath::resolve(documents_dir, &entry.old_relative_path)?;
+  fsutils::move_path(&from.path, &to.path)?;
+  let mut skipped_files = vec![];
+  for file in entry.files.iter() {
+    let file_path =
+      RelativePath::new(&entry.restored_path(&file.relative_path)).to_path(documents_dir);
+    let is_unchanged = fsutils::read_from_path(&file_path)
+      .map(|content| fsutils::get_content_hash(&content) == file.content_hash)
+      .unwrap_or(false);
+    if !is_unchanged {
+      warn!(
+        "not restoring {}, changed since the relocation",
+        file.relative_path
+      );
+      skipped_files.push(file.relative_path.clone());
+      continue;
+    }
+    fsutils::write_to_path(file_path.as_path(), file.original_content.clone())?;
+  }
+  Ok(skipped_files)
+}
+
+/// Update the search index after the relocation `entry` was applied (or `undone`)
+fn update_index(state: &AppState, entry: &RelocationEntry, undone: bool) {
+  let documents_dir = &state.dir_paths.documents;
+  let (from, to) = if undone {
+    (&entry.new_relative_path, &entry.old_relative_path)
+  } else {
+    (&entry.old_relative_path, &entry.new_relative_path)
+  };
+  update_search_index(&state.search_index, |index| {
+    if entry.is_folder {
+      index.rename_folder(from, to);
+    } else {
+      index.rename_document(from, to);
+    }
+    for file in entry.files.iter() {
+      let relative_path = if undone {
+        entry.restored_path(&file.relative_path)
+      } else {
+        file.relative_path.clone()
+      };
+      index.index_file(documents_dir, &relative_path)?;
+    }
+    Ok(())
+  });
+}
+
+fn entries(db: &PickleDb) -> Vec<RelocationEntry> {
+  db.get::<Vec<RelocationEntry>>(RELOCATIONS_KEY)
+    .unwrap_or_default()
+}
+
+fn save_entries(db: &mut PickleDb, entries: &[RelocationEntry]) -> Result<()> {
+  db.set(RELOCATIONS_KEY, &entries)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::test_utils::temp_documents_dir;
+
+  fn relocation(documents_dir: &Path, from: &str, to: &str) -> Relocation {
+    Relocation::new(
+      DocumentPath::resolve(documents_dir, from).unwrap(),
+      DocumentPath::resolve(documents_dir, to).unwrap(),
+    )
+  }
+
+  fn read(documents_dir: &Path, relative_path: &str) -> String {
+    fs::read_to_string(documents_dir.join(relative_path)).unwrap()
+  }
+
+  /// `(new relative path, rewritten content)` of the planned rewrites
+  fn planned(documents_dir: &Path, relocation: &Relocation) -> Vec<(String, String)> {
+    let mut rewrites: Vec<(String, String)> = relocation
+      .plan(documents_dir)
+      .unwrap()
+      .into_iter()
+      .map(|rewrite| (rewrite.new_relative_path, rewrite.content))
+      .collect();
+    rewrites.sort();
+    rewrites
+  }
+
+  /// Same as `Relocation::apply` without the search index/DB
+  fn apply(documents_dir: &Path, relocation: &Relocation) -> RelocationEntry {
+    let rewrites = relocation.plan(documents_dir).unwrap();
+    fsutils::move_path(&relocation.from.path, &relocation.to.path).unwrap();
+    let files = rewrites
+      .into_iter()
+      .map(|rewrite| {
+        fs::write(
+          documents_dir.join(&rewrite.new_relative_path),
+          &rewrite.content,
+        )
+        .unwrap();
+        RewrittenFile {
+          relative_path: rewrite.new_relative_path,
+          content_hash: fsutils::get_content_hash(&rewrite.content),
+          original_content: rewrite.original_content,
+          edits: rewrite.edits,
+        }
+      })
+      .collect();
+    RelocationEntry {
+      id: "test".to_string(),
+      old_relative_path: relocation.from.relative_path.to_string(),
+      new_relative_path: relocation.to.relative_path.to_string(),
+      is_folder: relocation.is_folder,
+      relocated_at: String::new(),
+      files,
+    }
+  }
+
+  fn rewrite(path: &str, content: &str) -> (String, String) {
+    (path.to_string(), content.to_string())
+  }
+
+  #[test]
+  fn relocates_paths() {
+    for (from, to, key, expected) in &[
+      ("a.md", "b.md", "a.md", Some("b.md")),
+      ("a.md", "b.md", "a.md.md", None),
+      (
+        "notes",
+        "archive/notes",
+        "notes/a.md",
+        Some("archive/notes/a.md"),
+      ),
+      ("notes", "archive/notes", "notes2/a.md", None),
+      ("notes", "archive/notes", "notes", Some("archive/notes")),
+    ] {
+      assert_eq!(relocated_path(from, to, key).as_deref(), *expected);
+    }
+  }
+
+  #[test]
+  fn makes_relative_link_paths() {
+    for (from_dir, to, expected) in &[
+      ("", "a.md", "a.md"),
+      ("", "notes/a.md", "notes/a.md"),
+      ("notes", "notes/a.md", "a.md"),
+      ("notes", "a.md", "../a.md"),
+      ("a/b", "a/c.md", "../c.md"),
+      ("a/b", "a/b/c/d.md", "c/d.md"),
+      ("x/y", "a/b.md", "../../a/b.md"),
+    ] {
+      assert_eq!(
+        relative_link_path(RelativePath::new(from_dir), RelativePath::new(to)),
+        *expected,
+        "{} -> {}",
+        from_dir,
+        to
+      );
+    }
+  }
+
+  #[test]
+  fn makes_new_target_texts() {
+    let name_counts: HashMap<String, usize> = vec![("b".to_string(), 1), ("a".to_string(), 2)]
+      .into_iter()
+      .collect();
+    // (link, source after the relocation, target, target after the relocation, expected)
+    for (content, source, target, new_target, expected) in &[
+      // wiki links by name only change with the name
+      ("[[a]]", "s.md", "notes/a.md", Some("notes/b.md"), Some("b")),
+      (
+        "[[a.md#H|l]]",
+        "s.md",
+        "notes/a.md",
+        Some("notes/b.md"),
+        Some("b.md"),
+      ),
+      (
+        "![[a.png]]",
+        "s.md",
+        "img/a.png",
+        Some("img/b.png"),
+        Some("b.png"),
+      ),
+      ("[[a]]", "s.md", "notes/a.md", Some("other/a.md"), None),
+      // ... or by path if the new name is ambiguous
+      (
+        "[[b]]",
+        "s.md",
+        "notes/b.md",
+        Some("notes/a.md"),
+        Some("notes/a"),
+      ),
+      // wiki links by path
+      (
+        "[[notes/a]]",
+        "s.md",
+        "notes/a.md",
+        Some("other/a.md"),
+        Some("other/a"),
+      ),
+      (
+        "[[/notes/a.md]]",
+        "s.md",
+        "notes/a.md",
+        Some("other/a.md"),
+        Some("other/a.md"),
+      ),
+      ("[[notes/a]]", "x/s.md", "notes/a.md", None, None),
+      (
+        "[[deep/a]]",
+        "x/s.md",
+        "notes/deep/a.md",
+        None,
+        Some("notes/deep/a"),
+      ),
+      // markdown links keep relative/root paths
+      (
+        "[l](../notes/a.md)",
+        "x/s.md",
+        "notes/a.md",
+        Some("other/a.md"),
+        Some("../other/a.md"),
+      ),
+      (
+        "[l](notes/a.md#H)",
+        "x/s.md",
+        "notes/a.md",
+        None,
+        Some("../notes/a.md"),
+      ),
+      (
+        "[l](/notes/a.md)",
+        "x/s.md",
+        "notes/a.md",
+        Some("other/a.md"),
+        Some("/other/a.md"),
+      ),
+      (
+        "[l]: a.md",
+        "s.md",
+        "a.md",
+        Some("my (a).md"),
+        Some("my%20%28a%29.md"),
+      ),
+      (
+        "[l](<a.md>)",
+        "s.md",
+        "a.md",
+        Some("my (a).md"),
+        Some("my (a).md"),
+      ),
+      (
+        "![l](a%25.png)",
+        "s.md",
+        "a%.png",
+        Some("b%.png"),
+        Some("b%25.png"),
+      ),
+    ] {
+      let link = &parse_links(content)[0];
+      assert_eq!(
+        new_target_text(link, content, source, (target, *new_target), &name_counts).as_deref(),
+        *expected,
+        "{:?} in {}",
+        content,
+        source
+      );
+    }
+  }
+
+  #[test]
+  fn plans_document_move() {
+    let documents_dir = temp_documents_dir(
+      "relocation_move",
+      &[
+        (
+          "b.md",
+          "[[a]] [[notes/a]] [a](notes/a.md#x) [a](<notes/a.md>)",
+        ),
+        (
+          "notes/a.md",
+          "[b](../b.md) [[b]] [c](c.md) [self](#x) [web](https://a.md)",
+        ),
+        ("notes/c.md", "[a](a.md)\n```\n[a](a.md)\n```\n"),
+        ("archive/d.md", ""),
+      ],
+    );
+    let relocation = relocation(&documents_dir, "notes/a.md", "archive/a.md");
+    assert_eq!(
+      planned(&documents_dir, &relocation),
+      vec![
+        rewrite(
+          "archive/a.md",
+          "[b](../b.md) [[b]] [c](../notes/c.md) [self](#x) [web](https://a.md)"
+        ),
+        rewrite(
+          "b.md",
+          "[[a]] [[archive/a]] [a](archive/a.md#x) [a](<archive/a.md>)"
+        ),
+        rewrite("notes/c.md", "[a](../archive/a.md)\n```\n[a](a.md)\n```\n"),
+      ]
+    );
+    let report = relocation.preview(&documents_dir).unwrap();
+    assert!(!report.applied);
+    assert_eq!(report.changed_files.len(), 3);
+    let b_edits: Vec<&str> = report
+      .changed_files
+      .iter()
+      .find(|file| file.relative_path == "b.md")
+      .unwrap()
+      .edits
+      .iter()
+      .map(|edit| edit.new_text.as_str())
+      .collect();
+    assert_eq!(
+      b_edits,
+      vec![
+        "[[archive/a]]",
+        "[a](archive/a.md#x)",
+        "[a](<archive/a.md>)"
+      ]
+    );
+    // nothing changed by the plan/preview
+    assert!(documents_dir.join("notes/a.md").exists());
+  }
+
+  #[test]
+  fn plans_document_rename() {
+    let documents_dir = temp_documents_dir(
+      "relocation_rename",
+      &[
+        ("a.md", ""),
+        ("notes/b.md", "[[x]] [[c]]"),
+        (
+          "notes/x.md",
+          "[[b]] [[notes/b]] [[b#H|label]] [b](b.md)\n[b]: <b.md>",
+        ),
+      ],
+    );
+    // `a` would be ambiguous: links by name switch to the path
+    let ambiguous_rename = relocation(&documents_dir, "notes/b.md", "notes/a.md");
+    assert_eq!(
+      planned(&documents_dir, &ambiguous_rename),
+      vec![rewrite(
+        "notes/x.md",
+        "[[notes/a]] [[notes/a]] [[notes/a#H|label]] [b](a.md)\n[b]: <a.md>"
+      )]
+    );
+    let rename = relocation(&documents_dir, "notes/b.md", "notes/new b.md");
+    assert_eq!(
+      planned(&documents_dir, &rename),
+      vec![rewrite(
+        "notes/x.md",
+        "[[new b]] [[notes/new b]] [[new b#H|label]] [b](new%20b.md)\n[b]: <new b.md>"
+      )]
+    );
+  }
+
+  #[test]
+  fn plans_folder_move() {
+    let documents_dir = temp_documents_dir(
+      "relocation_folder",
+      &[
+        ("b.md", "[a](notes/a.md) [[notes/a]] [[a]]"),
+        ("notes/a.md", "[b](../b.md) [c](c.md) [[c]] ![i](img/i.png)"),
+        ("notes/c.md", ""),
+        ("notes/img/i.png", ""),
+        ("archive/d.md", ""),
+      ],
+    );
+    let relocation = relocation(&documents_dir, "notes", "archive/notes");
+    assert!(relocation.is_folder);
+    assert_eq!(
+      planned(&documents_dir, &relocation),
+      vec![
+        rewrite(
+          "archive/notes/a.md",
+          "[b](../../b.md) [c](c.md) [[c]] ![i](img/i.png)"
+        ),
+        rewrite("b.md", "[a](archive/notes/a.md) [[archive/notes/a]] [[a]]"),
+      ]
+    );
+  }
+
+  #[test]
+  fn reverts_relocation() {
+    let documents_dir = temp_documents_dir(
+      "relocation_revert",
+      &[
+        ("b.md", "[a](notes/a.md)"),
+        ("c.md", "[[notes/a]]"),
+        ("notes/a.md", "[b](../b.md)"),
+      ],
+    );
+    let relocation = relocation(&documents_dir, "notes/a.md", "a.md");
+    let entry = apply(&documents_dir, &relocation);
+    assert!(!documents_dir.join("notes/a.md").exists());
+    assert_eq!(read(&documents_dir, "a.md"), "[b](b.md)");
+    assert_eq!(read(&documents_dir, "b.md"), "[a](a.md)");
+    assert_eq!(read(&documents_dir, "c.md"), "[[a]]");
+    // edited since the relocation: not restored
+    fs::write(documents_dir.join("c.md"), "[[a]] edited").unwrap();
+
+    let skipped_files = revert(&documents_dir, &entry).unwrap();
+    assert_eq!(skipped_files, vec!["c.md"]);
+    assert!(!documents_dir.join("a.md").exists());
+    assert_eq!(read(&documents_dir, "notes/a.md"), "[b](../b.md)");
+    assert_eq!(read(&documents_dir, "b.md"), "[a](notes/a.md)");
+    assert_eq!(read(&documents_dir, "c.md"), "[[a]] edited");
+  }
+
+  #[test]
+  fn rejects_preview_of_missing_or_existing_paths() {
+    let documents_dir = temp_documents_dir("relocation_preview", &[("a.md", ""), ("b.md", "")]);
+    assert!(relocation(&documents_dir, "missing.md", "c.md")
+      .preview(&documents_dir)
+      .is_err());
+    assert!(relocation(&documents_dir, "a.md", "b.md")
+      .preview(&documents_dir)
+      .is_err());
+  }
+}
@@ -1,4 +1,4 @@
-use std::error::Error;
+use std::io;
 
 use derive_more::{Display, Error};
 
@@ -6,28 +6,33 @@ use derive_more::{Display, Error};
 pub enum ServerError {
   #[display(fmt = "{}", message)]
   UserError { message: String },
+  /// The file/folder does not exist
+  #[display(fmt = "{}", message)]
+  NotFoundError { message: String },
+  /// The file/folder already exists
+  #[display(fmt = "{}", message)]
+  AlreadyExistsError { message: String },
   #[display(fmt = "{}", message)]
   InternalError { message: String },
 }
 impl ServerError {
-  /// Get `name` of the error or `type` of error
-  fn name(&self) -> String {
-    match self {
-      ServerError::InternalError { message: _ } => "InternalError".to_string(),
-      ServerError::UserError { message: _ } => "UserError".to_string(),
-    }
-  }
   /// Get Error Message
-  fn message(&self) -> String {
+  pub fn message(&self) -> String {
     match self {
       ServerError::InternalError { message } => message.to_string(),
       ServerError::UserError { message } => message.to_string(),
+      ServerError::NotFoundError { message } => message.to_string(),
+      ServerError::AlreadyExistsError { message } => message.to_string(),
     }
   }
 }
 
-pub fn map_to_server_error<T: Error>(e: T) -> ServerError {
-  ServerError::InternalError {
-    message: e.to_string(),
+/// Map an `io::Error` (missing/existing files keep their kind)
+pub fn map_to_server_error(e: io::Error) -> ServerError {
+  let message = e.to_string();
+  match e.kind() {
+    io::ErrorKind::NotFound => ServerError::NotFoundError { message },
+    io::ErrorKind::AlreadyExists => ServerError::AlreadyExistsError { message },
+    _ => ServerError::InternalError { message },
   }
 }
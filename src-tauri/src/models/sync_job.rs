@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+  cloud_sync::CloudSyncStatus, cloud_sync_config::CloudSyncConfig, command_error::CommandError,
+  merge_conflict::MergeConflict, sync_preview::SyncPreview,
+};
+
+/// What a cloud sync job runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncJobKind {
+  Setup,
+  Sync,
+  CompleteMerge,
+  /// Run by the auto-sync scheduler.
+  AutoSync,
+  /// Dry run of a sync (only fetches the remote).
+  Preview,
+  SwitchBranch,
+}
+
+/// State of a cloud sync job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncJobStatus {
+  Running,
+  /// Finished, see the `outcome` of the job.
+  Completed,
+  /// Finished with an error, see the `error` of the job.
+  Failed,
+  Cancelled,
+}
+
+/// # Sync Job Output
+///
+/// What a job returns once it succeeds (kept in its `SyncJob`).
+#[derive(Debug, Clone)]
+pub enum SyncJobOutput {
+  Status(CloudSyncStatus),
+  Preview(SyncPreview),
+  Config(CloudSyncConfig),
+}
+
+impl From<CloudSyncStatus> for SyncJobOutput {
+  fn from(status: CloudSyncStatus) -> Self {
+    Self::Status(status)
+  }
+}
+
+impl From<SyncPreview> for SyncJobOutput {
+  fn from(preview: SyncPreview) -> Self {
+    Self::Preview(preview)
+  }
+}
+
+impl From<CloudSyncConfig> for SyncJobOutput {
+  fn from(config: CloudSyncConfig) -> Self {
+    Self::Config(config)
+  }
+}
+
+/// # Sync Job
+///
+/// A cloud sync (setup/sync/merge completion/preview/branch switch)
+/// running in the background.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncJob {
+  pub id: String,
+  pub kind: SyncJobKind,
+  pub status: SyncJobStatus,
+  /// Outcome of the sync once `Completed`
+  pub outcome: Option<CloudSyncStatus>,
+  /// Sync preview once a `Preview` job is `Completed`
+  pub preview: Option<SyncPreview>,
+  /// Updated config once a `SwitchBranch` job is `Completed`
+  pub config: Option<CloudSyncConfig>,
+  /// Conflicted documents, if the sync stopped on merge conflicts
+  pub conflicts: Option<Vec<MergeConflict>>,
+  /// Error once `Failed` (same as the commands return, eg. `NETWORK_ERROR` is `retryable`)
+  pub error: Option<CommandError>,
+  /// `true` once cancelling was requested (the job stops at the next checkpoint)
+  pub cancel_requested: bool,
+  /// Time the job started (RFC 3339)
+  pub started_at: String,
+  /// Time the job finished (RFC 3339)
+  pub finished_at: Option<String>,
+}
+
+impl SyncJob {
+  pub fn is_running(&self) -> bool {
+    self.status == SyncJobStatus::Running
+  }
+}
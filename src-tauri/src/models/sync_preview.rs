@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use super::diff::FileChange;
+
+/// # Sync Preview
+///
+/// What a cloud sync would do, computed from a fetch only (nothing is merged or pushed).
+/// - Changes are relative to the common ancestor of the local and remote branches.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPreview {
+  /// Local documents changes (uncommitted + committed, not yet pushed).
+  pub local_changes: Vec<FileChange>,
+  /// Remote documents changes not yet merged locally.
+  pub remote_changes: Vec<FileChange>,
+  /// Number of local commits not on the remote.
+  pub ahead: usize,
+  /// Number of remote commits not merged locally.
+  pub behind: usize,
+  /// `true` if the remote changes can be applied as a fast-forward
+  /// (ie. there are no local changes).
+  pub can_fast_forward: bool,
+  /// Documents that would conflict when merging the remote changes.
+  pub conflicts: Vec<String>,
+}
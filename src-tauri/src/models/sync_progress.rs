@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// # Sync Phase
+///
+/// Step of a cloud sync network/checkout operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncPhase {
+  /// Receiving objects from the remote.
+  Receiving,
+  /// Resolving the deltas of the received objects.
+  ResolvingDeltas,
+  /// Updating the documents from the fetched/merged commits.
+  Checkout,
+  /// Building the pack of objects to push.
+  Packing,
+  /// Sending objects to the remote.
+  Sending,
+}
+
+/// # Sync Progress
+///
+/// Progress of the current sync phase, sent to the window
+/// as `cloud_sync_progress` events (throttled).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+  pub phase: SyncPhase,
+  /// Work done in the phase (objects/deltas/files), use with `total` for progress bars.
+  pub current: usize,
+  /// Total work of the phase (`0` if unknown yet).
+  pub total: usize,
+  /// Objects received from the remote (fetch only).
+  pub received_objects: usize,
+  /// Objects to receive/send.
+  pub total_objects: usize,
+  /// Deltas resolved (fetch only).
+  pub indexed_deltas: usize,
+  /// Deltas to resolve (fetch only).
+  pub total_deltas: usize,
+  /// Bytes received (fetch) or sent (push).
+  pub bytes: usize,
+}
+
+impl SyncProgress {
+  /// Progress of `phase` without transfer statistics
+  pub fn new(phase: SyncPhase, current: usize, total: usize) -> Self {
+    Self {
+      phase,
+      current,
+      total,
+      received_objects: 0,
+      total_objects: 0,
+      indexed_deltas: 0,
+      total_deltas: 0,
+      bytes: 0,
+    }
+  }
+
+  /// `true` if all the work of the phase is done
+  pub fn is_complete(&self) -> bool {
+    self.total > 0 && self.current >= self.total
+  }
+}
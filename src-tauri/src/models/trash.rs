@@ -0,0 +1,441 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use log::{info, warn};
+use pickledb::PickleDb;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  constants::db_keys::{TRASH_ENTRIES_KEY, TRASH_RETENTION_DAYS_KEY},
+  utils::{document_path::DocumentPath, fsutils},
+};
+
+use super::app_dir_paths::AppDirPaths;
+
+/// Default number of days trashed documents are kept before being purged
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+/// # Trash Entry
+///
+/// A deleted document (or folder, with all its documents) kept in the trash dir.
+/// - The document/folder is stored at `<trash dir>/<id>/<file name>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+  /// Unique id of the entry (also the name of its dir in the trash dir)
+  pub id: String,
+  /// Path of the document/folder (relative to the documents dir) before it was deleted
+  pub original_relative_path: String,
+  /// Time the document/folder was deleted (RFC 3339)
+  pub deleted_at: String,
+  /// `true` if a whole folder was deleted
+  #[serde(default)]
+  pub is_folder: bool,
+}
+
+impl TrashEntry {
+  /// Dir holding the trashed document/folder
+  fn dir_path(&self, trash_dir: &Path) -> PathBuf {
+    trash_dir.join(&self.id)
+  }
+
+  /// Path of the trashed document/folder
+  fn file_path(&self, trash_dir: &Path) -> PathBuf {
+    let file_name = Path::new(&self.original_relative_path)
+      .file_name()
+      .map(|n| n.to_os_string())
+      .unwrap_or_default();
+    self.dir_path(trash_dir).join(file_name)
+  }
+
+  /// Check if the entry is older than the `retention_days`
+  fn is_expired(&self, retention_days: u64) -> bool {
+    match DateTime::parse_from_rfc3339(&self.deleted_at) {
+      Ok(deleted_at) => {
+        Utc::now().signed_duration_since(deleted_at) > Duration::days(retention_days as i64)
+      }
+      Err(e) => {
+        warn!("invalid deleted_at of trash entry {}: {}", self.id, e);
+        false
+      }
+    }
+  }
+}
+
+/// # Trash
+///
+/// Soft-delete for documents/folders. Deleted documents are moved to the trash dir
+/// (`~/.mediocre/.trash`, outside the synced documents dir) and their
+/// original path + deletion time are stored in the DB.
+pub struct Trash;
+
+impl Trash {
+  /// Get all trash entries (oldest first)
+  pub fn entries(db: &PickleDb) -> Vec<TrashEntry> {
+    db.get::<Vec<TrashEntry>>(TRASH_ENTRIES_KEY)
+      .unwrap_or_default()
+  }
+
+  fn save_entries(db: &mut PickleDb, entries: &[TrashEntry]) -> Result<()> {
+    db.set(TRASH_ENTRIES_KEY, &entries)?;
+    Ok(())
+  }
+
+  /// Number of days trashed documents are kept (`0` to keep them forever)
+  pub fn retention_days(db: &PickleDb) -> u64 {
+    db.get::<u64>(TRASH_RETENTION_DAYS_KEY)
+      .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+  }
+
+  /// Set the number of days trashed documents are kept (`0` to keep them forever)
+  pub fn set_retention_days(db: &mut PickleDb, retention_days: u64) -> Result<()> {
+    db.set(TRASH_RETENTION_DAYS_KEY, &retention_days)?;
+    Ok(())
+  }
+
+  /// # Trash Document
+  ///
+  /// Move the document at `document_path` to the trash.
+  /// - Entries older than the retention policy are purged.
+  pub fn trash_document(
+    dir_paths: &AppDirPaths,
+    db: &mut PickleDb,
+    document_path: &DocumentPath,
+  ) -> Result<TrashEntry> {
+    if !document_path.path.is_file() {
+      return Err(anyhow!(
+        "`{}` is not a document!",
+        document_path.relative_path
+      ));
+    }
+    Self::trash(dir_paths, db, document_path, false)
+  }
+
+  /// # Trash Folder
+  ///
+  /// Move the folder at `folder_path` (with all its documents/folders) to the trash,
+  /// as a single entry.
+  /// - Entries older than the retention policy are purged.
+  pub fn trash_folder(
+    dir_paths: &AppDirPaths,
+    db: &mut PickleDb,
+    folder_path: &DocumentPath,
+  ) -> Result<TrashEntry> {
+    if !folder_path.path.is_dir() {
+      return Err(anyhow!("`{}` is not a folder!", folder_path.relative_path));
+    }
+    Self::trash(dir_paths, db, folder_path, true)
+  }
+
+  fn trash(
+    dir_paths: &AppDirPaths,
+    db: &mut PickleDb,
+    document_path: &DocumentPath,
+    is_folder: bool,
+  ) -> Result<TrashEntry> {
+    let now = Utc::now();
+    let timestamp = now.format("%Y%m%d%H%M%S%f").to_string();
+    let mut id = timestamp.clone();
+    let mut suffix = 1;
+    while dir_paths.trash.join(&id).exists() {
+      id = format!("{}-{}", timestamp, suffix);
+      suffix += 1;
+    }
+    let entry = TrashEntry {
+      id,
+      original_relative_path: document_path.relative_path.to_string(),
+      deleted_at: now.to_rfc3339_opts(SecondsFormat::Millis, true),
+      is_folder,
+    };
+    fs::create_dir_all(entry.dir_path(&dir_paths.trash))?;
+    fsutils::move_path(&document_path.path, &entry.file_path(&dir_paths.trash))?;
+    let mut entries = Self::entries(db);
+    entries.push(entry.clone());
+    Self::save_entries(db, &entries)?;
+    info!(
+      "trashed {}: {} (id: {})",
+      if is_folder { "folder" } else { "document" },
+      entry.original_relative_path,
+      entry.id
+    );
+    if let Err(e) = Self::purge_expired(dir_paths, db) {
+      warn!("failed to purge expired trash entries: {:?}", e);
+    }
+    Ok(entry)
+  }
+
+  /// # Restore
+  ///
+  /// Move the trashed document/folder back to the documents dir.
+  /// - `target_path`: where to restore to (defaults to the original path).
+  /// - Never overwrites an existing document/folder.
+  ///
+  /// Returns the path the document/folder was restored to.
+  pub fn restore(
+    dir_paths: &AppDirPaths,
+    db: &mut PickleDb,
+    id: &str,
+    target_path: Option<DocumentPath>,
+  ) -> Result<DocumentPath> {
+    let mut entries = Self::entries(db);
+    let index = entries
+      .iter()
+      .position(|entry| entry.id == id)
+      .ok_or_else(|| anyhow!("Trash entry `{}` not found!", id))?;
+    let entry = entries[index].clone();
+    let target_path = match target_path {
+      Some(target_path) => target_path,
+      None => DocumentPath::resolve(&dir_paths.documents, &entry.original_relative_path)?,
+    };
+    if let Some(parent_path) = target_path.path.parent() {
+      fs::create_dir_all(parent_path)?; // original folder could have been removed
+    }
+    fsutils::move_path(&entry.file_path(&dir_paths.trash), &target_path.path)?;
+    remove_entry_dir(&entry, &dir_paths.trash)?;
+    entries.remove(index);
+    Self::save_entries(db, &entries)?;
+    info!(
+      "restored {}: {} (id: {})",
+      if entry.is_folder {
+        "folder"
+      } else {
+        "document"
+      },
+      target_path.relative_path,
+      entry.id
+    );
+    Ok(target_path)
+  }
+
+  /// # Purge
+  ///
+  /// Permanently delete the trash entries with the given `ids` (all entries if `None`).
+  ///
+  /// Returns the purged entries.
+  pub fn purge(
+    dir_paths: &AppDirPaths,
+    db: &mut PickleDb,
+    ids: Option<&[String]>,
+  ) -> Result<Vec<TrashEntry>> {
+    Self::purge_where(dir_paths, db, |entry| {
+      ids.map_or(true, |ids| ids.contains(&entry.id))
+    })
+  }
+
+  /// # Purge Expired
+  ///
+  /// Permanently delete the trash entries older than the retention policy.
+  ///
+  /// Returns the purged entries.
+  pub fn purge_expired(dir_paths: &AppDirPaths, db: &mut PickleDb) -> Result<Vec<TrashEntry>> {
+    let retention_days = Self::retention_days(db);
+    if retention_days == 0 {
+      return Ok(Vec::new()); // keep forever
+    }
+    Self::purge_where(dir_paths, db, |entry| entry.is_expired(retention_days))
+  }
+
+  fn purge_where<F: Fn(&TrashEntry) -> bool>(
+    dir_paths: &AppDirPaths,
+    db: &mut PickleDb,
+    should_purge: F,
+  ) -> Result<Vec<TrashEntry>> {
+    let mut purged = Vec::new();
+    let mut kept = Vec::new();
+    for entry in Self::entries(db) {
+      if !should_purge(&entry) {
+        kept.push(entry);
+        continue;
+      }
+      match remove_entry_dir(&entry, &dir_paths.trash) {
+        Ok(_) => purged.push(entry),
+        Err(e) => {
+          warn!("failed to purge trash entry {}: {:?}", entry.id, e);
+          kept.push(entry);
+        }
+      }
+    }
+    if !purged.is_empty() {
+      Self::save_entries(db, &kept)?;
+      info!("purged {} trash entries", purged.len());
+    }
+    Ok(purged)
+  }
+}
+
+/// Remove the dir of the trash entry (ok if already removed)
+fn remove_entry_dir(entry: &TrashEntry, trash_dir: &Path) -> Result<()> {
+  match fs::remove_dir_all(entry.dir_path(trash_dir)) {
+    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::test_utils::temp_app_dirs;
+
+  fn resolve(dir_paths: &AppDirPaths, relative_path: &str) -> DocumentPath {
+    DocumentPath::resolve(&dir_paths.documents, relative_path).unwrap()
+  }
+
+  fn read(dir_paths: &AppDirPaths, relative_path: &str) -> String {
+    fs::read_to_string(dir_paths.documents.join(relative_path)).unwrap()
+  }
+
+  fn entry_ids(db: &PickleDb) -> Vec<String> {
+    Trash::entries(db)
+      .into_iter()
+      .map(|entry| entry.id)
+      .collect()
+  }
+
+  #[test]
+  fn restores_to_the_original_path() {
+    let (dir_paths, mut db) = temp_app_dirs("trash_restore", &[("notes/a.md", "# A")]);
+    let a = resolve(&dir_paths, "notes/a.md");
+    let entry = Trash::trash_document(&dir_paths, &mut db, &a).unwrap();
+    assert_eq!(entry.original_relative_path, "notes/a.md");
+    assert!(!entry.is_folder);
+    assert!(!a.path.exists());
+    assert!(entry.file_path(&dir_paths.trash).is_file());
+    assert_eq!(entry_ids(&db), vec![entry.id.clone()]);
+
+    let restored = Trash::restore(&dir_paths, &mut db, &entry.id, None).unwrap();
+    assert_eq!(restored.relative_path.as_str(), "notes/a.md");
+    assert_eq!(read(&dir_paths, "notes/a.md"), "# A");
+    assert!(!entry.dir_path(&dir_paths.trash).exists());
+    assert!(Trash::entries(&db).is_empty());
+    assert!(Trash::restore(&dir_paths, &mut db, &entry.id, None).is_err());
+  }
+
+  #[test]
+  fn restores_to_a_given_path() {
+    let (dir_paths, mut db) = temp_app_dirs("trash_restore_to", &[("notes/a.md", "# A")]);
+    let a = resolve(&dir_paths, "notes/a.md");
+    let entry = Trash::trash_document(&dir_paths, &mut db, &a).unwrap();
+    // also into a removed/new folder
+    fs::remove_dir(dir_paths.documents.join("notes")).unwrap();
+    let target = resolve(&dir_paths, "archive/b.md");
+    let restored = Trash::restore(&dir_paths, &mut db, &entry.id, Some(target)).unwrap();
+    assert_eq!(restored.relative_path.as_str(), "archive/b.md");
+    assert_eq!(read(&dir_paths, "archive/b.md"), "# A");
+    assert!(!a.path.exists());
+  }
+
+  #[test]
+  fn never_restores_onto_an_occupied_path() {
+    let (dir_paths, mut db) = temp_app_dirs("trash_occupied", &[("notes/a.md", "# A")]);
+    let a = resolve(&dir_paths, "notes/a.md");
+    let entry = Trash::trash_document(&dir_paths, &mut db, &a).unwrap();
+    fs::write(&a.path, "# New A").unwrap();
+    assert!(Trash::restore(&dir_paths, &mut db, &entry.id, None).is_err());
+    assert_eq!(read(&dir_paths, "notes/a.md"), "# New A");
+    // the entry is kept, to be restored elsewhere
+    assert_eq!(entry_ids(&db), vec![entry.id.clone()]);
+    let target = resolve(&dir_paths, "notes/a (restored).md");
+    Trash::restore(&dir_paths, &mut db, &entry.id, Some(target)).unwrap();
+    assert_eq!(read(&dir_paths, "notes/a (restored).md"), "# A");
+  }
+
+  #[test]
+  fn restores_folders_as_a_whole() {
+    let (dir_paths, mut db) = temp_app_dirs(
+      "trash_folder",
+      &[("notes/a.md", "# A"), ("notes/sub/b.md", "# B")],
+    );
+    let notes = resolve(&dir_paths, "notes");
+    assert!(Trash::trash_document(&dir_paths, &mut db, &notes).is_err());
+    let entry = Trash::trash_folder(&dir_paths, &mut db, &notes).unwrap();
+    assert!(entry.is_folder);
+    assert!(!notes.path.exists());
+    assert_eq!(entry_ids(&db), vec![entry.id.clone()]);
+    Trash::restore(&dir_paths, &mut db, &entry.id, None).unwrap();
+    assert_eq!(read(&dir_paths, "notes/a.md"), "# A");
+    assert_eq!(read(&dir_paths, "notes/sub/b.md"), "# B");
+  }
+
+  #[test]
+  fn purges_by_id_or_all() {
+    let (dir_paths, mut db) = temp_app_dirs(
+      "trash_purge",
+      &[("a.md", "# A"), ("b.md", "# B"), ("c.md", "# C")],
+    );
+    let entries = ["a.md", "b.md", "c.md"]
+      .iter()
+      .map(|path| Trash::trash_document(&dir_paths, &mut db, &resolve(&dir_paths, path)).unwrap())
+      .collect::<Vec<_>>();
+    let purged = Trash::purge(&dir_paths, &mut db, Some(&[entries[1].id.clone()])).unwrap();
+    assert_eq!(purged.len(), 1);
+    assert_eq!(purged[0].original_relative_path, "b.md");
+    assert!(!entries[1].dir_path(&dir_paths.trash).exists());
+    assert_eq!(
+      entry_ids(&db),
+      vec![entries[0].id.clone(), entries[2].id.clone()]
+    );
+    // unknown ids are skipped
+    let purged = Trash::purge(&dir_paths, &mut db, Some(&["unknown".to_string()])).unwrap();
+    assert!(purged.is_empty());
+
+    let purged = Trash::purge(&dir_paths, &mut db, None).unwrap();
+    assert_eq!(purged.len(), 2);
+    assert!(Trash::entries(&db).is_empty());
+    assert_eq!(fs::read_dir(&dir_paths.trash).unwrap().count(), 0);
+  }
+
+  #[test]
+  fn purges_expired_entries() {
+    let (dir_paths, mut db) = temp_app_dirs(
+      "trash_retention",
+      &[("old.md", "# Old"), ("recent.md", "# Recent")],
+    );
+    assert_eq!(Trash::retention_days(&db), DEFAULT_TRASH_RETENTION_DAYS);
+    let mut entries = ["old.md", "recent.md"]
+      .iter()
+      .map(|path| Trash::trash_document(&dir_paths, &mut db, &resolve(&dir_paths, path)).unwrap())
+      .collect::<Vec<_>>();
+    let days_ago =
+      |days| (Utc::now() - Duration::days(days)).to_rfc3339_opts(SecondsFormat::Millis, true);
+    entries[0].deleted_at = days_ago(400);
+    entries[1].deleted_at = days_ago(20);
+    Trash::save_entries(&mut db, &entries).unwrap();
+
+    // `0` keeps the entries forever
+    Trash::set_retention_days(&mut db, 0).unwrap();
+    assert!(Trash::purge_expired(&dir_paths, &mut db)
+      .unwrap()
+      .is_empty());
+    assert_eq!(Trash::entries(&db).len(), 2);
+
+    Trash::set_retention_days(&mut db, 30).unwrap();
+    let purged = Trash::purge_expired(&dir_paths, &mut db).unwrap();
+    assert_eq!(purged.len(), 1);
+    assert_eq!(purged[0].original_relative_path, "old.md");
+    assert!(!entries[0].dir_path(&dir_paths.trash).exists());
+    assert_eq!(entry_ids(&db), vec![entries[1].id.clone()]);
+
+    Trash::set_retention_days(&mut db, 10).unwrap();
+    let purged = Trash::purge_expired(&dir_paths, &mut db).unwrap();
+    assert_eq!(purged.len(), 1);
+    assert!(Trash::entries(&db).is_empty());
+  }
+
+  #[test]
+  fn purges_expired_entries_when_trashing() {
+    let (dir_paths, mut db) = temp_app_dirs(
+      "trash_auto_purge",
+      &[("old.md", "# Old"), ("new.md", "# New")],
+    );
+    let mut old =
+      Trash::trash_document(&dir_paths, &mut db, &resolve(&dir_paths, "old.md")).unwrap();
+    old.deleted_at = "2000-01-01T00:00:00.000Z".to_string();
+    Trash::save_entries(&mut db, &[old]).unwrap();
+    let new = Trash::trash_document(&dir_paths, &mut db, &resolve(&dir_paths, "new.md")).unwrap();
+    assert_eq!(entry_ids(&db), vec![new.id]);
+  }
+}
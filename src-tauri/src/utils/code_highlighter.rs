@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+use comrak::adapters::SyntaxHighlighterAdapter;
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use syntect::{
+  easy::HighlightLines,
+  highlighting::{Color, Theme, ThemeSet},
+  html::{
+    css_for_theme_with_class_style, styled_line_to_highlighted_html, ClassStyle,
+    ClassedHTMLGenerator, IncludeBackground,
+  },
+  parsing::{SyntaxReference, SyntaxSet},
+  util::LinesWithEndings,
+};
+
+use crate::utils::html::escape_html;
+
+/// Theme used when no (or an unknown) code theme is selected
+pub const DEFAULT_CODE_THEME: &str = "InspiredGitHub";
+
+/// CSS properties the highlighter puts in `style` attributes
+const HIGHLIGHT_STYLE_PROPERTIES: [&str; 5] = [
+  "color",
+  "background-color",
+  "font-weight",
+  "font-style",
+  "text-decoration",
+];
+
+lazy_static! {
+  static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+  static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+/// How highlighted code is styled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CodeHighlightMode {
+  /// Theme colors as inline `style` attributes (self-contained HTML).
+  Inline,
+  /// CSS classes, styled by the css of `code_theme_css`.
+  Classes,
+}
+
+impl Default for CodeHighlightMode {
+  fn default() -> Self {
+    Self::Inline
+  }
+}
+
+/// # Code Highlighter
+///
+/// Syntax highlights fenced code blocks rendered by comrak
+/// (the language is taken from the info string, eg. ` ```rust `).
+/// - Unknown languages are rendered as plain text.
+pub struct CodeHighlighter {
+  mode: CodeHighlightMode,
+  theme: &'static Theme,
+}
+
+impl CodeHighlighter {
+  pub fn new(mode: CodeHighlightMode, theme_name: Option<&str>) -> Self {
+    Self {
+      mode,
+      theme: find_theme(theme_name),
+    }
+  }
+
+  fn find_syntax(lang: Option<&str>) -> &'static SyntaxReference {
+    lang
+      .and_then(|lang| lang.split_whitespace().next())
+      .and_then(|lang| SYNTAX_SET.find_syntax_by_token(lang))
+      .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text())
+  }
+
+  fn highlight_inline(&self, syntax: &SyntaxReference, code: &str) -> String {
+    let mut highlighter = HighlightLines::new(syntax, self.theme);
+    let mut html = String::new();
+    for line in LinesWithEndings::from(code) {
+      let regions = highlighter.highlight(line, &SYNTAX_SET);
+      html.push_str(&styled_line_to_highlighted_html(
+        &regions[..],
+        IncludeBackground::No,
+      ));
+    }
+    html
+  }
+
+  fn highlight_classes(syntax: &SyntaxReference, code: &str) -> String {
+    let mut generator =
+      ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, ClassStyle::Spaced);
+    for line in LinesWithEndings::from(code) {
+      generator.parse_html_for_line_which_includes_newline(line);
+    }
+    generator.finalize()
+  }
+}
+
+impl SyntaxHighlighterAdapter for CodeHighlighter {
+  fn highlight(&self, lang: Option<&str>, code: &str) -> String {
+    let syntax = Self::find_syntax(lang);
+    match self.mode {
+      CodeHighlightMode::Inline => self.highlight_inline(syntax, code),
+      CodeHighlightMode::Classes => Self::highlight_classes(syntax, code),
+    }
+  }
+
+  fn build_pre_tag(&self, attributes: &HashMap<String, String>) -> String {
+    let mut attributes = attributes.clone();
+    match self.mode {
+      CodeHighlightMode::Inline => {
+        if let Some(background) = self.theme.settings.background {
+          let style = format!("background-color:{};", to_css_color(background));
+          attributes.insert("style".to_string(), style);
+        }
+      }
+      // `.code` is styled with the theme colors by `code_theme_css`
+      CodeHighlightMode::Classes => {
+        attributes.insert("class".to_string(), "code".to_string());
+      }
+    }
+    build_tag("pre", &attributes)
+  }
+
+  fn build_code_tag(&self, attributes: &HashMap<String, String>) -> String {
+    build_tag("code", attributes)
+  }
+}
+
+/// Names of the available code themes (sorted)
+pub fn code_theme_names() -> Vec<String> {
+  THEME_SET.themes.keys().cloned().collect()
+}
+
+/// CSS for the code highlighted in `CodeHighlightMode::Classes` with the `theme_name` theme
+pub fn code_theme_css(theme_name: Option<&str>) -> String {
+  css_for_theme_with_class_style(find_theme(theme_name), ClassStyle::Spaced)
+}
+
+/// `true` if the `style` attribute value only has declarations
+/// the highlighter generates (eg. `color:#323232;font-weight:bold;`)
+pub fn is_highlight_style(style: &str) -> bool {
+  style
+    .split(';')
+    .map(str::trim)
+    .filter(|declaration| !declaration.is_empty())
+    .all(|declaration| {
+      let mut parts = declaration.splitn(2, ':');
+      match (parts.next(), parts.next()) {
+        (Some(property), Some(value)) => {
+          HIGHLIGHT_STYLE_PROPERTIES.contains(&property.trim())
+            && value
+              .chars()
+              .all(|c| c.is_ascii_alphanumeric() || c == '#' || c == ' ')
+        }
+        _ => false,
+      }
+    })
+}
+
+fn find_theme(theme_name: Option<&str>) -> &'static Theme {
+  let theme_name = theme_name.unwrap_or(DEFAULT_CODE_THEME);
+  THEME_SET.themes.get(theme_name).unwrap_or_else(|| {
+    warn!("unknown code theme `{}`, using the default!", theme_name);
+    &THEME_SET.themes[DEFAULT_CODE_THEME]
+  })
+}
+
+fn to_css_color(color: Color) -> String {
+  format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Opening `tag` with the `attributes` (sorted by name)
+fn build_tag(tag: &str, attributes: &HashMap<String, String>) -> String {
+  let mut names: Vec<&String> = attributes.keys().collect();
+  names.sort();
+  let mut html = format!("<{}", tag);
+  for name in names {
+    html.push_str(&format!(" {}=\"{}\"", name, escape_html(&attributes[name])));
+  }
+  html.push('>');
+  html
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn attributes(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn highlights_with_inline_styles() {
+    let highlighter = CodeHighlighter::new(CodeHighlightMode::Inline, None);
+    let html = highlighter.highlight(Some("rust"), "fn main() {}\n");
+    assert!(html.starts_with("<span style=\""), "{}", html);
+    assert!(html.contains(">main</span>"), "{}", html);
+    assert!(!html.contains("class="), "{}", html);
+    for style in html.split("style=\"").skip(1) {
+      let style = &style[..style.find('"').unwrap()];
+      assert!(is_highlight_style(style), "{}", style);
+    }
+    assert_eq!(
+      highlighter.build_pre_tag(&attributes(&[])),
+      format!(
+        "<pre style=\"background-color:{};\">",
+        to_css_color(highlighter.theme.settings.background.unwrap())
+      )
+    );
+  }
+
+  #[test]
+  fn highlights_with_classes() {
+    let highlighter = CodeHighlighter::new(CodeHighlightMode::Classes, None);
+    let html = highlighter.highlight(Some("rust"), "fn main() {}\n");
+    assert!(html.contains("<span class=\"source rust\">"), "{}", html);
+    assert!(!html.contains("style="), "{}", html);
+    assert_eq!(
+      highlighter.build_pre_tag(&attributes(&[("lang", "rust")])),
+      "<pre class=\"code\" lang=\"rust\">"
+    );
+    assert!(code_theme_css(None).contains(".code"));
+  }
+
+  #[test]
+  fn escapes_plain_text_and_attributes() {
+    let highlighter = CodeHighlighter::new(CodeHighlightMode::Inline, None);
+    for lang in &[None, Some("unknown-lang"), Some("")] {
+      let html = highlighter.highlight(*lang, "<b>bold</b> & co\n");
+      assert!(
+        html.contains("&lt;b&gt;bold&lt;/b&gt; &amp; co"),
+        "{}",
+        html
+      );
+    }
+    assert_eq!(
+      highlighter.build_code_tag(&attributes(&[
+        ("class", "language-\"><script>"),
+        ("data-a", "1")
+      ])),
+      "<code class=\"language-&quot;&gt;&lt;script&gt;\" data-a=\"1\">"
+    );
+  }
+
+  #[test]
+  fn falls_back_to_the_default_theme() {
+    assert!(code_theme_names().contains(&DEFAULT_CODE_THEME.to_string()));
+    let default_theme = find_theme(None);
+    assert!(std::ptr::eq(find_theme(Some("unknown")), default_theme));
+    let other_theme = code_theme_names()
+      .into_iter()
+      .find(|name| name != DEFAULT_CODE_THEME)
+      .unwrap();
+    assert!(!std::ptr::eq(find_theme(Some(&other_theme)), default_theme));
+  }
+
+  #[test]
+  fn accepts_only_highlight_styles() {
+    for (style, expected) in &[
+      ("", true),
+      ("color:#323232;", true),
+      ("font-weight:bold;color:#a71d5d;", true),
+      ("background-color:#ffffff", true),
+      (" font-style : italic ; text-decoration:underline;", true),
+      ("position:fixed;top:0", false),
+      ("color:#323232;position:absolute", false),
+      ("background:url(https://example.com/x.png)", false),
+      ("color:expression(alert(1))", false),
+      ("color:#fff;background-image:url(x)", false),
+      ("color", false),
+      ("color:red\"", false),
+    ] {
+      assert_eq!(is_highlight_style(style), *expected, "{:?}", style);
+    }
+  }
+}
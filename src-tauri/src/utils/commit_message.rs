@@ -0,0 +1,95 @@
+use chrono::{SecondsFormat, Utc};
+
+use crate::models::diff::{FileChange, FileDiffStatus};
+
+/// Template used when no commit message template is configured.
+///
+/// Placeholders:
+/// - `{summary}`: eg. `Update 3 notes, add 1, delete 2`.
+/// - `{body}`: changed paths grouped by status.
+/// - `{added}`, `{updated}`, `{renamed}`, `{deleted}`, `{total}`: counts.
+/// - `{time}`: commit time (RFC 3339).
+pub const DEFAULT_COMMIT_MESSAGE_TEMPLATE: &str = "{summary}\n\n{body}";
+
+/// # Generate Commit Message
+///
+/// Fill the commit message `template` (see `DEFAULT_COMMIT_MESSAGE_TEMPLATE`)
+/// from the staged `changes`.
+pub fn generate_commit_message(template: &str, changes: &[FileChange]) -> String {
+  let paths_of = |status: FileDiffStatus| -> Vec<String> {
+    changes
+      .iter()
+      .filter(|change| change.status == status)
+      .map(|change| match &change.old_relative_path {
+        Some(old_relative_path) => format!("{} -> {}", old_relative_path, change.relative_path),
+        None => change.relative_path.clone(),
+      })
+      .collect()
+  };
+  let updated = paths_of(FileDiffStatus::Modified);
+  let added = paths_of(FileDiffStatus::Added);
+  let renamed = paths_of(FileDiffStatus::Renamed);
+  let deleted = paths_of(FileDiffStatus::Deleted);
+
+  let summary = generate_summary(updated.len(), added.len(), renamed.len(), deleted.len());
+  let mut body = vec![];
+  for (title, paths) in [
+    ("Updated", &updated),
+    ("Added", &added),
+    ("Renamed", &renamed),
+    ("Deleted", &deleted),
+  ]
+  .iter()
+  {
+    if paths.is_empty() {
+      continue;
+    }
+    let mut section = format!("{}:", title);
+    for path in paths.iter() {
+      section.push_str(&format!("\n- {}", path));
+    }
+    body.push(section);
+  }
+
+  let message = template
+    .replace("{summary}", &summary)
+    .replace("{body}", &body.join("\n\n"))
+    .replace("{added}", &added.len().to_string())
+    .replace("{updated}", &updated.len().to_string())
+    .replace("{renamed}", &renamed.len().to_string())
+    .replace("{deleted}", &deleted.len().to_string())
+    .replace("{total}", &changes.len().to_string())
+    .replace(
+      "{time}",
+      &Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+    );
+  let message = message.trim();
+  if message.is_empty() {
+    summary // template produced nothing, git needs a message
+  } else {
+    message.to_string()
+  }
+}
+
+/// Summary line, eg. `Update 3 notes, add 1, delete 2`
+fn generate_summary(updated: usize, added: usize, renamed: usize, deleted: usize) -> String {
+  let notes = |count: usize| if count == 1 { "note" } else { "notes" };
+  let parts = [
+    ("Update", updated),
+    ("Add", added),
+    ("Rename", renamed),
+    ("Delete", deleted),
+  ];
+  let mut summary = String::new();
+  for (verb, count) in parts.iter().filter(|(_, count)| *count > 0) {
+    if summary.is_empty() {
+      summary = format!("{} {} {}", verb, count, notes(*count));
+    } else {
+      summary.push_str(&format!(", {} {}", verb.to_lowercase(), count));
+    }
+  }
+  if summary.is_empty() {
+    summary = "No changes".to_string();
+  }
+  summary
+}
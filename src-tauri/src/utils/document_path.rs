@@ -0,0 +1,406 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use relative_path::{Component, RelativePath, RelativePathBuf};
+
+use crate::{models::server_error::ServerError, utils::fsutils};
+
+/// Max length (in bytes) of a single file/folder name
+const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Chars not allowed in file/folder names (invalid on at least one supported platform)
+const INVALID_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names reserved by windows (also with any extension, eg. `con.md`)
+const RESERVED_NAMES: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+  "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// # Document Path
+///
+/// Path of a document/folder resolved from (untrusted) caller input.
+/// Always confined to the documents dir:
+/// - `..`, absolute and drive/UNC prefixed paths are rejected.
+/// - Reserved names and invalid characters are rejected.
+/// - Symlinks resolving outside the documents dir are rejected.
+#[derive(Debug, Clone)]
+pub struct DocumentPath {
+  /// Normalized path relative to the documents dir
+  pub relative_path: RelativePathBuf,
+  /// Absolute path in the documents dir
+  pub path: PathBuf,
+}
+
+impl DocumentPath {
+  /// Resolve the caller provided `relative_path` in the `documents_dir`
+  pub fn resolve(documents_dir: &Path, relative_path: &str) -> Result<Self, ServerError> {
+    if relative_path.starts_with('/') || relative_path.starts_with('\\') {
+      return Err(user_error(format!(
+        "Invalid path `{}`: absolute paths are not allowed!",
+        relative_path
+      )));
+    }
+    let mut normalized = RelativePathBuf::new();
+    for component in RelativePath::new(relative_path).components() {
+      match component {
+        Component::CurDir => {}
+        Component::ParentDir => {
+          return Err(user_error(format!(
+            "Invalid path `{}`: `..` is not allowed!",
+            relative_path
+          )))
+        }
+        Component::Normal(name) => {
+          validate_file_name(name)?;
+          normalized.push(name);
+        }
+      }
+    }
+    if normalized.as_str().is_empty() {
+      return Err(user_error(format!(
+        "Invalid path `{}`: path is empty!",
+        relative_path
+      )));
+    }
+    let path = normalized.to_path(documents_dir);
+    check_no_symlink_escape(documents_dir, &normalized)?;
+    Ok(Self {
+      relative_path: normalized,
+      path,
+    })
+  }
+
+  /// Resolve the path of a sibling named `file_name` (eg. for renames)
+  pub fn with_file_name(&self, documents_dir: &Path, file_name: &str) -> Result<Self, ServerError> {
+    validate_file_name(file_name)?;
+    Self::resolve(
+      documents_dir,
+      self.relative_path.with_file_name(file_name).as_str(),
+    )
+  }
+
+  /// Resolve the path of this document/folder moved into the `target_folder`
+  /// (`None` or empty for the documents dir itself)
+  pub fn moved_to(
+    &self,
+    documents_dir: &Path,
+    target_folder: Option<&str>,
+  ) -> Result<Self, ServerError> {
+    let file_name = self
+      .relative_path
+      .file_name()
+      .ok_or_else(|| user_error(format!("Invalid path `{}`!", self.relative_path)))?;
+    let new_relative_path = match target_folder.filter(|folder| !folder.is_empty()) {
+      Some(target_folder) => Self::resolve(documents_dir, target_folder)?
+        .relative_path
+        .join(file_name),
+      None => RelativePathBuf::from(file_name),
+    };
+    Self::resolve(documents_dir, new_relative_path.as_str())
+  }
+}
+
+/// # Validate File Name
+///
+/// Check that `name` is a valid file/folder name (a single path component):
+/// - Not empty, `.` or `..`.
+/// - No invalid (`<>:"/\|?*`) or control chars.
+/// - Not a reserved name (`CON`, `NUL`, `COM1`...) and no trailing dot/space.
+/// - Not a temp file name (used by atomic writes).
+pub fn validate_file_name(name: &str) -> Result<(), ServerError> {
+  if name.is_empty() || name == "." || name == ".." {
+    return Err(user_error(format!("Invalid name `{}`!", name)));
+  }
+  if name.len() > MAX_FILE_NAME_LEN {
+    return Err(user_error(format!(
+      "Invalid name `{}`: longer than {} bytes!",
+      name, MAX_FILE_NAME_LEN
+    )));
+  }
+  if let Some(c) = name
+    .chars()
+    .find(|c| INVALID_CHARS.contains(c) || c.is_control())
+  {
+    return Err(user_error(format!(
+      "Invalid name `{}`: character `{}` is not allowed!",
+      name.escape_debug(),
+      c.escape_debug()
+    )));
+  }
+  if name.ends_with('.') || name.ends_with(' ') {
+    return Err(user_error(format!(
+      "Invalid name `{}`: names can not end with a dot or space!",
+      name
+    )));
+  }
+  let stem = name.split('.').next().unwrap_or(name).trim_end();
+  if RESERVED_NAMES
+    .iter()
+    .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+  {
+    return Err(user_error(format!(
+      "Invalid name `{}`: `{}` is a reserved name!",
+      name, stem
+    )));
+  }
+  if fsutils::is_temp_file(name) {
+    return Err(user_error(format!(
+      "Invalid name `{}`: reserved for temp files!",
+      name
+    )));
+  }
+  Ok(())
+}
+
+/// Check that no existing component of `relative_path` is a symlink
+/// resolving outside the `documents_dir`.
+fn check_no_symlink_escape(
+  documents_dir: &Path,
+  relative_path: &RelativePath,
+) -> Result<(), ServerError> {
+  let mut path = documents_dir.to_path_buf();
+  let mut documents_dir_canonical = None;
+  for component in relative_path.components() {
+    path.push(component.as_str());
+    let metadata = match fs::symlink_metadata(&path) {
+      Ok(metadata) => metadata,
+      Err(_) => break, // rest of the path does not exist yet
+    };
+    if !metadata.file_type().is_symlink() {
+      continue;
+    }
+    if documents_dir_canonical.is_none() {
+      documents_dir_canonical = Some(canonicalize(documents_dir)?);
+    }
+    let escapes = match fs::canonicalize(&path) {
+      Ok(target) => !target.starts_with(documents_dir_canonical.as_ref().unwrap()),
+      Err(_) => true, // dangling symlink (target can't be checked)
+    };
+    if escapes {
+      return Err(user_error(format!(
+        "Invalid path `{}`: links outside the documents dir are not allowed!",
+        relative_path
+      )));
+    }
+  }
+  Ok(())
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, ServerError> {
+  fs::canonicalize(path).map_err(|e| ServerError::InternalError {
+    message: format!("failed to resolve {:?}: {}", path, e),
+  })
+}
+
+fn user_error(message: String) -> ServerError {
+  ServerError::UserError { message }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use super::*;
+  use crate::utils::test_utils::temp_documents_dir;
+
+  /// Create a fresh documents dir with a `notes` folder for a test
+  fn setup(name: &str) -> PathBuf {
+    temp_documents_dir(&format!("document_path_{}", name), &[("notes/a.md", "a")])
+  }
+
+  fn assert_user_error(result: Result<DocumentPath, ServerError>, input: &str) {
+    match result {
+      Err(ServerError::UserError { .. }) => {}
+      other => panic!("expected UserError for {:?}, got {:?}", input, other),
+    }
+  }
+
+  #[test]
+  fn resolves_valid_paths() {
+    let documents_dir = setup("valid");
+    for (input, expected) in &[
+      ("a.md", "a.md"),
+      ("notes/a.md", "notes/a.md"),
+      ("./notes/./a.md", "notes/a.md"),
+      ("notes//a.md", "notes/a.md"),
+      ("notes/new folder/b c.md", "notes/new folder/b c.md"),
+      ("ünïcödé/日本語.md", "ünïcödé/日本語.md"),
+      (".hidden.md", ".hidden.md"),
+      ("console.md", "console.md"),
+      ("a.b.c.md", "a.b.c.md"),
+    ] {
+      let document_path = DocumentPath::resolve(&documents_dir, input).unwrap();
+      assert_eq!(document_path.relative_path.as_str(), *expected);
+      assert_eq!(document_path.path, documents_dir.join(expected));
+    }
+  }
+
+  #[test]
+  fn rejects_parent_dir_components() {
+    let documents_dir = setup("parent_dir");
+    for input in &[
+      "..",
+      "../a.md",
+      "../../etc/passwd",
+      "notes/../../a.md",
+      "notes/../a.md",
+      "./../a.md",
+      "notes/..",
+    ] {
+      assert_user_error(DocumentPath::resolve(&documents_dir, input), input);
+    }
+  }
+
+  #[test]
+  fn rejects_absolute_paths() {
+    let documents_dir = setup("absolute");
+    for input in &[
+      "/etc/passwd",
+      "//server/share/a.md",
+      "\\a.md",
+      "\\\\server\\share\\a.md",
+      "C:\\Windows\\a.md",
+      "C:/Windows/a.md",
+      "c:a.md",
+    ] {
+      assert_user_error(DocumentPath::resolve(&documents_dir, input), input);
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_characters() {
+    let documents_dir = setup("invalid_chars");
+    for input in &[
+      "notes\\..\\..\\a.md",
+      "a<b.md",
+      "a>b.md",
+      "a:b.md",
+      "a.md:stream",
+      "a\"b.md",
+      "a|b.md",
+      "a?.md",
+      "*.md",
+      "a\0b.md",
+      "a\nb.md",
+      "a\u{7f}b.md",
+    ] {
+      assert_user_error(DocumentPath::resolve(&documents_dir, input), input);
+    }
+  }
+
+  #[test]
+  fn rejects_reserved_names() {
+    let documents_dir = setup("reserved");
+    for input in &[
+      "CON",
+      "con.md",
+      "notes/nul",
+      "Aux.txt",
+      "com1.md",
+      "LPT9/a.md",
+      "prn .md",
+      "a.md.",
+      "a.md ",
+      "notes./a.md",
+      ".a.md.mediocre-tmp",
+    ] {
+      assert_user_error(DocumentPath::resolve(&documents_dir, input), input);
+    }
+  }
+
+  #[test]
+  fn rejects_empty_and_too_long_paths() {
+    let documents_dir = setup("empty");
+    let too_long = format!("{}.md", "a".repeat(MAX_FILE_NAME_LEN));
+    for input in &["", ".", "./", "./.", too_long.as_str()] {
+      assert_user_error(DocumentPath::resolve(&documents_dir, input), input);
+    }
+  }
+
+  #[test]
+  fn validates_new_file_names() {
+    let documents_dir = setup("file_names");
+    let document_path = DocumentPath::resolve(&documents_dir, "notes/a.md").unwrap();
+    let renamed = document_path
+      .with_file_name(&documents_dir, "b.md")
+      .unwrap();
+    assert_eq!(renamed.relative_path.as_str(), "notes/b.md");
+    assert_eq!(renamed.path, documents_dir.join("notes").join("b.md"));
+    for input in &[
+      "",
+      "..",
+      "../b.md",
+      "sub/b.md",
+      "sub\\b.md",
+      "/b.md",
+      "nul.md",
+    ] {
+      assert_user_error(document_path.with_file_name(&documents_dir, input), input);
+    }
+  }
+
+  #[test]
+  fn resolves_moved_paths() {
+    let documents_dir = setup("moved");
+    let document_path = DocumentPath::resolve(&documents_dir, "notes/a.md").unwrap();
+    for (target_folder, expected) in &[
+      (None, "a.md"),
+      (Some(""), "a.md"),
+      (Some("work"), "work/a.md"),
+      (Some("./work/2021/"), "work/2021/a.md"),
+    ] {
+      let moved = document_path
+        .moved_to(&documents_dir, *target_folder)
+        .unwrap();
+      assert_eq!(moved.relative_path.as_str(), *expected);
+    }
+    for input in &["..", "../outside", "/tmp", "work/../..", "nul"] {
+      assert_user_error(document_path.moved_to(&documents_dir, Some(input)), input);
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn rejects_symlinks_escaping_documents_dir() {
+    use std::os::unix::fs::symlink;
+
+    let documents_dir = setup("symlinks");
+    let root = documents_dir.parent().unwrap();
+    let outside_dir = root.join("outside");
+    fs::create_dir_all(&outside_dir).unwrap();
+    fs::write(outside_dir.join("secret.md"), "secret").unwrap();
+    symlink(&outside_dir, documents_dir.join("escape")).unwrap();
+    symlink(
+      outside_dir.join("secret.md"),
+      documents_dir.join("secret.md"),
+    )
+    .unwrap();
+    symlink(root.join("missing"), documents_dir.join("dangling")).unwrap();
+    symlink("../..", documents_dir.join("notes").join("up")).unwrap();
+    for input in &[
+      "escape",
+      "escape/secret.md",
+      "escape/new.md",
+      "secret.md",
+      "dangling",
+      "dangling/new.md",
+      "notes/up/a.md",
+    ] {
+      assert_user_error(DocumentPath::resolve(&documents_dir, input), input);
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn allows_symlinks_inside_documents_dir() {
+    use std::os::unix::fs::symlink;
+
+    let documents_dir = setup("inner_symlinks");
+    symlink(documents_dir.join("notes"), documents_dir.join("linked")).unwrap();
+    let document_path = DocumentPath::resolve(&documents_dir, "linked/a.md").unwrap();
+    assert_eq!(document_path.relative_path.as_str(), "linked/a.md");
+  }
+}
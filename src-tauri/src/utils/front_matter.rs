@@ -0,0 +1,416 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Format of a front matter (by its delimiter)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FrontMatterFormat {
+  /// Delimited by `---` lines (may also be closed by `...`)
+  Yaml,
+  /// Delimited by `+++` lines
+  Toml,
+}
+
+impl FrontMatterFormat {
+  fn delimiter(self) -> &'static str {
+    match self {
+      Self::Yaml => "---",
+      Self::Toml => "+++",
+    }
+  }
+}
+
+/// # Front Matter
+///
+/// Metadata block at the start of a document, eg.
+/// ```text
+/// ---
+/// title: Meeting notes
+/// tags: [work, ideas]
+/// ---
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontMatter {
+  pub format: FrontMatterFormat,
+  /// Front matter keys/values as JSON
+  pub data: Map<String, Value>,
+}
+
+/// Document metadata read from the front matter (see `FileMetaInfo`)
+#[derive(Debug, Clone, Default)]
+pub struct FrontMatterMeta {
+  /// `title`
+  pub title: Option<String>,
+  /// `tags` (list or comma separated, leading `#` removed)
+  pub tags: Vec<String>,
+  /// `aliases` (list or comma separated)
+  pub aliases: Vec<String>,
+  /// `created`, else `date`
+  pub created: Option<String>,
+}
+
+impl FrontMatter {
+  pub fn meta(&self) -> FrontMatterMeta {
+    FrontMatterMeta {
+      title: self.data.get("title").and_then(value_to_string),
+      tags: self
+        .string_list("tags")
+        .into_iter()
+        .map(|tag| tag.trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect(),
+      aliases: self.string_list("aliases"),
+      created: ["created", "date"]
+        .iter()
+        .find_map(|key| self.data.get(*key).and_then(value_to_string)),
+    }
+  }
+
+  /// Value of `key` as a list of strings (from a list or a comma separated string)
+  fn string_list(&self, key: &str) -> Vec<String> {
+    match self.data.get(key) {
+      Some(Value::Array(values)) => values.iter().filter_map(value_to_string).collect(),
+      Some(Value::String(value)) => value
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .collect(),
+      _ => vec![],
+    }
+  }
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+  match value {
+    Value::String(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+    Value::Number(value) => Some(value.to_string()),
+    Value::Bool(value) => Some(value.to_string()),
+    _ => None,
+  }
+}
+
+/// A document split into its (raw) front matter and body
+struct SplitDocument<'a> {
+  format: FrontMatterFormat,
+  front_matter: &'a str,
+  body: &'a str,
+}
+
+/// Split the front matter from the document `content`
+/// - The first line must be the delimiter, the block ends at the next delimiter line.
+/// - `None` if the document has no (closed) front matter.
+/// - A leading byte order mark is skipped (and not kept in the body).
+fn split_document(content: &str) -> Option<SplitDocument> {
+  let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+  let first_line_end = content.find('\n')?;
+  let first_line = content[..first_line_end].trim_end_matches('\r');
+  let format = [FrontMatterFormat::Yaml, FrontMatterFormat::Toml]
+    .iter()
+    .copied()
+    .find(|format| first_line == format.delimiter())?;
+  let rest = &content[first_line_end + 1..];
+  let mut offset = 0;
+  for line in rest.split_inclusive('\n') {
+    let line_content = line.trim_end_matches(|c| c == '\r' || c == '\n');
+    let is_end = line_content == format.delimiter()
+      || (format == FrontMatterFormat::Yaml && line_content == "...");
+    if is_end {
+      return Some(SplitDocument {
+        format,
+        front_matter: &rest[..offset],
+        body: &rest[offset + line.len()..],
+      });
+    }
+    offset += line.len();
+  }
+  None
+}
+
+/// Get the body of the document `content` (without its front matter)
+pub fn document_body(content: &str) -> &str {
+  split_document(content).map_or(content, |document| document.body)
+}
+
+/// # Parse Front Matter
+///
+/// Parse the YAML/TOML front matter of the document `content` into JSON.
+/// - `Ok(None)` if the document has no front matter.
+/// - Fails if the front matter is invalid or not a map of keys.
+pub fn parse_front_matter(content: &str) -> Result<Option<FrontMatter>> {
+  let document = match split_document(content) {
+    Some(document) => document,
+    None => return Ok(None),
+  };
+  let data = match document.format {
+    FrontMatterFormat::Yaml => parse_yaml(document.front_matter)?,
+    FrontMatterFormat::Toml => parse_toml(document.front_matter)?
+      .into_iter()
+      .map(|(key, value)| (key, toml_to_json(value)))
+      .collect(),
+  };
+  Ok(Some(FrontMatter {
+    format: document.format,
+    data,
+  }))
+}
+
+fn parse_yaml(front_matter: &str) -> Result<Map<String, Value>> {
+  if front_matter.trim().is_empty() {
+    return Ok(Map::new());
+  }
+  let value: Value = serde_yaml::from_str(front_matter).context("Invalid YAML front matter!")?;
+  match value {
+    Value::Object(data) => Ok(data),
+    Value::Null => Ok(Map::new()),
+    _ => Err(anyhow!("YAML front matter is not a map of keys!")),
+  }
+}
+
+fn parse_toml(front_matter: &str) -> Result<toml::value::Table> {
+  toml::from_str(front_matter).context("Invalid TOML front matter!")
+}
+
+/// Convert a TOML value to JSON (dates become RFC 3339 strings)
+fn toml_to_json(value: toml::Value) -> Value {
+  match value {
+    toml::Value::String(value) => Value::String(value),
+    toml::Value::Integer(value) => Value::from(value),
+    toml::Value::Float(value) => Value::from(value),
+    toml::Value::Boolean(value) => Value::Bool(value),
+    toml::Value::Datetime(value) => Value::String(value.to_string()),
+    toml::Value::Array(values) => Value::Array(values.into_iter().map(toml_to_json).collect()),
+    toml::Value::Table(table) => Value::Object(
+      table
+        .into_iter()
+        .map(|(key, value)| (key, toml_to_json(value)))
+        .collect(),
+    ),
+  }
+}
+
+/// # Set Front Matter Keys
+///
+/// Set the `updates` keys in the front matter of the document `content`
+/// (a `null` value removes the key). Other keys and the body are kept as is.
+/// - A YAML front matter is added if the document has none
+///   (and removed if no keys are left).
+/// - Comments/formatting inside the front matter are not kept.
+///
+/// Returns the updated content and its front matter.
+pub fn set_front_matter_keys(
+  content: &str,
+  updates: Map<String, Value>,
+) -> Result<(String, FrontMatter)> {
+  let (format, front_matter, body) = match split_document(content) {
+    Some(document) => (document.format, document.front_matter, document.body),
+    None => (FrontMatterFormat::Yaml, "", content),
+  };
+  let front_matter = match format {
+    FrontMatterFormat::Yaml => update_yaml(front_matter, updates)?,
+    FrontMatterFormat::Toml => update_toml(front_matter, updates)?,
+  };
+  let content = match front_matter {
+    Some(front_matter) => {
+      let delimiter = format.delimiter();
+      format!(
+        "{}\n{}\n{}\n{}",
+        delimiter,
+        front_matter.trim_end(),
+        delimiter,
+        body
+      )
+    }
+    None => body.to_string(),
+  };
+  let front_matter = parse_front_matter(&content)?.unwrap_or(FrontMatter {
+    format,
+    data: Map::new(),
+  });
+  Ok((content, front_matter))
+}
+
+/// Returns the updated YAML, `None` if no keys are left
+fn update_yaml(front_matter: &str, updates: Map<String, Value>) -> Result<Option<String>> {
+  let mut mapping: serde_yaml::Mapping = if front_matter.trim().is_empty() {
+    serde_yaml::Mapping::new()
+  } else {
+    serde_yaml::from_str(front_matter).context("Invalid YAML front matter!")?
+  };
+  for (key, value) in updates {
+    let key = serde_yaml::Value::String(key);
+    if value.is_null() {
+      mapping.remove(&key);
+    } else {
+      mapping.insert(key, serde_yaml::to_value(value)?);
+    }
+  }
+  if mapping.is_empty() {
+    return Ok(None);
+  }
+  let yaml = serde_yaml::to_string(&mapping)?;
+  // serde_yaml starts the document with a `---` line
+  Ok(Some(yaml.trim_start_matches("---\n").to_string()))
+}
+
+/// Returns the updated TOML, `None` if no keys are left
+fn update_toml(front_matter: &str, updates: Map<String, Value>) -> Result<Option<String>> {
+  let mut table = parse_toml(front_matter)?;
+  for (key, value) in updates {
+    if value.is_null() {
+      table.remove(&key);
+    } else {
+      let value = toml::Value::try_from(value)
+        .with_context(|| format!("`{}` can not be written as TOML!", key))?;
+      table.insert(key, value);
+    }
+  }
+  if table.is_empty() {
+    return Ok(None);
+  }
+  // Serialized as a value so that plain keys are written before sub tables
+  Ok(Some(toml::to_string(&toml::Value::Table(table))?))
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn front_matter_data(content: &str) -> Option<(FrontMatterFormat, Value)> {
+    parse_front_matter(content)
+      .unwrap()
+      .map(|front_matter| (front_matter.format, Value::Object(front_matter.data)))
+  }
+
+  #[test]
+  fn parses_front_matter() {
+    for (content, expected) in &[
+      (
+        "---\ntitle: Notes\ntags: [a, b]\n---\n# Body\n",
+        Some((
+          FrontMatterFormat::Yaml,
+          json!({"title": "Notes", "tags": ["a", "b"]}),
+        )),
+      ),
+      (
+        "---\ntitle: Notes\n...\n# Body\n",
+        Some((FrontMatterFormat::Yaml, json!({"title": "Notes"}))),
+      ),
+      (
+        "---\n---\n# Body\n",
+        Some((FrontMatterFormat::Yaml, json!({}))),
+      ),
+      (
+        "+++\ntitle = \"Notes\"\ndate = 2021-01-31\n[extra]\nkey = 1\n+++\nBody\n",
+        Some((
+          FrontMatterFormat::Toml,
+          json!({"title": "Notes", "date": "2021-01-31", "extra": {"key": 1}}),
+        )),
+      ),
+      // CRLF line endings
+      (
+        "---\r\ntitle: Notes\r\ntags: [a]\r\n---\r\nBody\r\n",
+        Some((
+          FrontMatterFormat::Yaml,
+          json!({"title": "Notes", "tags": ["a"]}),
+        )),
+      ),
+      (
+        "+++\r\ntitle = \"Notes\"\r\n+++\r\nBody\r\n",
+        Some((FrontMatterFormat::Toml, json!({"title": "Notes"}))),
+      ),
+      // byte order mark
+      (
+        "\u{feff}---\ntitle: Notes\n---\nBody\n",
+        Some((FrontMatterFormat::Yaml, json!({"title": "Notes"}))),
+      ),
+      // no (closed) front matter
+      ("# Body\n---\ntitle: Notes\n---\n", None),
+      ("---\ntitle: Notes\n# Body\n", None),
+      ("+++\ntitle = \"Notes\"\n---\nBody\n", None),
+      ("---", None),
+      ("", None),
+    ] {
+      assert_eq!(&front_matter_data(content), expected, "{:?}", content);
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_front_matter() {
+    for content in &[
+      "---\ntitle: [unclosed\n---\nBody\n",
+      "---\n- a list\n---\nBody\n",
+      "+++\ntitle = \n+++\nBody\n",
+    ] {
+      assert!(parse_front_matter(content).is_err(), "{:?}", content);
+    }
+  }
+
+  #[test]
+  fn splits_the_body() {
+    for (content, expected) in &[
+      ("---\ntitle: Notes\n---\n# Body\n", "# Body\n"),
+      ("---\r\ntitle: Notes\r\n---\r\n# Body\r\n", "# Body\r\n"),
+      ("\u{feff}+++\ntitle = \"a\"\n+++\n# Body\n", "# Body\n"),
+      // missing closing fence: all of it is the body
+      ("---\ntitle: Notes\n# Body\n", "---\ntitle: Notes\n# Body\n"),
+      ("# Body\n", "# Body\n"),
+    ] {
+      assert_eq!(document_body(content), *expected, "{:?}", content);
+    }
+  }
+
+  #[test]
+  fn reads_meta() {
+    let content =
+      "---\ntitle: \" Notes \"\ntags: \"#a, b,,\"\naliases: [x, 1]\ndate: 2021-01-31\n---\n";
+    let meta = parse_front_matter(content).unwrap().unwrap().meta();
+    assert_eq!(meta.title.as_deref(), Some("Notes"));
+    assert_eq!(meta.tags, vec!["a", "b"]);
+    assert_eq!(meta.aliases, vec!["x", "1"]);
+    assert_eq!(meta.created.as_deref(), Some("2021-01-31"));
+  }
+
+  #[test]
+  fn sets_front_matter_keys() {
+    let updates = |value: Value| value.as_object().unwrap().clone();
+    for (content, keys, expected) in &[
+      (
+        "# Body\n",
+        json!({"title": "Notes"}),
+        "---\ntitle: Notes\n---\n# Body\n",
+      ),
+      (
+        "---\ntitle: Notes\ntags: [a]\n---\n# Body\n",
+        json!({"tags": null, "draft": true}),
+        "---\ntitle: Notes\ndraft: true\n---\n# Body\n",
+      ),
+      (
+        "---\ntitle: Notes\n---\n# Body\n",
+        json!({"title": null}),
+        "# Body\n",
+      ),
+      (
+        "+++\ntitle = \"Notes\"\n+++\n# Body\n",
+        json!({"tags": ["a"]}),
+        "+++\ntitle = \"Notes\"\ntags = [\"a\"]\n+++\n# Body\n",
+      ),
+      (
+        "\u{feff}---\r\ntitle: Notes\r\n---\r\n# Body\r\n",
+        json!({"draft": false}),
+        "---\ntitle: Notes\ndraft: false\n---\n# Body\r\n",
+      ),
+    ] {
+      let (updated, front_matter) = set_front_matter_keys(content, updates(keys.clone())).unwrap();
+      assert_eq!(updated, *expected, "{:?}", content);
+      let expected_data = parse_front_matter(expected)
+        .unwrap()
+        .map_or_else(Map::new, |front_matter| front_matter.data);
+      assert_eq!(front_matter.data, expected_data);
+    }
+  }
+}
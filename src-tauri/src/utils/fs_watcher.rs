@@ -0,0 +1,307 @@
+use std::{
+  path::PathBuf,
+  sync::{mpsc, Arc, Mutex},
+  thread,
+  time::Duration,
+};
+
+use anyhow::Result;
+use log::{debug, error, info};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use serde::Serialize;
+
+use crate::utils::{
+  fsutils::{self, FileMetaInfo},
+  window_event_manager::{WindowEvent, WindowEventManager, WindowEventType},
+};
+
+/// Delay used to coalesce bursts of fs events into a single event.
+const WATCHER_DEBOUNCE_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentChangePayload {
+  /// Meta info of the changed document.
+  pub file_meta_info: FileMetaInfo,
+  /// Meta info of the document before rename (only for renames).
+  pub old_file_meta_info: Option<FileMetaInfo>,
+}
+
+/// # Documents Watcher Handle
+///
+/// Used to attach the window the watcher events are sent to.
+#[derive(Clone)]
+pub struct DocumentsWatcherHandle {
+  window: Arc<Mutex<Option<tauri::Window>>>,
+}
+
+impl DocumentsWatcherHandle {
+  /// Attach the window to send document change events to.
+  pub fn attach_window(&self, window: tauri::Window) {
+    match self.window.lock() {
+      Ok(mut w) => *w = Some(window),
+      Err(e) => error!("documents watcher window lock error: {}", e),
+    }
+  }
+}
+
+/// # Documents Watcher
+///
+/// Watches the documents dir for external changes (other editors, scripts etc.)
+/// and sends `document_created`, `document_modified`, `document_removed`
+/// and `document_renamed` window events.
+/// - Bursts of fs events are coalesced.
+/// - Changes made by the app itself (via `fsutils`) are ignored, unless
+///   the file was changed again by another program meanwhile.
+/// - Temp files of atomic writes are ignored.
+pub struct DocumentsWatcher {
+  documents_dir: PathBuf,
+  window: Arc<Mutex<Option<tauri::Window>>>,
+}
+
+impl DocumentsWatcher {
+  /// Start watching the `documents_dir` on a new thread
+  pub fn start(documents_dir: PathBuf) -> Result<DocumentsWatcherHandle> {
+    let (tx, rx) = mpsc::channel();
+    let mut fs_watcher = watcher(tx, WATCHER_DEBOUNCE_DELAY)?;
+    fs_watcher.watch(&documents_dir, RecursiveMode::Recursive)?;
+    let window = Arc::new(Mutex::new(None));
+    let documents_watcher = DocumentsWatcher {
+      documents_dir,
+      window: window.clone(),
+    };
+    thread::Builder::new()
+      .name("documents_watcher".to_string())
+      .spawn(move || {
+        let _fs_watcher = fs_watcher; // keep the watcher alive for as long as the thread runs
+        info!("Documents watcher started...");
+        for event in rx {
+          documents_watcher.handle_event(event);
+        }
+        info!("Documents watcher stopped!");
+      })?;
+    Ok(DocumentsWatcherHandle { window })
+  }
+
+  fn handle_event(&self, event: DebouncedEvent) {
+    debug!("documents watcher event: {:?}", event);
+    if let Some((name, payload)) = self.document_change(event) {
+      self.send(name, payload);
+    }
+  }
+
+  /// # Document Change
+  ///
+  /// Map the fs `event` to the window event to send (`None` if it is ignored).
+  /// - Folders only get events once removed/renamed (they can't be told from
+  ///   documents then), a removed folder is a `document_removed` of its path.
+  fn document_change(
+    &self,
+    event: DebouncedEvent,
+  ) -> Option<(&'static str, DocumentChangePayload)> {
+    let (name, path, old_path) = match event {
+      DebouncedEvent::Create(path) => ("document_created", path, None),
+      DebouncedEvent::Write(path) => ("document_modified", path, None),
+      DebouncedEvent::Remove(path) => ("document_removed", path, None),
+      DebouncedEvent::Rename(old_path, path) => ("document_renamed", path, Some(old_path)),
+      DebouncedEvent::Error(e, path) => {
+        error!("documents watcher error: {}, path: {:?}", e, path);
+        return None;
+      }
+      // Notices are followed by the actual (debounced) events.
+      // Chmod/Rescan do not change documents.
+      _ => return None,
+    };
+    if path.is_dir() || fsutils::is_temp_file(&path) {
+      return None; // only documents are reported
+    }
+    if fsutils::is_recent_app_write(&path)
+      && old_path.as_ref().map_or(true, fsutils::is_recent_app_write)
+    {
+      debug!("ignoring app write: {:?}", path);
+      return None;
+    }
+    let file_meta_info =
+      match fsutils::get_file_meta_from_path(self.documents_dir.as_path(), path.as_path()) {
+        Ok(file_meta_info) => file_meta_info,
+        Err(e) => {
+          error!("{:?}", e);
+          return None;
+        }
+      };
+    let old_file_meta_info = old_path.and_then(|old_path| {
+      fsutils::get_file_meta_from_path(self.documents_dir.as_path(), old_path.as_path()).ok()
+    });
+    Some((
+      name,
+      DocumentChangePayload {
+        file_meta_info,
+        old_file_meta_info,
+      },
+    ))
+  }
+
+  fn send(&self, name: &'static str, payload: DocumentChangePayload) {
+    let window = match self.window.lock() {
+      Ok(window) => match window.as_ref() {
+        Some(window) => window.clone(),
+        None => return, // window not ready yet
+      },
+      Err(e) => {
+        error!("documents watcher window lock error: {}", e);
+        return;
+      }
+    };
+    let wem = WindowEventManager::new(&window);
+    if let Err(e) = wem.send(WindowEvent {
+      name,
+      typ: WindowEventType::INFO,
+      data: payload,
+    }) {
+      error!("{:?}", e);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use serde_json::Value;
+
+  use super::*;
+  use crate::utils::test_utils::temp_documents_dir;
+
+  fn watcher(documents_dir: PathBuf) -> DocumentsWatcher {
+    DocumentsWatcher {
+      documents_dir,
+      window: Arc::new(Mutex::new(None)),
+    }
+  }
+
+  /// `(event name, relative path, old relative path)` of the document change
+  fn change(
+    watcher: &DocumentsWatcher,
+    event: DebouncedEvent,
+  ) -> Option<(&'static str, Value, Value)> {
+    let (name, payload) = watcher.document_change(event)?;
+    let payload = serde_json::to_value(payload).unwrap();
+    Some((
+      name,
+      payload["fileMetaInfo"]["fileRelativePath"].clone(),
+      payload["oldFileMetaInfo"]["fileRelativePath"].clone(),
+    ))
+  }
+
+  #[test]
+  fn maps_document_events() {
+    let documents_dir = temp_documents_dir("fs_watcher_events", &[("notes/a.md", "# A")]);
+    let watcher = watcher(documents_dir.clone());
+    let a = documents_dir.join("notes/a.md");
+    let b = documents_dir.join("notes/b.md");
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Create(a.clone())),
+      Some(("document_created", "notes/a.md".into(), Value::Null))
+    );
+    fs::write(&a, "# A changed").unwrap();
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Write(a.clone())),
+      Some(("document_modified", "notes/a.md".into(), Value::Null))
+    );
+    fs::rename(&a, &b).unwrap();
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Rename(a, b.clone())),
+      Some(("document_renamed", "notes/b.md".into(), "notes/a.md".into()))
+    );
+    fs::remove_file(&b).unwrap();
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Remove(b.clone())),
+      Some(("document_removed", "notes/b.md".into(), Value::Null))
+    );
+    let error = notify::Error::Generic("watch failed".to_string());
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Error(error, Some(b.clone()))),
+      None
+    );
+    assert_eq!(change(&watcher, DebouncedEvent::Chmod(b)), None);
+  }
+
+  #[test]
+  fn ignores_folders_and_temp_files() {
+    let documents_dir = temp_documents_dir("fs_watcher_ignored", &[("notes/a.md", "# A")]);
+    let watcher = watcher(documents_dir.clone());
+    let temp_path = documents_dir.join("notes/.a.md.mediocre-tmp");
+    fs::write(&temp_path, "# A").unwrap();
+    assert_eq!(
+      change(
+        &watcher,
+        DebouncedEvent::Create(documents_dir.join("notes"))
+      ),
+      None
+    );
+    assert_eq!(change(&watcher, DebouncedEvent::Create(temp_path)), None);
+  }
+
+  #[test]
+  fn ignores_app_writes_until_changed_again() {
+    let documents_dir = temp_documents_dir("fs_watcher_app_writes", &[("notes/a.md", "# A")]);
+    let watcher = watcher(documents_dir.clone());
+    let a = documents_dir.join("notes/a.md");
+    let temp_path = documents_dir.join("notes/.a.md.mediocre-tmp");
+    fsutils::write_to_path(&a, "# A saved".to_string()).unwrap();
+    assert_eq!(change(&watcher, DebouncedEvent::Write(a.clone())), None);
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Rename(temp_path, a.clone())),
+      None
+    );
+    // eg. another editor saves right after the app
+    fs::write(&a, "# A saved by another editor").unwrap();
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Write(a.clone())),
+      Some(("document_modified", "notes/a.md".into(), Value::Null))
+    );
+
+    let b = documents_dir.join("b.md");
+    fsutils::move_path(&a, &b).unwrap();
+    assert_eq!(change(&watcher, DebouncedEvent::Rename(a, b.clone())), None);
+    fsutils::remove_from_path(&b).unwrap();
+    assert_eq!(change(&watcher, DebouncedEvent::Remove(b.clone())), None);
+    // eg. restored by a git checkout
+    fs::write(&b, "# B").unwrap();
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Create(b)),
+      Some(("document_created", "b.md".into(), Value::Null))
+    );
+  }
+
+  #[test]
+  fn maps_removed_folders() {
+    let documents_dir = temp_documents_dir(
+      "fs_watcher_folders",
+      &[("notes/a.md", "# A"), ("old/b.md", "# B")],
+    );
+    let watcher = watcher(documents_dir.clone());
+    let notes = documents_dir.join("notes");
+    fs::remove_dir_all(&notes).unwrap();
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Remove(notes.join("a.md"))),
+      Some(("document_removed", "notes/a.md".into(), Value::Null))
+    );
+    let (name, payload) = watcher
+      .document_change(DebouncedEvent::Remove(notes))
+      .unwrap();
+    let payload = serde_json::to_value(payload).unwrap();
+    assert_eq!(name, "document_removed");
+    assert_eq!(payload["fileMetaInfo"]["fileRelativePath"], "notes");
+    assert_eq!(payload["fileMetaInfo"]["fileType"], Value::Null);
+
+    // removed by the app
+    let old = documents_dir.join("old");
+    fsutils::remove_dir(&old, true).unwrap();
+    assert_eq!(
+      change(&watcher, DebouncedEvent::Remove(old.join("b.md"))),
+      None
+    );
+    assert_eq!(change(&watcher, DebouncedEvent::Remove(old)), None);
+  }
+}
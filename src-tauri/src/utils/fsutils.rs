@@ -1,13 +1,20 @@
 use std::{
+  collections::HashMap,
   env,
+  ffi::OsStr,
   fs::{self, OpenOptions},
-  io::Write,
+  io::{self, Write},
   path::{Path, PathBuf},
+  sync::Mutex,
+  time::{Duration, Instant, SystemTime},
 };
 
 use chrono::{DateTime, SecondsFormat, Utc};
-use log::error;
+use lazy_static::lazy_static;
+use log::{error, warn};
+use relative_path::RelativePathBuf;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tauri::api::path::home_dir;
 use walkdir::WalkDir;
 
@@ -17,8 +24,68 @@ use crate::{
     app_dir_paths::AppDirPaths,
     server_error::{map_to_server_error, ServerError},
   },
+  utils::{
+    document_path::DocumentPath,
+    front_matter::{parse_front_matter, FrontMatterMeta},
+  },
 };
 
+/// Extension of the temp files used for atomic writes
+const TEMP_FILE_EXTENSION: &str = "mediocre-tmp";
+
+/// How long a change made by the app itself is remembered
+/// (so that fs watchers can ignore it).
+const APP_WRITE_TTL: Duration = Duration::from_secs(10);
+
+/// Modified time and size of a file/folder (`None` if there is none)
+type FileStamp = Option<(SystemTime, u64)>;
+
+lazy_static! {
+  /// Paths recently written/removed/renamed by the app itself
+  /// (with the stamp of the file right after the change)
+  static ref APP_WRITES: Mutex<HashMap<PathBuf, (Instant, FileStamp)>> = Mutex::new(HashMap::new());
+  /// Front matter metadata of the documents by path (with the modified time it was read at)
+  static ref FRONT_MATTER_META_CACHE: Mutex<HashMap<PathBuf, (SystemTime, FrontMatterMeta)>> =
+    Mutex::new(HashMap::new());
+}
+
+fn get_file_stamp(path: &Path) -> FileStamp {
+  let metadata = fs::symlink_metadata(path).ok()?;
+  Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Record that the app itself changed the file at `path` (once it is changed)
+fn record_app_write<P: AsRef<Path>>(path: P) {
+  let path = path.as_ref();
+  let stamp = get_file_stamp(path);
+  match APP_WRITES.lock() {
+    Ok(mut app_writes) => {
+      app_writes.insert(path.to_path_buf(), (Instant::now(), stamp));
+    }
+    Err(e) => error!("app writes lock error: {}", e),
+  }
+}
+
+/// Check if the file at `path` was recently changed by the app itself
+/// (via `write_to_path`, `remove_from_path` or `move_path`).
+/// - Only while the file is still as the app left it, a later change
+///   by another program (eg. an editor or a git checkout) is not ignored.
+pub fn is_recent_app_write<P: AsRef<Path>>(path: P) -> bool {
+  let path = path.as_ref();
+  match APP_WRITES.lock() {
+    Ok(mut app_writes) => {
+      app_writes.retain(|_, (written_at, _)| written_at.elapsed() < APP_WRITE_TTL);
+      app_writes
+        .get(path)
+        .map_or(false, |(_, stamp)| *stamp == get_file_stamp(path))
+    }
+    Err(e) => {
+      error!("app writes lock error: {}", e);
+      false
+    }
+  }
+}
+
 pub fn get_app_root_dir_path() -> Result<PathBuf, ServerError> {
   let debug_level = env::var("RUST_DEBUG").unwrap_or("0".to_string());
   match home_dir() {
@@ -51,6 +118,7 @@ pub fn create_app_default_dirs(app_dir_paths: &AppDirPaths) -> Result<(), Server
   fs::create_dir_all(&app_dir_paths.db).map_err(map_to_server_error)?;
   fs::create_dir_all(&app_dir_paths.documents).map_err(map_to_server_error)?;
   fs::create_dir_all(&app_dir_paths.logs).map_err(map_to_server_error)?;
+  fs::create_dir_all(&app_dir_paths.trash).map_err(map_to_server_error)?;
   Ok(())
 }
 
@@ -61,43 +129,236 @@ pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<String, ServerError> {
 }
 
 /// Write `json_data` to the specified file path (recursively create all parent paths)
+/// - Data is written to a temp file (in the same dir), synced to disk and then
+///   renamed to `path`. So a crash/full disk never leaves a truncated file behind.
 pub fn write_to_path<P: AsRef<Path> + Copy>(path: P, file_data: String) -> Result<(), ServerError> {
   let default_path = get_app_root_dir_path()?;
   let parent_path = path.as_ref().parent().unwrap_or(default_path.as_path());
   fs::create_dir_all(parent_path).map_err(map_to_server_error)?;
+  let temp_path = get_temp_file_path(path)?;
+  let written = write_and_sync(&temp_path, file_data.as_ref())
+    .and_then(|_| fs::rename(&temp_path, path))
+    .and_then(|_| sync_dir(parent_path));
+  if let Err(e) = written {
+    if temp_path.exists() {
+      if let Err(e) = fs::remove_file(&temp_path) {
+        error!("failed to remove temp file {:?}: {}", temp_path, e);
+      }
+    }
+    return Err(map_to_server_error(e));
+  }
+  record_app_write(&temp_path);
+  record_app_write(path);
+  Ok(())
+}
+
+/// Get the path of the temp file used to atomically write to `path`
+/// - eg. `notes/a.md` -> `notes/.a.md.mediocre-tmp`
+fn get_temp_file_path<P: AsRef<Path>>(path: P) -> Result<PathBuf, ServerError> {
+  let file_name = path
+    .as_ref()
+    .file_name()
+    .ok_or_else(|| ServerError::UserError {
+      message: "file_name not available!".to_string(),
+    })?
+    .to_string_lossy();
+  Ok(
+    path
+      .as_ref()
+      .with_file_name(format!(".{}.{}", file_name, TEMP_FILE_EXTENSION)),
+  )
+}
+
+/// Check if the file at `path` is a temp file (of an atomic write)
+pub fn is_temp_file<P: AsRef<Path>>(path: P) -> bool {
+  path.as_ref().extension() == Some(OsStr::new(TEMP_FILE_EXTENSION))
+}
+
+fn write_and_sync(path: &Path, data: &[u8]) -> io::Result<()> {
   let mut f = OpenOptions::new()
     .write(true)
     .create(true)
     .truncate(true)
-    .open(path.as_ref())
-    .map_err(map_to_server_error)?;
-  f.write_all(file_data.as_ref())
-    .map_err(map_to_server_error)?;
-  f.sync_all().map_err(map_to_server_error)?;
+    .open(path)?;
+  f.write_all(data)?;
+  f.sync_all()
+}
+
+/// Sync the dir entries (so that a rename survives a crash)
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+  fs::File::open(path)?.sync_all()
+}
+
+/// Dirs can't be opened/synced on windows (renames are synced by the fs)
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
   Ok(())
 }
 
+/// Get the SHA-256 hash (hex) of the `content`
+pub fn get_content_hash(content: &str) -> String {
+  format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Get the modified time (RFC 3339) of the file at `path`
+/// - Same format as `FileMetaInfo.modified`.
+pub fn get_modified_from_path<P: AsRef<Path>>(path: P) -> Option<String> {
+  let modified = path.as_ref().metadata().ok()?.modified().ok()?;
+  let system_time: DateTime<Utc> = modified.into();
+  Some(system_time.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+/// # Write Conflict
+///
+/// The file changed on disk since it was read (see `check_write_preconditions`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteConflict {
+  /// Current content on disk (`None` if the file was removed)
+  pub disk_content: Option<String>,
+  /// Current modified time on disk
+  pub disk_modified: Option<String>,
+  /// SHA-256 hash of the current content on disk
+  pub disk_content_hash: Option<String>,
+}
+
+/// Check the write preconditions against the file at `path` on disk.
+/// - `expected_modified`/`expected_content_hash`: values from when the file was read
+///   (see `get_modified_from_path`/`get_content_hash`), not checked if `None`.
+/// - Returns the conflict if the file changed since it was read.
+pub fn check_write_preconditions<P: AsRef<Path>>(
+  path: P,
+  expected_modified: Option<&str>,
+  expected_content_hash: Option<&str>,
+) -> Result<Option<WriteConflict>, ServerError> {
+  let path = path.as_ref();
+  if expected_modified.is_none() && expected_content_hash.is_none() {
+    return Ok(None);
+  }
+  let disk_content = if path.exists() {
+    Some(read_from_path(path)?)
+  } else {
+    None
+  };
+  let disk_modified = get_modified_from_path(path);
+  let disk_content_hash = disk_content.as_deref().map(get_content_hash);
+  let modified_matches = expected_modified.map_or(true, |m| disk_modified.as_deref() == Some(m));
+  let content_hash_matches =
+    expected_content_hash.map_or(true, |h| disk_content_hash.as_deref() == Some(h));
+  if modified_matches && content_hash_matches {
+    return Ok(None);
+  }
+  Ok(Some(WriteConflict {
+    disk_content,
+    disk_modified,
+    disk_content_hash,
+  }))
+}
+
 /// Remove file from path
 pub fn remove_from_path<P: AsRef<Path> + Copy>(path: P) -> Result<(), ServerError> {
   // remove only the file of specified path
   fs::remove_file(path).map_err(map_to_server_error)?;
+  record_app_write(path);
   Ok(())
 }
 
-/// Rename file at the specified path to `new_file_name`
-pub fn rename_file<P: AsRef<Path> + Copy>(
-  path: P,
-  new_file_name: String,
-) -> Result<(), ServerError> {
-  let parent_path = path.as_ref().parent().ok_or(ServerError::UserError {
+/// Paths of all files in the dir at `path` (and the dir itself)
+fn get_paths_recursive<P: AsRef<Path>>(path: P) -> Vec<PathBuf> {
+  WalkDir::new(path)
+    .into_iter()
+    .filter_map(|e| e.ok())
+    .map(|entry| entry.into_path())
+    .collect()
+}
+
+/// Create a folder at the specified path
+/// - `recursive`: also create missing parent folders.
+pub fn create_dir<P: AsRef<Path>>(path: P, recursive: bool) -> Result<(), ServerError> {
+  let path = path.as_ref();
+  if path.exists() {
+    return Err(ServerError::AlreadyExistsError {
+      message: format!("{:?} already exists!", path),
+    });
+  }
+  if recursive {
+    fs::create_dir_all(path).map_err(map_to_server_error)?;
+  } else {
+    fs::create_dir(path).map_err(map_to_server_error)?;
+  }
+  record_app_write(path);
+  Ok(())
+}
+
+/// Move (or rename) the file/folder at `from` to `to`
+/// - Never overwrites: fails if `to` already exists.
+/// - A folder can't be moved into itself.
+pub fn move_path<P: AsRef<Path>>(from: P, to: P) -> Result<(), ServerError> {
+  let (from, to) = (from.as_ref(), to.as_ref());
+  if !from.exists() {
+    return Err(ServerError::NotFoundError {
+      message: format!("{:?} does not exist!", from),
+    });
+  }
+  if to.exists() {
+    return Err(ServerError::AlreadyExistsError {
+      message: format!("{:?} already exists!", to),
+    });
+  }
+  if from.is_dir() && to.starts_with(from) {
+    return Err(ServerError::UserError {
+      message: "A folder can not be moved into itself!".to_string(),
+    });
+  }
+  let parent_path = to.parent().ok_or(ServerError::UserError {
     message: "parent_path invalid!".to_string(),
   })?;
-  let new_path = parent_path.join(new_file_name);
-  fs::rename(path, new_path).map_err(map_to_server_error)?;
+  if !parent_path.is_dir() {
+    return Err(ServerError::NotFoundError {
+      message: format!("Folder {:?} does not exist!", parent_path),
+    });
+  }
+  let moved_paths = get_paths_recursive(from);
+  fs::rename(from, to).map_err(map_to_server_error)?;
+  // the old paths are gone now, the new ones have the moved files
+  moved_paths
+    .iter()
+    .chain(&get_paths_recursive(to))
+    .for_each(record_app_write);
+  Ok(())
+}
+
+/// Remove folder from path
+/// - `recursive`: also remove all its files/folders, else the folder must be empty.
+pub fn remove_dir<P: AsRef<Path>>(path: P, recursive: bool) -> Result<(), ServerError> {
+  let path = path.as_ref();
+  if !path.is_dir() {
+    return Err(ServerError::UserError {
+      message: format!("{:?} is not a folder!", path),
+    });
+  }
+  if recursive {
+    let removed_paths = get_paths_recursive(path);
+    fs::remove_dir_all(path).map_err(map_to_server_error)?;
+    removed_paths.iter().for_each(record_app_write);
+  } else {
+    let is_empty = fs::read_dir(path)
+      .map_err(map_to_server_error)?
+      .next()
+      .is_none();
+    if !is_empty {
+      return Err(ServerError::UserError {
+        message: format!("Folder {:?} is not empty!", path),
+      });
+    }
+    fs::remove_dir(path).map_err(map_to_server_error)?;
+    record_app_write(path);
+  }
   Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileMetaInfo {
   file_name: String,
@@ -106,6 +367,14 @@ pub struct FileMetaInfo {
   file_dir: Option<String>,
   file_type: Option<String>,
   modified: Option<String>,
+  /// Title from the front matter (markdown documents)
+  title: Option<String>,
+  /// Tags from the front matter
+  tags: Vec<String>,
+  /// Aliases (alternative names) from the front matter
+  aliases: Vec<String>,
+  /// `created`/`date` from the front matter, else the file creation time (RFC 3339)
+  created: Option<String>,
 }
 
 /// Get file meta info for given file path.
@@ -154,16 +423,14 @@ pub fn get_file_meta_from_path<P: AsRef<Path> + Copy>(
     }
     None => None,
   };
-  let modified = match path_ref.metadata() {
-    Ok(m) => match m.modified() {
-      Ok(t) => {
-        let system_time: DateTime<Utc> = t.into();
-        Some(system_time.to_rfc3339_opts(SecondsFormat::Millis, true))
-      }
-      Err(_) => None,
-    },
-    Err(_) => None,
+  let modified = get_modified_from_path(path_ref);
+  let front_matter_meta = match file_type {
+    Some(_) => get_front_matter_meta_from_path(path_ref),
+    None => FrontMatterMeta::default(),
   };
+  let created = front_matter_meta
+    .created
+    .or_else(|| get_created_from_path(path_ref));
   Ok(FileMetaInfo {
     file_name,
     file_path,
@@ -171,9 +438,66 @@ pub fn get_file_meta_from_path<P: AsRef<Path> + Copy>(
     file_dir,
     file_type,
     modified,
+    title: front_matter_meta.title,
+    tags: front_matter_meta.tags,
+    aliases: front_matter_meta.aliases,
+    created,
   })
 }
 
+/// Get the front matter metadata of the markdown document at `path`
+/// - Empty if the document has no (or an invalid) front matter.
+/// - Cached until the modified time of the document changes
+///   (so listing the documents does not parse all of them every time).
+fn get_front_matter_meta_from_path(path: &Path) -> FrontMatterMeta {
+  let modified = match path.metadata().and_then(|m| m.modified()) {
+    Ok(modified) => modified,
+    Err(_) => return read_front_matter_meta(path),
+  };
+  if let Ok(cache) = FRONT_MATTER_META_CACHE.lock() {
+    if let Some((cached_modified, meta)) = cache.get(path) {
+      if *cached_modified == modified {
+        return meta.clone();
+      }
+    }
+  }
+  let meta = read_front_matter_meta(path);
+  match FRONT_MATTER_META_CACHE.lock() {
+    Ok(mut cache) => {
+      cache.insert(path.to_path_buf(), (modified, meta.clone()));
+    }
+    Err(e) => error!("front matter cache lock error: {}", e),
+  }
+  meta
+}
+
+fn read_front_matter_meta(path: &Path) -> FrontMatterMeta {
+  let content = match read_from_path(path) {
+    Ok(content) => content,
+    Err(e) => {
+      warn!("failed to read {:?} for front matter: {}", path, e);
+      return FrontMatterMeta::default();
+    }
+  };
+  match parse_front_matter(&content) {
+    Ok(front_matter) => front_matter
+      .map(|front_matter| front_matter.meta())
+      .unwrap_or_default(),
+    Err(e) => {
+      warn!("invalid front matter in {:?}: {:#}", path, e);
+      FrontMatterMeta::default()
+    }
+  }
+}
+
+/// Get the creation time (RFC 3339) of the file at `path`
+/// - `None` if the platform/fs does not record it.
+fn get_created_from_path(path: &Path) -> Option<String> {
+  let created = path.metadata().ok()?.created().ok()?;
+  let system_time: DateTime<Utc> = created.into();
+  Some(system_time.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
 /// Get all files meta info in the given dir path
 pub fn get_all_files_meta_from_path<P: AsRef<Path> + Copy>(
   dir_path: P,
@@ -183,7 +507,7 @@ pub fn get_all_files_meta_from_path<P: AsRef<Path> + Copy>(
     .filter_map(|e| e.ok())
     .filter_map(|e| match e.metadata() {
       Ok(m) => {
-        if m.is_file() {
+        if m.is_file() && !is_temp_file(e.path()) {
           Some(e)
         } else {
           None
@@ -191,54 +515,260 @@ pub fn get_all_files_meta_from_path<P: AsRef<Path> + Copy>(
       }
       Err(_) => None,
     })
-    .filter_map(|e| {
-      // There have been discussions wrt differences in file
-      // path serialization between various platforms namely win and unix
-      // Currently will assume valid and serialize(able) chars are used
-      // as file names and paths will be constructed using the app itself.
-      // Update: Trying to use RelativePath crate to solve this
-      let file_name = e.file_name().to_string_lossy().to_string();
-      let file_path = e.path().to_string_lossy().to_string();
-      let file_relative_path = match e.path().strip_prefix(dir_path.clone()) {
-        Ok(p) => Some(p.to_string_lossy().to_string()),
-        Err(_) => None,
-      };
-      let file_dir = match e.path().parent() {
-        Some(p) => match p.components().last() {
-          Some(c) => Some(c.as_os_str().to_string_lossy().to_string()),
-          None => None,
-        },
-        None => None,
-      };
-      let file_type = match e.path().extension() {
-        Some(ext) => {
-          if ext.eq(Path::new("test.md").extension().unwrap()) {
-            Some("markdown".to_string())
-          } else {
-            None
-          }
-        }
-        None => None,
-      };
-      let modified = match e.metadata() {
-        Ok(m) => match m.modified() {
-          Ok(t) => {
-            let system_time: DateTime<Utc> = t.into();
-            Some(system_time.to_rfc3339_opts(SecondsFormat::Millis, true))
-          }
-          Err(_) => None,
-        },
-        Err(_) => None,
-      };
-      Some(FileMetaInfo {
-        file_name,
-        file_path,
-        file_relative_path,
-        file_dir,
-        file_type,
-        modified,
-      })
-    })
+    .filter_map(|e| get_file_meta_from_path(dir_path.as_ref(), e.path()).ok())
     .collect::<Vec<FileMetaInfo>>();
   Ok(meta_info)
 }
+
+/// Node of the documents tree (see `get_docs_tree_from_path`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DocsTreeNode {
+  #[serde(rename_all = "camelCase")]
+  Folder {
+    name: String,
+    /// Path relative to the dir the tree was built from
+    relative_path: String,
+    children: Vec<DocsTreeNode>,
+  },
+  #[serde(rename_all = "camelCase")]
+  Document { file_meta_info: FileMetaInfo },
+}
+
+/// Get the nested tree of all folders (including empty ones) and files in the given documents dir
+/// - Folders are listed before files, both sorted by name.
+/// - Symlinked folders are not followed (they may loop), symlinked files are only
+///   listed if they resolve inside the documents dir (see `DocumentPath`).
+pub fn get_docs_tree_from_path<P: AsRef<Path>>(
+  dir_path: P,
+) -> Result<Vec<DocsTreeNode>, ServerError> {
+  get_docs_tree_nodes(dir_path.as_ref(), dir_path.as_ref())
+}
+
+fn get_docs_tree_nodes(
+  base_path: &Path,
+  dir_path: &Path,
+) -> Result<Vec<DocsTreeNode>, ServerError> {
+  let mut entries = vec![];
+  for entry in fs::read_dir(dir_path)
+    .map_err(map_to_server_error)?
+    .filter_map(|e| e.ok())
+  {
+    let path = entry.path();
+    let relative_path = match path.strip_prefix(base_path) {
+      Ok(p) => p.to_string_lossy().to_string(),
+      Err(_) => continue,
+    };
+    // file type of the entry itself (symlinks are not followed)
+    let file_type = match entry.file_type() {
+      Ok(file_type) => file_type,
+      Err(_) => continue,
+    };
+    let is_dir = if file_type.is_symlink() {
+      let is_inside = RelativePathBuf::from_path(Path::new(&relative_path)).map_or(false, |p| {
+        DocumentPath::resolve(base_path, p.as_str()).is_ok()
+      });
+      if path.is_dir() || !is_inside {
+        warn!("skipping symlink {:?} in the documents tree", path);
+        continue;
+      }
+      false
+    } else {
+      file_type.is_dir()
+    };
+    if !is_temp_file(&path) {
+      entries.push((is_dir, path, relative_path));
+    }
+  }
+  entries.sort_by_key(|(is_dir, path, _)| (!is_dir, path.file_name().map(|n| n.to_os_string())));
+  let mut nodes = Vec::with_capacity(entries.len());
+  for (is_dir, path, relative_path) in entries {
+    if is_dir {
+      nodes.push(DocsTreeNode::Folder {
+        name: path
+          .file_name()
+          .map(|n| n.to_string_lossy().to_string())
+          .unwrap_or_default(),
+        relative_path,
+        children: get_docs_tree_nodes(base_path, &path)?,
+      });
+    } else if path.is_file() {
+      nodes.push(DocsTreeNode::Document {
+        file_meta_info: get_file_meta_from_path(base_path, path.as_path())?,
+      });
+    }
+  }
+  Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::test_utils::temp_documents_dir;
+
+  /// Relative paths of all the nodes of the `tree` (folders end with `/`)
+  fn tree_paths(tree: &[DocsTreeNode]) -> Vec<String> {
+    let mut paths = vec![];
+    for node in tree {
+      match node {
+        DocsTreeNode::Folder {
+          relative_path,
+          children,
+          ..
+        } => {
+          paths.push(format!("{}/", relative_path));
+          paths.extend(tree_paths(children));
+        }
+        DocsTreeNode::Document { file_meta_info } => {
+          paths.push(file_meta_info.file_relative_path.clone().unwrap())
+        }
+      }
+    }
+    paths
+  }
+
+  #[test]
+  fn lists_docs_tree() {
+    let documents_dir = temp_documents_dir("fsutils_tree", &[("notes/a.md", "# A")]);
+    fs::create_dir_all(documents_dir.join("empty")).unwrap();
+    fs::write(documents_dir.join("b.md"), "b").unwrap();
+    fs::write(documents_dir.join(".b.md.mediocre-tmp"), "b").unwrap();
+    let tree = get_docs_tree_from_path(&documents_dir).unwrap();
+    assert_eq!(
+      tree_paths(&tree),
+      vec!["empty/", "notes/", "notes/a.md", "b.md"]
+    );
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn skips_symlink_loops_and_escapes() {
+    use std::os::unix::fs::symlink;
+
+    let documents_dir = temp_documents_dir("fsutils_symlinks", &[("notes/a.md", "# A")]);
+    let root = documents_dir.parent().unwrap();
+    fs::write(root.join("outside.md"), "outside").unwrap();
+    symlink(&documents_dir, documents_dir.join("notes/loop")).unwrap();
+    symlink("..", documents_dir.join("notes/parent")).unwrap();
+    symlink(root.join("outside.md"), documents_dir.join("outside.md")).unwrap();
+    symlink(&root, documents_dir.join("root")).unwrap();
+    symlink("notes/a.md", documents_dir.join("inside.md")).unwrap();
+    let tree = get_docs_tree_from_path(&documents_dir).unwrap();
+    assert_eq!(tree_paths(&tree), vec!["notes/", "notes/a.md", "inside.md"]);
+  }
+
+  #[test]
+  fn keeps_not_found_and_already_exists_errors() {
+    let documents_dir = temp_documents_dir("fsutils_errors", &[("notes/a.md", "# A")]);
+    let missing = documents_dir.join("missing.md");
+    let existing = documents_dir.join("notes/a.md");
+    assert!(matches!(
+      read_from_path(&missing),
+      Err(ServerError::NotFoundError { .. })
+    ));
+    assert!(matches!(
+      move_path(&missing, &documents_dir.join("b.md")),
+      Err(ServerError::NotFoundError { .. })
+    ));
+    assert!(matches!(
+      move_path(&existing, &existing),
+      Err(ServerError::AlreadyExistsError { .. })
+    ));
+    assert!(matches!(
+      create_dir(documents_dir.join("notes"), false),
+      Err(ServerError::AlreadyExistsError { .. })
+    ));
+    assert!(matches!(
+      remove_from_path(&missing),
+      Err(ServerError::NotFoundError { .. })
+    ));
+  }
+
+  /// Names of the files in the dir at `path`
+  fn file_names(path: &Path) -> Vec<String> {
+    let mut names = fs::read_dir(path)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+      .collect::<Vec<_>>();
+    names.sort();
+    names
+  }
+
+  #[test]
+  fn writes_atomically() {
+    let documents_dir = temp_documents_dir("fsutils_atomic_write", &[("notes/a.md", "# A")]);
+    let a = documents_dir.join("notes/a.md");
+    write_to_path(&a, "# A changed".to_string()).unwrap();
+    assert_eq!(read_from_path(&a).unwrap(), "# A changed");
+    let b = documents_dir.join("new/folder/b.md");
+    write_to_path(&b, "# B".to_string()).unwrap();
+    assert_eq!(read_from_path(&b).unwrap(), "# B");
+    // the temp files are gone
+    assert_eq!(file_names(&documents_dir.join("notes")), vec!["a.md"]);
+    assert_eq!(file_names(&documents_dir.join("new/folder")), vec!["b.md"]);
+  }
+
+  #[test]
+  fn removes_the_temp_file_on_errors() {
+    let documents_dir = temp_documents_dir("fsutils_failed_write", &[("notes/a.md", "# A")]);
+    // a folder can't be replaced by the written file
+    let notes = documents_dir.join("notes");
+    assert!(write_to_path(&notes, "# Notes".to_string()).is_err());
+    assert_eq!(file_names(&documents_dir), vec!["notes"]);
+    assert_eq!(file_names(&notes), vec!["a.md"]);
+    assert_eq!(read_from_path(notes.join("a.md")).unwrap(), "# A");
+  }
+
+  #[test]
+  fn checks_write_preconditions() {
+    let documents_dir = temp_documents_dir("fsutils_preconditions", &[("notes/a.md", "# A")]);
+    let a = documents_dir.join("notes/a.md");
+    let modified = get_modified_from_path(&a).unwrap();
+    let content_hash = get_content_hash("# A");
+    let other_hash = get_content_hash("# B");
+    for (expected_modified, expected_content_hash, conflicts) in &[
+      (None, None, false),
+      (Some(modified.as_str()), None, false),
+      (None, Some(content_hash.as_str()), false),
+      (Some(modified.as_str()), Some(content_hash.as_str()), false),
+      (Some("2000-01-01T00:00:00.000Z"), None, true),
+      (None, Some(other_hash.as_str()), true),
+      (Some(modified.as_str()), Some(other_hash.as_str()), true),
+    ] {
+      let conflict = check_write_preconditions(&a, *expected_modified, *expected_content_hash)
+        .unwrap()
+        .map(|conflict| {
+          (
+            conflict.disk_content.unwrap(),
+            conflict.disk_modified.unwrap(),
+            conflict.disk_content_hash.unwrap(),
+          )
+        });
+      let expected = if *conflicts {
+        Some(("# A".to_string(), modified.clone(), content_hash.clone()))
+      } else {
+        None
+      };
+      assert_eq!(
+        conflict, expected,
+        "{:?}, {:?}",
+        expected_modified, expected_content_hash
+      );
+    }
+  }
+
+  #[test]
+  fn conflicts_with_removed_files() {
+    let documents_dir = temp_documents_dir("fsutils_removed_precondition", &[]);
+    let missing = documents_dir.join("missing.md");
+    assert!(check_write_preconditions(&missing, None, None)
+      .unwrap()
+      .is_none());
+    let conflict = check_write_preconditions(&missing, None, Some(&get_content_hash("# A")))
+      .unwrap()
+      .unwrap();
+    assert_eq!(conflict.disk_content, None);
+    assert_eq!(conflict.disk_modified, None);
+    assert_eq!(conflict.disk_content_hash, None);
+  }
+}
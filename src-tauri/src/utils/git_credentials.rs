@@ -0,0 +1,203 @@
+use std::path::Path;
+
+use git2::{Cred, CredentialType, RemoteCallbacks};
+use log::debug;
+
+use crate::models::git_auth::{GitAuthMethod, GitAuthSecret};
+
+/// Max number of times the credentials callback is allowed to run
+/// for a single operation. `libgit2` keeps calling it for as long as
+/// auth fails, so this stops it from looping forever.
+const MAX_AUTH_ATTEMPTS: usize = 3;
+
+/// Default user name for SSH remotes without a user in the url.
+const DEFAULT_SSH_USER_NAME: &str = "git";
+
+/// # Git Credential Provider
+///
+/// Provides `git2` credentials for the configured `GitAuthMethod`
+/// based on the credential types allowed by the remote.
+#[derive(Debug, Clone, Default)]
+pub struct GitCredentialProvider {
+  method: GitAuthMethod,
+  secret: Option<GitAuthSecret>,
+}
+
+impl GitCredentialProvider {
+  /// Create a new provider for the given auth `method` and (optional) `secret`
+  pub fn new(method: GitAuthMethod, secret: Option<GitAuthSecret>) -> Self {
+    Self { method, secret }
+  }
+
+  /// # Get Credentials
+  ///
+  /// Get credentials for the remote `url` based on the `allowed_types`
+  /// requested by `libgit2`.
+  pub fn credentials(
+    &self,
+    url: &str,
+    username_from_url: Option<&str>,
+    allowed_types: CredentialType,
+  ) -> Result<Cred, git2::Error> {
+    debug!(
+      "credentials() -> url: {}, username_from_url: {:?}, allowed_types: {:?}",
+      url, username_from_url, allowed_types
+    );
+    let ssh_user_name = username_from_url.unwrap_or(DEFAULT_SSH_USER_NAME);
+    // SSH remotes without a user in the url first ask for the user name only
+    if allowed_types.contains(CredentialType::USERNAME) {
+      return Cred::username(ssh_user_name);
+    }
+    match &self.method {
+      GitAuthMethod::Https { username }
+        if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) =>
+      {
+        let token = self
+          .secret
+          .as_ref()
+          .ok_or_else(|| git2::Error::from_str("access token is required for https auth!"))?;
+        Cred::userpass_plaintext(username, &token.0)
+      }
+      GitAuthMethod::SshKey {
+        private_key_path,
+        public_key_path,
+      } if allowed_types.contains(CredentialType::SSH_KEY) => Cred::ssh_key(
+        ssh_user_name,
+        public_key_path.as_ref().map(Path::new),
+        Path::new(private_key_path),
+        self.secret.as_ref().map(|s| s.0.as_str()),
+      ),
+      GitAuthMethod::SshAgent if allowed_types.contains(CredentialType::SSH_KEY) => {
+        Cred::ssh_key_from_agent(ssh_user_name)
+      }
+      _ if allowed_types.contains(CredentialType::DEFAULT) => Cred::default(),
+      method => Err(git2::Error::from_str(&format!(
+        "auth method {:?} is not supported by the remote (allowed: {:?})",
+        method, allowed_types
+      ))),
+    }
+  }
+
+  /// # Create Callbacks for Git Auth
+  ///
+  /// Create `RemoteCallbacks` with the credentials callback set.
+  pub fn create_callbacks<'a>(&'a self) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(self.credentials_callback());
+    callbacks
+  }
+
+  /// Credentials callback failing once called more than `MAX_AUTH_ATTEMPTS` times
+  fn credentials_callback(
+    &self,
+  ) -> impl FnMut(&str, Option<&str>, CredentialType) -> Result<Cred, git2::Error> + '_ {
+    let mut attempts = 0;
+    move |url, username_from_url, allowed_types| {
+      attempts += 1;
+      if attempts > MAX_AUTH_ATTEMPTS {
+        return Err(git2::Error::from_str(
+          "authentication failed, please check the cloud sync credentials!",
+        ));
+      }
+      self.credentials(url, username_from_url, allowed_types)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const URL: &str = "https://example.com/mediocre.git";
+
+  fn https(secret: Option<&str>) -> GitCredentialProvider {
+    GitCredentialProvider::new(
+      GitAuthMethod::Https {
+        username: "mediocre".to_string(),
+      },
+      secret.map(|secret| GitAuthSecret(secret.to_string())),
+    )
+  }
+
+  fn ssh_key() -> GitCredentialProvider {
+    GitCredentialProvider::new(
+      GitAuthMethod::SshKey {
+        private_key_path: "/home/mediocre/.ssh/id_ed25519".to_string(),
+        public_key_path: None,
+      },
+      Some(GitAuthSecret("passphrase".to_string())),
+    )
+  }
+
+  #[test]
+  fn negotiates_the_allowed_types() {
+    let (ssh_agent, ssh_key, https) = (
+      GitCredentialProvider::default(),
+      ssh_key(),
+      https(Some("token")),
+    );
+    let cases = vec![
+      (&ssh_agent, CredentialType::SSH_KEY, CredentialType::SSH_KEY),
+      (
+        &ssh_agent,
+        CredentialType::USER_PASS_PLAINTEXT | CredentialType::DEFAULT,
+        CredentialType::DEFAULT,
+      ),
+      (
+        &ssh_agent,
+        CredentialType::USERNAME | CredentialType::SSH_KEY,
+        CredentialType::USERNAME,
+      ),
+      (&ssh_key, CredentialType::SSH_KEY, CredentialType::SSH_KEY),
+      (
+        &https,
+        CredentialType::USER_PASS_PLAINTEXT | CredentialType::SSH_KEY,
+        CredentialType::USER_PASS_PLAINTEXT,
+      ),
+      (&https, CredentialType::DEFAULT, CredentialType::DEFAULT),
+    ];
+    for (provider, allowed_types, expected) in cases {
+      let cred = provider.credentials(URL, None, allowed_types).unwrap();
+      assert_eq!(
+        cred.credtype(),
+        expected.bits(),
+        "{:?} with {:?}",
+        provider,
+        allowed_types
+      );
+    }
+  }
+
+  #[test]
+  fn fails_without_a_usable_method() {
+    let error = https(None)
+      .credentials(URL, None, CredentialType::USER_PASS_PLAINTEXT)
+      .err()
+      .unwrap();
+    assert!(error.message().contains("access token is required"));
+    let error = https(Some("token"))
+      .credentials(URL, None, CredentialType::SSH_KEY)
+      .err()
+      .unwrap();
+    assert!(error.message().contains("not supported by the remote"));
+    assert!(ssh_key()
+      .credentials(URL, None, CredentialType::USER_PASS_PLAINTEXT)
+      .is_err());
+  }
+
+  #[test]
+  fn stops_after_max_auth_attempts() {
+    let provider = https(Some("token"));
+    let mut callback = provider.credentials_callback();
+    for _ in 0..MAX_AUTH_ATTEMPTS {
+      assert!(callback(URL, None, CredentialType::USER_PASS_PLAINTEXT).is_ok());
+    }
+    let error = callback(URL, None, CredentialType::USER_PASS_PLAINTEXT)
+      .err()
+      .unwrap();
+    assert!(error.message().contains("authentication failed"));
+    // each operation gets its own attempts
+    let mut callback = provider.credentials_callback();
+    assert!(callback(URL, None, CredentialType::USER_PASS_PLAINTEXT).is_ok());
+  }
+}
@@ -0,0 +1,503 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use git2::{Delta, Diff, Patch};
+
+use crate::models::diff::{
+  DiffChangeKind, DiffHunk, DiffLine, DiffMode, DiffSegment, FileChange, FileDiff, FileDiffStatus,
+};
+
+/// Max size (old tokens x new tokens) of a change block diffed word by word.
+/// Bigger blocks are reported as fully removed + added.
+const MAX_WORD_DIFF_CELLS: usize = 1_000_000;
+
+/// # Diff To File Diffs
+///
+/// Convert a `git2::Diff` to structured `FileDiff`s.
+/// - Paths are relative to the repo root.
+pub fn diff_to_file_diffs(diff: &Diff, mode: DiffMode) -> Result<Vec<FileDiff>> {
+  let mut file_diffs = vec![];
+  for index in 0..diff.deltas().len() {
+    match Patch::from_diff(diff, index)? {
+      Some(patch) => file_diffs.extend(patch_to_file_diff(&patch, mode)?),
+      None => {
+        // no patch is generated for binary/unchanged files
+        let delta = diff
+          .get_delta(index)
+          .ok_or_else(|| anyhow!("diff delta {} not found!", index))?;
+        if let Some(file_diff) = delta_to_file_diff(&delta, vec![]) {
+          file_diffs.push(file_diff);
+        }
+      }
+    }
+  }
+  Ok(file_diffs)
+}
+
+/// # Diff To File Changes
+///
+/// Get the changed files of a `git2::Diff` (without computing the patches).
+/// - Paths are relative to the repo root.
+pub fn diff_to_file_changes(diff: &Diff) -> Vec<FileChange> {
+  diff
+    .deltas()
+    .filter_map(|delta| delta_to_file_change(&delta))
+    .collect()
+}
+
+/// # Patch To File Diff
+///
+/// Convert a `git2::Patch` to a structured `FileDiff` (`None` if nothing changed).
+pub fn patch_to_file_diff(patch: &Patch, mode: DiffMode) -> Result<Option<FileDiff>> {
+  let mut hunks = vec![];
+  for hunk_index in 0..patch.num_hunks() {
+    let (hunk, line_count) = patch.hunk(hunk_index)?;
+    let mut lines = vec![];
+    for line_index in 0..line_count {
+      let line = patch.line_in_hunk(hunk_index, line_index)?;
+      let kind = match line.origin() {
+        ' ' => DiffChangeKind::Equal,
+        '+' => DiffChangeKind::Added,
+        '-' => DiffChangeKind::Removed,
+        _ => continue, // end of file newline markers
+      };
+      let content = String::from_utf8_lossy(line.content());
+      lines.push(DiffLine {
+        kind,
+        old_line_number: line.old_lineno(),
+        new_line_number: line.new_lineno(),
+        content: content.trim_end_matches(&['\r', '\n'][..]).to_string(),
+      });
+    }
+    let words = match mode {
+      DiffMode::Word => Some(diff_hunk_words(&lines)),
+      DiffMode::Line => None,
+    };
+    hunks.push(DiffHunk {
+      header: String::from_utf8_lossy(hunk.header())
+        .trim_end()
+        .to_string(),
+      old_start: hunk.old_start(),
+      old_lines: hunk.old_lines(),
+      new_start: hunk.new_start(),
+      new_lines: hunk.new_lines(),
+      lines,
+      words,
+    });
+  }
+  Ok(delta_to_file_diff(&patch.delta(), hunks))
+}
+
+/// # Diff Contents
+///
+/// Diff two versions of a document given as `(path, content)`
+/// (`None` if the document does not exist in that version).
+/// - Returns `None` if nothing changed.
+pub fn diff_contents(
+  old: Option<(&str, &str)>,
+  new: Option<(&str, &str)>,
+  mode: DiffMode,
+) -> Result<Option<FileDiff>> {
+  let (old_path, old_content) = old.unwrap_or(("", ""));
+  let (new_path, new_content) = new.unwrap_or(("", ""));
+  let patch = Patch::from_buffers(
+    old_content.as_bytes(),
+    Some(Path::new(old_path)).filter(|p| !p.as_os_str().is_empty()),
+    new_content.as_bytes(),
+    Some(Path::new(new_path)).filter(|p| !p.as_os_str().is_empty()),
+    None,
+  )?;
+  let mut file_diff = match patch_to_file_diff(&patch, mode)? {
+    Some(file_diff) => file_diff,
+    None if old.is_some() && new.is_some() && old_path != new_path => FileDiff {
+      relative_path: String::new(),
+      old_relative_path: None,
+      status: FileDiffStatus::Renamed,
+      binary: false,
+      hunks: vec![],
+    },
+    None => return Ok(None),
+  };
+  // libgit2 can't tell adds/deletes/renames of plain buffers apart
+  file_diff.status = match (old, new) {
+    (None, Some(_)) => FileDiffStatus::Added,
+    (Some(_), None) => FileDiffStatus::Deleted,
+    _ if old_path != new_path => FileDiffStatus::Renamed,
+    _ => FileDiffStatus::Modified,
+  };
+  file_diff.relative_path = match new {
+    Some(_) => new_path.to_string(),
+    None => old_path.to_string(),
+  };
+  file_diff.old_relative_path = match file_diff.status {
+    FileDiffStatus::Renamed => Some(old_path.to_string()),
+    _ => None,
+  };
+  Ok(Some(file_diff))
+}
+
+fn delta_to_file_diff(delta: &git2::DiffDelta, hunks: Vec<DiffHunk>) -> Option<FileDiff> {
+  let change = delta_to_file_change(delta)?;
+  Some(FileDiff {
+    relative_path: change.relative_path,
+    old_relative_path: change.old_relative_path,
+    status: change.status,
+    binary: delta.flags().is_binary(),
+    hunks,
+  })
+}
+
+fn delta_to_file_change(delta: &git2::DiffDelta) -> Option<FileChange> {
+  let status = match delta.status() {
+    Delta::Added | Delta::Untracked | Delta::Copied => FileDiffStatus::Added,
+    Delta::Deleted => FileDiffStatus::Deleted,
+    Delta::Renamed => FileDiffStatus::Renamed,
+    Delta::Modified | Delta::Typechange => FileDiffStatus::Modified,
+    _ => return None, // unmodified/ignored/unreadable/conflicted
+  };
+  let path_of = |file: git2::DiffFile| file.path().map(|p| p.to_string_lossy().to_string());
+  let relative_path = match status {
+    FileDiffStatus::Deleted => path_of(delta.old_file()),
+    _ => path_of(delta.new_file()),
+  }?;
+  let old_relative_path = match status {
+    FileDiffStatus::Renamed => path_of(delta.old_file()),
+    _ => None,
+  };
+  Some(FileChange {
+    relative_path,
+    old_relative_path,
+    status,
+  })
+}
+
+/// Inline word changes of the hunk `lines`
+fn diff_hunk_words(lines: &[DiffLine]) -> Vec<DiffSegment> {
+  let mut segments = vec![];
+  let mut index = 0;
+  while index < lines.len() {
+    if lines[index].kind == DiffChangeKind::Equal {
+      push_segment(
+        &mut segments,
+        DiffChangeKind::Equal,
+        &format!("{}\n", lines[index].content),
+      );
+      index += 1;
+      continue;
+    }
+    // diff each block of removed + added lines word by word
+    let (mut old_text, mut new_text) = (String::new(), String::new());
+    while index < lines.len() && lines[index].kind != DiffChangeKind::Equal {
+      let text = match lines[index].kind {
+        DiffChangeKind::Removed => &mut old_text,
+        _ => &mut new_text,
+      };
+      text.push_str(&lines[index].content);
+      text.push('\n');
+      index += 1;
+    }
+    for segment in diff_words(&old_text, &new_text) {
+      push_segment(&mut segments, segment.kind, &segment.text);
+    }
+  }
+  segments
+}
+
+/// # Diff Words
+///
+/// Diff `old` and `new` text word by word (words, whitespace runs and
+/// punctuation are compared as separate tokens).
+pub fn diff_words(old: &str, new: &str) -> Vec<DiffSegment> {
+  let old_tokens = tokenize_words(old);
+  let new_tokens = tokenize_words(new);
+  // skip the common prefix/suffix (cheap and keeps the LCS table small)
+  let prefix_len = old_tokens
+    .iter()
+    .zip(new_tokens.iter())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let suffix_len = old_tokens[prefix_len..]
+    .iter()
+    .rev()
+    .zip(new_tokens[prefix_len..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let old_changed = &old_tokens[prefix_len..old_tokens.len() - suffix_len];
+  let new_changed = &new_tokens[prefix_len..new_tokens.len() - suffix_len];
+
+  let mut segments = vec![];
+  push_segment(
+    &mut segments,
+    DiffChangeKind::Equal,
+    &old_tokens[..prefix_len].concat(),
+  );
+  if old_changed.len().saturating_mul(new_changed.len()) > MAX_WORD_DIFF_CELLS {
+    push_segment(
+      &mut segments,
+      DiffChangeKind::Removed,
+      &old_changed.concat(),
+    );
+    push_segment(&mut segments, DiffChangeKind::Added, &new_changed.concat());
+  } else {
+    for (kind, token) in lcs_diff(old_changed, new_changed) {
+      push_segment(&mut segments, kind, token);
+    }
+  }
+  push_segment(
+    &mut segments,
+    DiffChangeKind::Equal,
+    &old_tokens[old_tokens.len() - suffix_len..].concat(),
+  );
+  segments
+}
+
+/// Diff two token lists using their longest common subsequence
+fn lcs_diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(DiffChangeKind, &'a str)> {
+  let (n, m) = (old.len(), new.len());
+  // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+  let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+  for i in (0..n).rev() {
+    for j in (0..m).rev() {
+      lcs[i][j] = if old[i] == new[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        lcs[i + 1][j].max(lcs[i][j + 1])
+      };
+    }
+  }
+  let mut changes = Vec::with_capacity(n + m);
+  let (mut i, mut j) = (0, 0);
+  while i < n && j < m {
+    if old[i] == new[j] {
+      changes.push((DiffChangeKind::Equal, old[i]));
+      i += 1;
+      j += 1;
+    } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+      changes.push((DiffChangeKind::Removed, old[i]));
+      i += 1;
+    } else {
+      changes.push((DiffChangeKind::Added, new[j]));
+      j += 1;
+    }
+  }
+  changes.extend(old[i..].iter().map(|t| (DiffChangeKind::Removed, *t)));
+  changes.extend(new[j..].iter().map(|t| (DiffChangeKind::Added, *t)));
+  changes
+}
+
+/// Split text into words, whitespace runs and single punctuation chars
+fn tokenize_words(text: &str) -> Vec<&str> {
+  #[derive(PartialEq)]
+  enum CharClass {
+    Word,
+    Space,
+    Other,
+  }
+  let class_of = |c: char| {
+    if c.is_alphanumeric() || c == '_' {
+      CharClass::Word
+    } else if c.is_whitespace() {
+      CharClass::Space
+    } else {
+      CharClass::Other
+    }
+  };
+  let mut tokens = vec![];
+  let mut start = 0;
+  let mut previous_class = None;
+  for (index, c) in text.char_indices() {
+    let class = class_of(c);
+    let continues_token = match &previous_class {
+      Some(previous_class) => *previous_class == class && class != CharClass::Other,
+      None => true,
+    };
+    if !continues_token {
+      tokens.push(&text[start..index]);
+      start = index;
+    }
+    previous_class = Some(class);
+  }
+  if start < text.len() {
+    tokens.push(&text[start..]);
+  }
+  tokens
+}
+
+/// Append `text` to `segments` (merged into the last segment if of the same kind)
+fn push_segment(segments: &mut Vec<DiffSegment>, kind: DiffChangeKind, text: &str) {
+  if text.is_empty() {
+    return;
+  }
+  match segments.last_mut() {
+    Some(last) if last.kind == kind => last.text.push_str(text),
+    _ => segments.push(DiffSegment {
+      kind,
+      text: text.to_string(),
+    }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use DiffChangeKind::{Added, Equal, Removed};
+
+  fn segments(segments: &[DiffSegment]) -> Vec<(DiffChangeKind, &str)> {
+    segments
+      .iter()
+      .map(|segment| (segment.kind, segment.text.as_str()))
+      .collect()
+  }
+
+  #[test]
+  fn tokenizes_words() {
+    assert_eq!(
+      tokenize_words("Hello, world  foo_bar!?\n"),
+      vec!["Hello", ",", " ", "world", "  ", "foo_bar", "!", "?", "\n"]
+    );
+    assert!(tokenize_words("").is_empty());
+  }
+
+  #[test]
+  fn diffs_words() {
+    let cases: Vec<(&str, &str, Vec<(DiffChangeKind, &str)>)> = vec![
+      ("same text", "same text", vec![(Equal, "same text")]),
+      ("", "new text", vec![(Added, "new text")]),
+      ("old text", "", vec![(Removed, "old text")]),
+      (
+        "the quick fox",
+        "the slow fox",
+        vec![
+          (Equal, "the "),
+          (Removed, "quick"),
+          (Added, "slow"),
+          (Equal, " fox"),
+        ],
+      ),
+      (
+        "one two three",
+        "one three four",
+        vec![
+          (Equal, "one "),
+          (Removed, "two "),
+          (Equal, "three"),
+          (Added, " four"),
+        ],
+      ),
+      (
+        "Hello, world.",
+        "Hello world!",
+        vec![
+          (Equal, "Hello"),
+          (Removed, ","),
+          (Equal, " world"),
+          (Removed, "."),
+          (Added, "!"),
+        ],
+      ),
+    ];
+    for (old, new, expected) in cases {
+      assert_eq!(
+        segments(&diff_words(old, new)),
+        expected,
+        "{:?} -> {:?}",
+        old,
+        new
+      );
+    }
+  }
+
+  #[test]
+  fn diffs_contents_word_by_word() {
+    let old = "# Title\n\nThe quick fox.\nEnd\n";
+    let new = "# Title\n\nThe slow fox.\nEnd\n";
+    let file_diff = diff_contents(Some(("a.md", old)), Some(("a.md", new)), DiffMode::Word)
+      .unwrap()
+      .unwrap();
+    assert_eq!(file_diff.relative_path, "a.md");
+    assert_eq!(file_diff.old_relative_path, None);
+    assert_eq!(file_diff.status, FileDiffStatus::Modified);
+    assert_eq!(file_diff.hunks.len(), 1);
+    let hunk = &file_diff.hunks[0];
+    assert_eq!(hunk.header, "@@ -1,4 +1,4 @@");
+    let lines = hunk
+      .lines
+      .iter()
+      .map(|line| {
+        (
+          line.kind,
+          line.old_line_number,
+          line.new_line_number,
+          line.content.as_str(),
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      lines,
+      vec![
+        (Equal, Some(1), Some(1), "# Title"),
+        (Equal, Some(2), Some(2), ""),
+        (Removed, Some(3), None, "The quick fox."),
+        (Added, None, Some(3), "The slow fox."),
+        (Equal, Some(4), Some(4), "End"),
+      ]
+    );
+    assert_eq!(
+      segments(hunk.words.as_ref().unwrap()),
+      vec![
+        (Equal, "# Title\n\nThe "),
+        (Removed, "quick"),
+        (Added, "slow"),
+        (Equal, " fox.\nEnd\n"),
+      ]
+    );
+
+    let file_diff = diff_contents(Some(("a.md", old)), Some(("a.md", new)), DiffMode::Line)
+      .unwrap()
+      .unwrap();
+    assert!(file_diff.hunks[0].words.is_none());
+  }
+
+  #[test]
+  fn diffs_added_deleted_and_renamed_contents() {
+    let added = diff_contents(None, Some(("a.md", "# A\n")), DiffMode::Line)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      (added.status, added.relative_path.as_str()),
+      (FileDiffStatus::Added, "a.md")
+    );
+    assert_eq!(added.hunks[0].lines[0].kind, Added);
+
+    let deleted = diff_contents(Some(("a.md", "# A\n")), None, DiffMode::Line)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      (deleted.status, deleted.relative_path.as_str()),
+      (FileDiffStatus::Deleted, "a.md")
+    );
+    assert_eq!(deleted.hunks[0].lines[0].kind, Removed);
+
+    // renamed without changes
+    let renamed = diff_contents(
+      Some(("a.md", "# A\n")),
+      Some(("b.md", "# A\n")),
+      DiffMode::Line,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(renamed.status, FileDiffStatus::Renamed);
+    assert_eq!(renamed.relative_path, "b.md");
+    assert_eq!(renamed.old_relative_path.as_deref(), Some("a.md"));
+    assert!(renamed.hunks.is_empty());
+
+    assert!(diff_contents(
+      Some(("a.md", "# A\n")),
+      Some(("a.md", "# A\n")),
+      DiffMode::Word
+    )
+    .unwrap()
+    .is_none());
+  }
+}
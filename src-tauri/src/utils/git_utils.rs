@@ -1,32 +1,169 @@
-use std::path::{Path, PathBuf};
+use std::{
+  cell::RefCell,
+  path::{Path, PathBuf},
+  rc::Rc,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+};
 
-use anyhow::{anyhow, Context, Result};
-use git2::{BranchType, Cred, Direction, IndexAddOption, PushOptions, RemoteCallbacks, Repository};
-use log::{debug, error, info, warn};
+use anyhow::{anyhow, Result};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use derive_more::{Display, Error};
+use git2::{
+  build::CheckoutBuilder, BranchType, Delta, DiffFindOptions, DiffOptions, Direction, ErrorCode,
+  Index, IndexAddOption, IndexEntry, Oid, PushOptions, RemoteCallbacks, Repository,
+  RepositoryState, Sort, Tree,
+};
+use log::{debug, error, info};
+
+use crate::{
+  models::{
+    diff::{DiffMode, FileChange, FileDiff},
+    document_revision::{DocumentRevision, RevisionChange},
+    merge_conflict::{ConflictResolution, MergeConflict},
+    sync_preview::SyncPreview,
+    sync_progress::{SyncPhase, SyncProgress},
+  },
+  utils::{
+    fsutils, git_credentials::GitCredentialProvider, git_diff,
+    sync_progress_reporter::SyncProgressReporter,
+  },
+};
+
+/// # Merge Status
+///
+/// Outcome of merging fetched changes into the local branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStatus {
+  /// Nothing to merge.
+  UpToDate,
+  /// Local branch was fast-forwarded to the remote.
+  FastForward,
+  /// A merge commit was created.
+  Merged,
+  /// Merge stopped with conflicts. The repository is left
+  /// in merge state until all conflicts are resolved.
+  Conflicted,
+}
+
+/// # Push Status
+///
+/// Outcome of pushing the local branch to the remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushStatus {
+  /// Local commits were pushed.
+  Pushed,
+  /// The remote already has all local commits, nothing was pushed.
+  UpToDate,
+  /// The remote rejected the update (with the reason), eg. because another
+  /// device pushed meanwhile. Pull the new remote commits, then retry.
+  Rejected(String),
+}
+
+/// # Sync Cancelled
+///
+/// Returned when a fetch/pull/push is aborted through the cancel flag
+/// (see `GitUtils::with_cancel_flag`).
+#[derive(Debug, Display, Error)]
+#[display(fmt = "Sync cancelled!")]
+pub struct SyncCancelled;
+
+/// Name of the remote used when none is configured
+pub const DEFAULT_REMOTE_NAME: &str = "origin";
+/// Name of the initial branch created when none is configured
+pub const DEFAULT_BRANCH_NAME: &str = "main";
 
 /// # Utilities for interacting with git
 /// Wrapper on top of `git2` library
 pub struct GitUtils {
   repository: Repository,
+  /// Name of the remote synced with (upstream of the current branch)
+  remote_name: String,
+  /// Credentials used to auth with the remote
+  credentials: GitCredentialProvider,
+  /// Receives the fetch/checkout/push progress (if set)
+  progress: Option<SyncProgressReporter>,
+  /// Set to `true` to abort the running fetch/pull/push (if set)
+  cancel_flag: Option<Arc<AtomicBool>>,
 }
 
 impl GitUtils {
   /// # Create a new GitUtils instance
   ///
-  /// - Inits a new git repo.
-  /// - Sets the repository remote.
+  /// - Inits a new git repo (or opens the existing one).
+  /// - Sets the repository remote `remote_name`.
+  /// - On a repo without commits, explicitly creates `branch_name` as the
+  /// initial branch (instead of relying on the git default).
+  /// - Sets `remote_name` as the upstream of the branch.
   pub fn new(
     remote_url: &str,
     repo_path: &Path,
+    remote_name: &str,
+    branch_name: &str,
     config_user_name: &str,
     config_user_email: &str,
+    credentials: GitCredentialProvider,
   ) -> Result<Self> {
     let repository = Repository::init(repo_path)?;
-    repository.remote("origin", remote_url)?;
+    match repository.find_remote(remote_name) {
+      Ok(_) => repository.remote_set_url(remote_name, remote_url)?,
+      Err(_) => {
+        repository.remote(remote_name, remote_url)?;
+      }
+    }
     let mut config = repository.config()?;
     config.set_str("user.name", config_user_name)?;
     config.set_str("user.email", config_user_email)?;
-    Ok(Self { repository })
+    let git_utils = Self {
+      repository,
+      remote_name: remote_name.to_string(),
+      credentials,
+      progress: None,
+      cancel_flag: None,
+    };
+    if git_utils.repository.head().is_err() {
+      // no commits yet: HEAD can point to the (unborn) branch
+      git_utils.set_head_branch(branch_name)?;
+    }
+    git_utils.set_upstream(&git_utils.get_branch_name()?)?;
+    Ok(git_utils)
+  }
+
+  /// # Set Remote Url
+  ///
+  /// Point the repository remote to the given `remote_url`.
+  pub fn set_remote_url(&self, remote_url: &str) -> Result<()> {
+    self
+      .repository
+      .remote_set_url(&self.remote_name, remote_url)?;
+    Ok(())
+  }
+
+  /// # Remove Remote
+  ///
+  /// Remove the repository remote (the local history is kept).
+  pub fn remove_remote(&self) -> Result<()> {
+    self.repository.remote_delete(&self.remote_name)?;
+    Ok(())
+  }
+
+  /// # Get Remote Name
+  ///
+  /// Name of the remote synced with.
+  pub fn get_remote_name(&self) -> &str {
+    &self.remote_name
+  }
+
+  /// # Set User Config
+  ///
+  /// Set the commit author `user.name` and `user.email` in the repo config.
+  pub fn set_user_config(&self, config_user_name: &str, config_user_email: &str) -> Result<()> {
+    let mut config = self.repository.config()?;
+    config.set_str("user.name", config_user_name)?;
+    config.set_str("user.email", config_user_email)?;
+    Ok(())
   }
 
   /// # Get Ref Spec String
@@ -40,40 +177,174 @@ impl GitUtils {
   ///
   /// - Loads an existing instance of the git repository at
   /// the `repo_path` and returns a GitUtils instance based on this.
-  pub fn load(repo_path: &Path) -> Result<Self> {
+  /// - The remote is the upstream of the current branch (`DEFAULT_REMOTE_NAME` if not set).
+  pub fn load(repo_path: &Path, credentials: GitCredentialProvider) -> Result<Self> {
     let repository = Repository::open(repo_path)?;
-    Ok(Self { repository })
+    let remote_name = Self::head_branch_name(&repository)
+      .ok()
+      .and_then(|branch_name| {
+        repository
+          .config()
+          .ok()?
+          .get_string(&format!("branch.{}.remote", branch_name))
+          .ok()
+      })
+      .unwrap_or_else(|| DEFAULT_REMOTE_NAME.to_string());
+    Ok(Self {
+      repository,
+      remote_name,
+      credentials,
+      progress: None,
+      cancel_flag: None,
+    })
+  }
+
+  /// # With Progress
+  ///
+  /// Report the fetch/checkout/push progress to `reporter`.
+  pub fn with_progress(mut self, reporter: SyncProgressReporter) -> Self {
+    self.progress = Some(reporter);
+    self
+  }
+
+  /// # With Cancel Flag
+  ///
+  /// Abort fetches/pulls/pushes with `SyncCancelled` once `cancel_flag` is set.
+  /// - Transfers are aborted from the progress callbacks, other steps
+  /// check the flag before starting.
+  pub fn with_cancel_flag(mut self, cancel_flag: Arc<AtomicBool>) -> Self {
+    self.cancel_flag = Some(cancel_flag);
+    self
+  }
+
+  fn is_cancelled(&self) -> bool {
+    match &self.cancel_flag {
+      Some(cancel_flag) => cancel_flag.load(Ordering::SeqCst),
+      None => false,
+    }
+  }
+
+  /// # Check Cancelled
+  ///
+  /// Returns `SyncCancelled` if the cancel flag is set.
+  pub fn check_cancelled(&self) -> Result<()> {
+    if self.is_cancelled() {
+      return Err(SyncCancelled.into());
+    }
+    Ok(())
+  }
+
+  fn report_progress(&self, progress: SyncProgress) {
+    if let Some(reporter) = &self.progress {
+      reporter.report(progress);
+    }
+  }
+
+  /// Checkout options reporting the checkout progress
+  fn checkout_builder(&self) -> CheckoutBuilder<'_> {
+    let mut checkout_builder = CheckoutBuilder::new();
+    checkout_builder.progress(move |_, current, total| {
+      self.report_progress(SyncProgress::new(SyncPhase::Checkout, current, total));
+    });
+    checkout_builder
   }
 
   /// # Get the branch name from repo
   ///
-  /// - Finds the default branch: `main` or `master` in the repository.
-  pub fn get_default_branch_name(&self) -> Result<String> {
-    match self.repository.find_branch("master", BranchType::Local) {
-      Ok(branch) => {
-        let branch_name = branch.name()?.ok_or(anyhow!("branch invalid!"))?;
-        Ok(branch_name.to_string())
-      }
-      Err(e) => {
-        let branch = self.repository.find_branch("main", BranchType::Local)?;
-        let branch_name = branch.name()?.ok_or(anyhow!("branch invalid!"))?;
-        Ok(branch_name.to_string())
+  /// - The branch `HEAD` points to (ie. the sync branch).
+  /// - Also works on a fresh repo without commits.
+  pub fn get_branch_name(&self) -> Result<String> {
+    Self::head_branch_name(&self.repository)
+  }
+
+  fn head_branch_name(repository: &Repository) -> Result<String> {
+    let head = repository.find_reference("HEAD")?;
+    let target = head
+      .symbolic_target()
+      .ok_or_else(|| anyhow!("HEAD is not on a branch!"))?;
+    Ok(
+      target
+        .strip_prefix("refs/heads/")
+        .ok_or_else(|| anyhow!("HEAD is not on a branch!"))?
+        .to_string(),
+    )
+  }
+
+  /// Point `HEAD` to the branch `branch_name` (may not exist yet)
+  fn set_head_branch(&self, branch_name: &str) -> Result<()> {
+    let refname = format!("refs/heads/{}", branch_name);
+    if !git2::Reference::is_valid_name(&refname) {
+      return Err(anyhow!("invalid branch name: `{}`!", branch_name));
+    }
+    self.repository.set_head(&refname)?;
+    Ok(())
+  }
+
+  /// Set the remote as the upstream of the branch `branch_name`
+  fn set_upstream(&self, branch_name: &str) -> Result<()> {
+    let mut config = self.repository.config()?;
+    config.set_str(&format!("branch.{}.remote", branch_name), &self.remote_name)?;
+    config.set_str(
+      &format!("branch.{}.merge", branch_name),
+      &format!("refs/heads/{}", branch_name),
+    )?;
+    Ok(())
+  }
+
+  /// Name of the fetched remote branch of the current branch
+  fn remote_tracking_ref(&self) -> Result<String> {
+    Ok(format!(
+      "refs/remotes/{}/{}",
+      self.remote_name,
+      self.get_branch_name()?
+    ))
+  }
+
+  /// # Switch Branch
+  ///
+  /// Make `branch_name` the sync branch and check it out.
+  /// - A missing branch is created from the fetched remote branch if there
+  /// is one, else from the current commit.
+  /// - Local changes should be committed first, the working tree is overwritten.
+  pub fn switch_branch(&self, branch_name: &str) -> Result<()> {
+    if self
+      .repository
+      .find_branch(branch_name, BranchType::Local)
+      .is_err()
+    {
+      let remote_branch_ref = format!("refs/remotes/{}/{}", self.remote_name, branch_name);
+      let start_commit = match self.repository.find_reference(&remote_branch_ref) {
+        Ok(reference) => Some(reference.peel_to_commit()?),
+        Err(_) => match self.repository.head() {
+          Ok(head) => Some(head.peel_to_commit()?),
+          Err(_) => None, // no commits yet, branch stays unborn
+        },
+      };
+      if let Some(start_commit) = start_commit {
+        self.repository.branch(branch_name, &start_commit, false)?;
       }
     }
+    self.set_head_branch(branch_name)?;
+    if self.repository.head().is_ok() {
+      self
+        .repository
+        .checkout_head(Some(self.checkout_builder().force()))?;
+    }
+    self.set_upstream(branch_name)?;
+    Ok(())
   }
 
   /// # Clone a repo
   ///
   /// Clones a repository given the `remote_url` to the specified `path`
-  pub fn clone(remote_url: String, clone_path: &Path) -> Result<Repository> {
-    // Prepare callbacks.
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(|_url, username_from_url, _allowed_types| {
-      Cred::ssh_key_from_agent(username_from_url.unwrap())
-    });
+  pub fn clone(
+    remote_url: String,
+    clone_path: &Path,
+    credentials: &GitCredentialProvider,
+  ) -> Result<Repository> {
     // Prepare fetch options.
     let mut fo = git2::FetchOptions::new();
-    fo.remote_callbacks(callbacks);
+    fo.remote_callbacks(credentials.create_callbacks());
     // Prepare builder.
     let mut builder = git2::build::RepoBuilder::new();
     builder.fetch_options(fo);
@@ -85,23 +356,55 @@ impl GitUtils {
   /// # Add files to track
   ///
   /// Add all files to track under the specified `dirs` list.
+  /// - Temp files left behind by interrupted writes are skipped.
   pub fn add(&self, dirs: Vec<&Path>) -> Result<()> {
     let mut index = self.repository.index()?;
-    index.add_all(dirs.iter(), IndexAddOption::DEFAULT, None)?;
+    Self::stage(&mut index, &dirs)?;
     index.write()?;
     Ok(())
   }
 
+  /// Add all files under `dirs` to the (in-memory) `index`, skipping temp files
+  /// - Tracked files removed from the working dir are removed from the index.
+  fn stage(index: &mut Index, dirs: &[&Path]) -> Result<()> {
+    let mut skip_temp_files = |path: &Path, _: &[u8]| -> i32 {
+      if fsutils::is_temp_file(path) {
+        1 // skip
+      } else {
+        0 // add
+      }
+    };
+    index.add_all(
+      dirs.iter(),
+      IndexAddOption::DEFAULT,
+      Some(&mut skip_temp_files),
+    )?;
+    // make sure the deleted tracked files are removed from the index too
+    index.update_all(dirs.iter(), None)?;
+    Ok(())
+  }
+
   /// # Add + Commit to repo
   ///
   /// Create a commit adding and tracking the files to the repo.
   /// - `dirs` path to add files to commit
+  /// - `message` generates the commit message from the staged changes
+  /// (paths relative to repo root).
+  /// - No (empty) commit is created if nothing changed, returns `false` then.
   ///
   /// ## References
   ///
   /// - https://github.com/rust-lang/git2-rs/issues/507
-  pub fn add_commit(&self, dirs: Vec<&Path>, message: &str) -> Result<()> {
+  pub fn add_commit<F>(&self, dirs: Vec<&Path>, message: F) -> Result<bool>
+  where
+    F: FnOnce(&[FileChange]) -> String,
+  {
     self.add(dirs)?; // add files to commit on the specified dir_path
+    let changes = self.staged_changes()?;
+    if changes.is_empty() {
+      debug!("nothing to commit");
+      return Ok(false);
+    }
     let signature = self.repository.signature()?;
     let tree_id = self.repository.index()?.write_tree()?;
     let mut parents = Vec::new();
@@ -113,53 +416,131 @@ impl GitUtils {
       Some("HEAD"),
       &signature,
       &signature,
-      message,
+      &message(&changes),
       &self.repository.find_tree(tree_id)?,
       &parents,
     )?;
-    Ok(())
+    Ok(true)
   }
 
-  /// # Create Callbacks for Git SSH Auth
-  pub fn create_callbacks<'a>() -> RemoteCallbacks<'a> {
-    // Prepare callbacks.
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(|_url, username_from_url, _allowed_types| {
-      debug!("username_from_url: {:?}", username_from_url);
-      Cred::ssh_key_from_agent(username_from_url.unwrap())
-    });
-    callbacks
+  /// # Staged Changes
+  ///
+  /// Get the files changed in the index compared to HEAD (renames detected).
+  pub fn staged_changes(&self) -> Result<Vec<FileChange>> {
+    let head_tree = match self.repository.head() {
+      Ok(head) => Some(head.peel_to_tree()?),
+      Err(_) => None, // no commits yet, everything staged is new
+    };
+    let mut diff = self
+      .repository
+      .diff_tree_to_index(head_tree.as_ref(), None, None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    Ok(git_diff::diff_to_file_changes(&diff))
+  }
+
+  /// # Create Callbacks for Git Auth
+  ///
+  /// Uses the configured `GitCredentialProvider`.
+  pub fn create_callbacks(&self) -> RemoteCallbacks<'_> {
+    self.credentials.create_callbacks()
   }
 
   /// # Push changes to remote
   ///
   /// Push committed changes to remote repository.
+  /// - Returns `PushStatus::UpToDate` without pushing if the last fetched
+  /// remote branch already has the local commits.
+  /// - Rejected ref updates are returned as `PushStatus::Rejected`, other failures as errors.
   ///
   /// ## References
   ///
   /// - https://stackoverflow.com/questions/58201849/request-failed-with-status-code-401-error-when-trying-to-push-to-remote-using
-  pub fn push(&self) -> Result<()> {
-    let mut remote = self.repository.find_remote("origin")?;
-    let mut callbacks = Self::create_callbacks();
-    let mut is_error = false; // for checking if any error is present
+  pub fn push(&self) -> Result<PushStatus> {
+    self.check_cancelled()?;
+    let head_id = match self.repository.head() {
+      Ok(head) => head.peel_to_commit()?.id(),
+      Err(_) => {
+        debug!("no commits to push");
+        return Ok(PushStatus::UpToDate);
+      }
+    };
+    let remote_branch_id = self
+      .repository
+      .find_reference(&self.remote_tracking_ref()?)
+      .ok()
+      .and_then(|reference| reference.target());
+    if remote_branch_id == Some(head_id) {
+      debug!("remote is up to date");
+      return Ok(PushStatus::UpToDate);
+    }
+    let mut remote = self.repository.find_remote(&self.remote_name)?;
+    let mut callbacks = self.create_callbacks();
+    callbacks.pack_progress(|_, current, total| {
+      self.report_progress(SyncProgress::new(SyncPhase::Packing, current, total));
+    });
+    // the pack upload itself can't be aborted, only the remote messages
+    callbacks.sideband_progress(|_| !self.is_cancelled());
+    callbacks.push_transfer_progress(|current, total, bytes| {
+      self.report_progress(SyncProgress {
+        total_objects: total,
+        bytes,
+        ..SyncProgress::new(SyncPhase::Sending, current, total)
+      });
+    });
+    // reason the remote rejected the ref update with (if rejected)
+    let rejection = Rc::new(RefCell::new(None));
     let mut push_options = PushOptions::default();
-    callbacks.push_update_reference(move |reference, error| {
-      // check for errors in update refs
-      if error.is_some() {
-        error!("ref = {}, error = {:?}", reference, error);
-        is_error = is_error || error.is_some();
+    let callback_rejection = Rc::clone(&rejection);
+    callbacks.push_update_reference(move |reference, status| {
+      if let Some(status) = status {
+        error!("ref = {}, rejected: {}", reference, status);
+        *callback_rejection.borrow_mut() = Some(format!("{}: {}", reference, status));
       }
       Ok(())
     });
-    remote.connect_auth(Direction::Push, Some(Self::create_callbacks()), None)?;
-    let branch_name = self.get_default_branch_name()?;
+    remote.connect_auth(Direction::Push, Some(self.create_callbacks()), None)?;
+    let branch_name = self.get_branch_name()?;
     let ref_spec = Self::get_ref_specs(&branch_name);
-    self.repository.remote_add_push("origin", &ref_spec)?;
+    self.set_push_ref_spec(&ref_spec)?;
     push_options.remote_callbacks(callbacks);
-    remote.push(&[&ref_spec], Some(&mut push_options))?;
-    if is_error {
-      return Err(anyhow!("push_update_reference() has reported errors!"));
+    self.check_cancelled()?;
+    if let Err(e) = remote.push(&[&ref_spec], Some(&mut push_options)) {
+      if self.is_cancelled() {
+        return Err(SyncCancelled.into());
+      }
+      return match e.code() {
+        // detected before sending (eg. local remotes)
+        ErrorCode::NotFastForward => Ok(PushStatus::Rejected(e.message().to_string())),
+        _ => Err(e.into()),
+      };
+    }
+    let rejection = rejection.borrow_mut().take();
+    match rejection {
+      Some(reason) => Ok(PushStatus::Rejected(reason)),
+      None => Ok(PushStatus::Pushed),
+    }
+  }
+
+  /// # Set Push Ref Spec
+  ///
+  /// Make `ref_spec` the only push refspec of the remote.
+  /// - Idempotent, also cleans up duplicated refspecs (previously one was added per push).
+  fn set_push_ref_spec(&self, ref_spec: &str) -> Result<()> {
+    let remote = self.repository.find_remote(&self.remote_name)?;
+    let push_ref_specs = remote.push_refspecs()?;
+    let push_ref_specs = push_ref_specs.iter().flatten().collect::<Vec<_>>();
+    if push_ref_specs == [ref_spec] {
+      return Ok(());
+    }
+    if !push_ref_specs.is_empty() {
+      self
+        .repository
+        .config()?
+        .remove_multivar(&format!("remote.{}.push", self.remote_name), ".*")?;
     }
+    self
+      .repository
+      .remote_add_push(&self.remote_name, ref_spec)?;
     Ok(())
   }
 
@@ -168,6 +549,9 @@ impl GitUtils {
   /// Downloads data from remote repo and updates existing files.
   /// - `refs`: Update references
   /// - `remote`: Remote to fetch from
+  /// - Returns the fetched commit of the current branch, `None` if the branch
+  /// is not on the remote yet (eg. an empty remote repository).
+  /// - Fetch errors (network, auth, ...) are returned.
   ///
   /// ## Reference
   ///
@@ -176,8 +560,8 @@ impl GitUtils {
   fn do_fetch<'a>(
     &'a self,
     remote: &'a mut git2::Remote,
-  ) -> Result<git2::AnnotatedCommit<'a>, git2::Error> {
-    let mut cb = Self::create_callbacks();
+  ) -> Result<Option<git2::AnnotatedCommit<'a>>> {
+    let mut cb = self.create_callbacks();
     // Print out our transfer progress.
     cb.transfer_progress(|stats| {
       if stats.received_objects() == stats.total_objects() {
@@ -195,7 +579,29 @@ impl GitUtils {
           stats.received_bytes()
         );
       }
-      true
+      let (phase, current, total) = if stats.received_objects() == stats.total_objects() {
+        (
+          SyncPhase::ResolvingDeltas,
+          stats.indexed_deltas(),
+          stats.total_deltas(),
+        )
+      } else {
+        (
+          SyncPhase::Receiving,
+          stats.received_objects(),
+          stats.total_objects(),
+        )
+      };
+      self.report_progress(SyncProgress {
+        received_objects: stats.received_objects(),
+        total_objects: stats.total_objects(),
+        indexed_deltas: stats.indexed_deltas(),
+        total_deltas: stats.total_deltas(),
+        bytes: stats.received_bytes(),
+        ..SyncProgress::new(phase, current, total)
+      });
+      // returning `false` aborts the transfer
+      !self.is_cancelled()
     });
     let mut fo = git2::FetchOptions::new();
     fo.remote_callbacks(cb);
@@ -204,7 +610,12 @@ impl GitUtils {
     fo.download_tags(git2::AutotagOption::All);
     debug!("Fetching {} for repo", remote.name().unwrap());
     // Pass empty refspecs to use from base refs: https://libgit2.org/libgit2/#HEAD/group/remote/git_remote_fetch
-    remote.fetch(&[] as &[&str], Some(&mut fo), None)?;
+    if let Err(e) = remote.fetch(&[] as &[&str], Some(&mut fo), None) {
+      if self.is_cancelled() {
+        return Err(SyncCancelled.into());
+      }
+      return Err(e.into());
+    }
     // If there are local objects (we got a thin pack), then tell the user
     // how many objects we saved from having to cross the network.
     let stats = remote.stats();
@@ -225,8 +636,17 @@ impl GitUtils {
         stats.received_bytes()
       );
     }
-    let fetch_head = self.repository.find_reference("FETCH_HEAD")?;
-    Ok(self.repository.reference_to_annotated_commit(&fetch_head)?)
+    // fetched commit of the current branch (missing if not on the remote yet)
+    let remote_branch = match self.repository.find_reference(&self.remote_tracking_ref()?) {
+      Ok(remote_branch) => remote_branch,
+      Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+      Err(e) => return Err(e.into()),
+    };
+    Ok(Some(
+      self
+        .repository
+        .reference_to_annotated_commit(&remote_branch)?,
+    ))
   }
 
   /// # Merge: fast_forward
@@ -249,7 +669,8 @@ impl GitUtils {
     lb.set_target(rc.id(), &msg)?;
     self.repository.set_head(&name)?;
     self.repository.checkout_head(Some(
-      git2::build::CheckoutBuilder::default()
+      self
+        .checkout_builder()
         // For some reason the force is required to make the working directory actually get updated
         // I suspect we should be adding some logic to handle dirty working directory states
         // but this is just an example so maybe not.
@@ -260,39 +681,46 @@ impl GitUtils {
 
   /// # Merge: normal
   ///
+  /// Merges `remote` into the working tree + index (also records `MERGE_HEAD`).
+  /// - Creates the merge commit if there are no conflicts.
+  /// - Otherwise stops with `MergeStatus::Conflicted`, conflicted documents keep
+  /// the local version in the working tree until resolved.
+  ///
   /// ## Reference
   ///
   /// - https://github.com/rust-lang/git2-rs/blob/master/examples/pull.rs
   ///
-  fn normal_merge(
-    &self,
-    local: &git2::AnnotatedCommit,
-    remote: &git2::AnnotatedCommit,
-  ) -> Result<(), git2::Error> {
-    let local_tree = self.repository.find_commit(local.id())?.tree()?;
-    let remote_tree = self.repository.find_commit(remote.id())?.tree()?;
-    let ancestor = self
-      .repository
-      .find_commit(self.repository.merge_base(local.id(), remote.id())?)?
-      .tree()?;
-    let mut idx = self
-      .repository
-      .merge_trees(&ancestor, &local_tree, &remote_tree, None)?;
-    if idx.has_conflicts() {
+  fn normal_merge(&self, remote: &git2::AnnotatedCommit) -> Result<MergeStatus, git2::Error> {
+    self.repository.merge(
+      &[remote],
+      None,
+      Some(self.checkout_builder().allow_conflicts(true).use_ours(true)),
+    )?;
+    if self.repository.index()?.has_conflicts() {
       debug!("Merge conficts detected...");
-      self.repository.checkout_index(Some(&mut idx), None)?;
-      return Ok(());
+      return Ok(MergeStatus::Conflicted);
     }
+    self.commit_merge()?;
+    Ok(MergeStatus::Merged)
+  }
+
+  /// # Commit Merge
+  ///
+  /// Create the merge commit from the current index using `HEAD` and
+  /// `MERGE_HEAD` as parents, then clear the merge state.
+  fn commit_merge(&self) -> Result<(), git2::Error> {
+    let local_commit = self.repository.head()?.peel_to_commit()?;
+    let remote_commit = self
+      .repository
+      .find_reference("MERGE_HEAD")?
+      .peel_to_commit()?;
     let result_tree = self
       .repository
-      .find_tree(idx.write_tree_to(&self.repository)?)?;
-    // now create the merge commit
-    let msg = format!("Merge: {} into {}", remote.id(), local.id());
+      .find_tree(self.repository.index()?.write_tree()?)?;
+    let msg = format!("Merge: {} into {}", remote_commit.id(), local_commit.id());
     let sig = self.repository.signature()?;
-    let local_commit = self.repository.find_commit(local.id())?;
-    let remote_commit = self.repository.find_commit(remote.id())?;
     // Do our merge commit and set current branch head to that commit.
-    let _merge_commit = self.repository.commit(
+    self.repository.commit(
       Some("HEAD"),
       &sig,
       &sig,
@@ -300,8 +728,412 @@ impl GitUtils {
       &result_tree,
       &[&local_commit, &remote_commit],
     )?;
-    // Set working tree to match head.
-    self.repository.checkout_head(None)?;
+    self.repository.cleanup_state()?;
+    Ok(())
+  }
+
+  /// # File History
+  ///
+  /// Get the commits (newest first, at most `limit`) that changed the file
+  /// at `path` (relative to repo root).
+  /// - Renames are followed: older revisions have the path the file had in that commit.
+  /// - Merge commits are only listed if the file differs from all parents.
+  pub fn file_history(&self, path: &Path, limit: usize) -> Result<Vec<DocumentRevision>> {
+    let mut revwalk = self.repository.revwalk()?;
+    if let Err(e) = revwalk.push_head() {
+      debug!("no history (empty repository?): {}", e);
+      return Ok(vec![]);
+    }
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    let mut current_path = path.to_path_buf();
+    let mut revisions = vec![];
+    for oid in revwalk {
+      if revisions.len() >= limit {
+        break;
+      }
+      let commit = self.repository.find_commit(oid?)?;
+      let entry_id = Self::tree_entry_id(&commit.tree()?, &current_path);
+      let parent_trees = commit
+        .parents()
+        .map(|parent| parent.tree())
+        .collect::<Result<Vec<Tree>, git2::Error>>()?;
+      if entry_id.is_none() && parent_trees.is_empty() {
+        continue; // file did not exist yet
+      }
+      if parent_trees
+        .iter()
+        .any(|tree| Self::tree_entry_id(tree, &current_path) == entry_id)
+      {
+        continue; // unchanged compared to a parent
+      }
+      let first_parent_tree = parent_trees.first();
+      let mut previous_path = None;
+      let change = match entry_id {
+        None => RevisionChange::Deleted,
+        Some(_)
+          if first_parent_tree.map_or(false, |tree| {
+            Self::tree_entry_id(tree, &current_path).is_some()
+          }) =>
+        {
+          RevisionChange::Modified
+        }
+        Some(_) => {
+          previous_path =
+            self.find_rename_source(first_parent_tree, &commit.tree()?, &current_path)?;
+          if previous_path.is_some() {
+            RevisionChange::Renamed
+          } else {
+            RevisionChange::Added
+          }
+        }
+      };
+      let author = commit.author();
+      revisions.push(DocumentRevision {
+        commit_id: commit.id().to_string(),
+        relative_path: current_path.to_string_lossy().to_string(),
+        previous_relative_path: previous_path
+          .as_ref()
+          .map(|p| p.to_string_lossy().to_string()),
+        change,
+        author_name: author.name().unwrap_or_default().to_string(),
+        author_email: author.email().unwrap_or_default().to_string(),
+        time: Utc
+          .timestamp_opt(commit.time().seconds(), 0)
+          .single()
+          .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+          .unwrap_or_default(),
+        message: commit.message().unwrap_or_default().trim_end().to_string(),
+      });
+      if let Some(previous_path) = previous_path {
+        current_path = previous_path; // follow the rename
+      }
+    }
+    Ok(revisions)
+  }
+
+  /// # File At Revision
+  ///
+  /// Get the content of the file at `path` (relative to repo root) in the
+  /// commit `revision` (full/short commit id or any other revspec).
+  pub fn file_at_revision(&self, path: &Path, revision: &str) -> Result<String> {
+    let commit = self
+      .repository
+      .revparse_single(revision)?
+      .peel_to_commit()?;
+    let entry = commit
+      .tree()?
+      .get_path(path)
+      .map_err(|_| anyhow!("{} not found in revision {}", path.display(), revision))?;
+    let blob = self.repository.find_blob(entry.id())?;
+    Ok(String::from_utf8_lossy(blob.content()).to_string())
+  }
+
+  /// # Diff Workdir To Head
+  ///
+  /// Get the uncommitted changes (staged, unstaged and untracked) of the
+  /// files under `pathspec` (relative to repo root) compared to HEAD.
+  pub fn diff_workdir_to_head(&self, pathspec: &Path, mode: DiffMode) -> Result<Vec<FileDiff>> {
+    let head_tree = match self.repository.head() {
+      Ok(head) => Some(head.peel_to_tree()?),
+      Err(_) => None, // no commits yet, everything is new
+    };
+    let mut diff_options = DiffOptions::new();
+    diff_options
+      .pathspec(pathspec)
+      .include_untracked(true)
+      .recurse_untracked_dirs(true)
+      .show_untracked_content(true);
+    let mut diff = self
+      .repository
+      .diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut diff_options))?;
+    diff.find_similar(Some(
+      DiffFindOptions::new().renames(true).for_untracked(true),
+    ))?;
+    git_diff::diff_to_file_diffs(&diff, mode)
+  }
+
+  /// # Diff Head To Remote
+  ///
+  /// Get the changes of the files under `pathspec` (relative to repo root)
+  /// between HEAD and the last fetched remote branch (see `fetch`).
+  pub fn diff_head_to_remote(&self, pathspec: &Path, mode: DiffMode) -> Result<Vec<FileDiff>> {
+    let head_tree = match self.repository.head() {
+      Ok(head) => Some(head.peel_to_tree()?),
+      Err(_) => None,
+    };
+    let remote_tree = self
+      .repository
+      .find_reference(&self.remote_tracking_ref()?)
+      .map_err(|_| anyhow!("no fetched remote changes, fetch first!"))?
+      .peel_to_tree()?;
+    let mut diff_options = DiffOptions::new();
+    diff_options.pathspec(pathspec);
+    let mut diff = self.repository.diff_tree_to_tree(
+      head_tree.as_ref(),
+      Some(&remote_tree),
+      Some(&mut diff_options),
+    )?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    git_diff::diff_to_file_diffs(&diff, mode)
+  }
+
+  /// # Fetch
+  ///
+  /// Download the remote changes without merging them.
+  pub fn fetch(&self) -> Result<()> {
+    let mut remote = self.repository.find_remote(&self.remote_name)?;
+    self.do_fetch(&mut remote)?;
+    Ok(())
+  }
+
+  /// # Preview Sync
+  ///
+  /// Fetch the remote and report what syncing the files under `dirs`
+  /// (relative to repo root) would do, without committing, merging or pushing.
+  /// - Only the remote refs are updated (by the fetch).
+  pub fn preview_sync(&self, dirs: Vec<&Path>) -> Result<SyncPreview> {
+    if self.is_merging() {
+      return Err(anyhow!(
+        "a merge is in progress, resolve its conflicts first!"
+      ));
+    }
+    let mut remote = self.repository.find_remote(&self.remote_name)?;
+    // no remote commit if the branch is not on the remote yet
+    let remote_commit = match self.do_fetch(&mut remote)? {
+      Some(fetch_commit) => Some(self.repository.find_commit(fetch_commit.id())?),
+      None => None,
+    };
+    let head_commit = self
+      .repository
+      .head()
+      .ok()
+      .and_then(|head| head.peel_to_commit().ok());
+    let base_commit = match (&head_commit, &remote_commit) {
+      (Some(head_commit), Some(remote_commit)) => self
+        .repository
+        .merge_base(head_commit.id(), remote_commit.id())
+        .ok()
+        .map(|base_id| self.repository.find_commit(base_id))
+        .transpose()?,
+      (Some(head_commit), None) => Some(head_commit.clone()),
+      (None, _) => None,
+    };
+    let base_tree = base_commit.as_ref().map(|c| c.tree()).transpose()?;
+    let remote_tree = remote_commit.as_ref().map(|c| c.tree()).transpose()?;
+    // tree of the local files as the sync would commit them (index is left untouched on disk)
+    let local_tree = {
+      let mut index = self.repository.index()?;
+      Self::stage(&mut index, &dirs)?;
+      let tree_id = index.write_tree();
+      index.read(true)?; // discard the staged changes
+      self.repository.find_tree(tree_id?)?
+    };
+
+    let mut diff_options = DiffOptions::new();
+    for dir in dirs.iter() {
+      diff_options.pathspec(dir);
+    }
+    let mut find_options = DiffFindOptions::new();
+    find_options.renames(true);
+    let mut local_diff = self.repository.diff_tree_to_tree(
+      base_tree.as_ref(),
+      Some(&local_tree),
+      Some(&mut diff_options),
+    )?;
+    local_diff.find_similar(Some(&mut find_options))?;
+    let local_changes = git_diff::diff_to_file_changes(&local_diff);
+    let remote_changes = match &remote_tree {
+      Some(remote_tree) => {
+        let mut remote_diff = self.repository.diff_tree_to_tree(
+          base_tree.as_ref(),
+          Some(remote_tree),
+          Some(&mut diff_options),
+        )?;
+        remote_diff.find_similar(Some(&mut find_options))?;
+        git_diff::diff_to_file_changes(&remote_diff)
+      }
+      None => vec![],
+    };
+
+    let (ahead, behind) = match (&head_commit, &remote_commit) {
+      (Some(head_commit), Some(remote_commit)) => self
+        .repository
+        .graph_ahead_behind(head_commit.id(), remote_commit.id())?,
+      // one side has no commits yet (eg. empty remote)
+      (Some(head_commit), None) => (self.count_commits(head_commit.id())?, 0),
+      (None, Some(remote_commit)) => (0, self.count_commits(remote_commit.id())?),
+      (None, None) => (0, 0),
+    };
+    let can_fast_forward = behind > 0 && ahead == 0 && local_changes.is_empty();
+
+    let mut conflicts = vec![];
+    if let (Some(remote_tree), false) = (&remote_tree, can_fast_forward) {
+      let ancestor_tree = match &base_tree {
+        Some(base_tree) => base_tree.clone(),
+        // unrelated histories are merged against an empty ancestor
+        None => self
+          .repository
+          .find_tree(self.repository.treebuilder(None)?.write()?)?,
+      };
+      let merged_index =
+        self
+          .repository
+          .merge_trees(&ancestor_tree, &local_tree, remote_tree, None)?;
+      if merged_index.has_conflicts() {
+        for conflict in merged_index.conflicts()? {
+          let conflict = conflict?;
+          let entry = conflict
+            .our
+            .or(conflict.their)
+            .or(conflict.ancestor)
+            .ok_or_else(|| anyhow!("conflict without entries!"))?;
+          let path = PathBuf::from(String::from_utf8_lossy(&entry.path).to_string());
+          if dirs.iter().any(|dir| path.starts_with(dir)) {
+            conflicts.push(path.to_string_lossy().to_string());
+          }
+        }
+      }
+    }
+
+    Ok(SyncPreview {
+      local_changes,
+      remote_changes,
+      ahead,
+      behind,
+      can_fast_forward,
+      conflicts,
+    })
+  }
+
+  /// Number of commits reachable from the commit `id`
+  fn count_commits(&self, id: Oid) -> Result<usize> {
+    let mut revwalk = self.repository.revwalk()?;
+    revwalk.push(id)?;
+    Ok(revwalk.count())
+  }
+
+  /// Id of the blob at `path` in the `tree` (if present)
+  fn tree_entry_id(tree: &Tree, path: &Path) -> Option<Oid> {
+    tree.get_path(path).ok().map(|entry| entry.id())
+  }
+
+  /// Find the path the file at `path` in `new_tree` was renamed from (if renamed)
+  fn find_rename_source(
+    &self,
+    old_tree: Option<&Tree>,
+    new_tree: &Tree,
+    path: &Path,
+  ) -> Result<Option<PathBuf>> {
+    let old_tree = match old_tree {
+      Some(old_tree) => old_tree,
+      None => return Ok(None),
+    };
+    let mut diff = self
+      .repository
+      .diff_tree_to_tree(Some(old_tree), Some(new_tree), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    let rename_source = diff
+      .deltas()
+      .filter(|delta| delta.status() == Delta::Renamed)
+      .find(|delta| delta.new_file().path() == Some(path))
+      .and_then(|delta| delta.old_file().path().map(|p| p.to_path_buf()));
+    Ok(rename_source)
+  }
+
+  /// # Is Merging
+  ///
+  /// Check if a merge (with unresolved conflicts) is in progress.
+  pub fn is_merging(&self) -> bool {
+    self.repository.state() == RepositoryState::Merge
+  }
+
+  /// # Get Conflicts
+  ///
+  /// Get all the conflicted documents of the in-progress merge.
+  /// - `relative_path` of each conflict is relative to the repo root.
+  pub fn get_conflicts(&self) -> Result<Vec<MergeConflict>> {
+    let index = self.repository.index()?;
+    let mut conflicts = vec![];
+    for conflict in index.conflicts()? {
+      let conflict = conflict?;
+      let relative_path = conflict
+        .our
+        .as_ref()
+        .or(conflict.their.as_ref())
+        .or(conflict.ancestor.as_ref())
+        .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+        .ok_or(anyhow!("conflict path invalid!"))?;
+      conflicts.push(MergeConflict {
+        relative_path,
+        ancestor: self.read_index_entry(conflict.ancestor.as_ref())?,
+        ours: self.read_index_entry(conflict.our.as_ref())?,
+        theirs: self.read_index_entry(conflict.their.as_ref())?,
+      });
+    }
+    Ok(conflicts)
+  }
+
+  /// Read the blob content of an index entry (if present)
+  fn read_index_entry(&self, entry: Option<&IndexEntry>) -> Result<Option<String>> {
+    match entry {
+      Some(entry) => {
+        let blob = self.repository.find_blob(entry.id)?;
+        Ok(Some(String::from_utf8_lossy(blob.content()).to_string()))
+      }
+      None => Ok(None),
+    }
+  }
+
+  /// # Resolve Conflict
+  ///
+  /// Resolve the conflicted document at `path` (relative to repo root)
+  /// using the given `resolution`.
+  /// - Writes the resolved content to the working tree and stages it.
+  /// - If the chosen side deleted the document, the document is removed.
+  pub fn resolve_conflict(&self, path: &Path, resolution: ConflictResolution) -> Result<()> {
+    let conflict = self
+      .get_conflicts()?
+      .into_iter()
+      .find(|c| Path::new(&c.relative_path) == path)
+      .ok_or(anyhow!("no conflict found for path: {}", path.display()))?;
+    let content = match resolution {
+      ConflictResolution::Ours => conflict.ours,
+      ConflictResolution::Theirs => conflict.theirs,
+      ConflictResolution::Merged(content) => Some(content),
+    };
+    let workdir = self
+      .repository
+      .workdir()
+      .ok_or(anyhow!("repository has no working directory!"))?;
+    let file_path = workdir.join(path);
+    let mut index = self.repository.index()?;
+    match content {
+      Some(content) => {
+        fsutils::write_to_path(file_path.as_path(), content)?;
+        index.add_path(path)?; // staging the path also clears its conflict entries
+      }
+      None => {
+        if file_path.exists() {
+          fsutils::remove_from_path(file_path.as_path())?;
+        }
+        index.remove_path(path)?;
+      }
+    }
+    index.write()?;
+    Ok(())
+  }
+
+  /// # Complete Merge
+  ///
+  /// Create the merge commit once all conflicts are resolved.
+  pub fn complete_merge(&self) -> Result<()> {
+    if !self.is_merging() {
+      return Err(anyhow!("no merge in progress!"));
+    }
+    if self.repository.index()?.has_conflicts() {
+      return Err(anyhow!("unresolved merge conflicts remain!"));
+    }
+    self.commit_merge()?;
     Ok(())
   }
 
@@ -309,7 +1141,7 @@ impl GitUtils {
     &self,
     remote_branch: &str,
     fetch_commit: git2::AnnotatedCommit<'a>,
-  ) -> Result<(), git2::Error> {
+  ) -> Result<MergeStatus, git2::Error> {
     // 1. do a merge analysis
     let analysis = self.repository.merge_analysis(&[&fetch_commit])?;
     debug!("analysis: {:?}", analysis);
@@ -334,61 +1166,57 @@ impl GitUtils {
           )?;
           self.repository.set_head(&refname)?;
           self.repository.checkout_head(Some(
-            git2::build::CheckoutBuilder::default()
+            self
+              .checkout_builder()
               .allow_conflicts(true)
               .conflict_style_merge(true)
               .force(),
           ))?;
         }
       };
+      Ok(MergeStatus::FastForward)
     } else if analysis.0.is_normal() {
       debug!("Doing a normal merge");
-      let head_commit = self
-        .repository
-        .reference_to_annotated_commit(&self.repository.head()?)?;
-      self.normal_merge(&head_commit, &fetch_commit)?;
+      self.normal_merge(&fetch_commit)
     } else {
       debug!("No merging to do...");
+      Ok(MergeStatus::UpToDate)
     }
-    Ok(())
   }
 
   /// # Pull a repo
   ///
   /// Downloads data from remote repo and updates existing files.
-  pub fn pull(&self) -> Result<()> {
-    let mut fo = git2::FetchOptions::new();
-    fo.remote_callbacks(Self::create_callbacks());
-    let mut remote = self.repository.find_remote("origin")?;
-    let branch_name = self.get_default_branch_name()?;
-    // Error is likely to occur in the following because on the first go the user maybe
-    // trying to fetch an empty repository from the remote. Therefore we just log it
-    // with warn! and ignore it.
-    match self
-      .do_fetch(&mut remote)
-      .context("do_fetch() failed with error, probably an empty repository")
-    {
-      Ok(fetch_commit) => {
-        self.do_merge(&branch_name, fetch_commit)?;
+  /// - Returns the `MergeStatus` of merging the fetched changes.
+  /// - Returns `SyncCancelled` if cancelled before merging.
+  /// - Returns the fetch error (network, auth, ...) if the fetch fails.
+  pub fn pull(&self) -> Result<MergeStatus> {
+    self.check_cancelled()?;
+    let mut remote = self.repository.find_remote(&self.remote_name)?;
+    let branch_name = self.get_branch_name()?;
+    // On the first go the branch may not be on the remote yet (eg. an empty
+    // repository), there is nothing to merge then.
+    let merge_status = match self.do_fetch(&mut remote)? {
+      Some(fetch_commit) => {
+        // last chance to cancel, the merge is not aborted midway
+        self.check_cancelled()?;
+        self.do_merge(&branch_name, fetch_commit)?
       }
-      Err(err) => {
-        warn!("{:?}", err)
+      None => {
+        debug!("{} is not on the remote yet, nothing to merge", branch_name);
+        MergeStatus::UpToDate
       }
     };
-    Ok(())
+    Ok(merge_status)
   }
 
   pub fn test_git_clone_ssh() -> Result<(), git2::Error> {
-    // Prepare callbacks.
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(|_url, username_from_url, _allowed_types| {
-      debug!("username_from_url: {:?}", username_from_url);
-      Cred::ssh_key_from_agent(username_from_url.unwrap())
-    });
+    // Use the ssh-agent for auth
+    let credentials = GitCredentialProvider::default();
 
     // Prepare fetch options.
     let mut fo = git2::FetchOptions::new();
-    fo.remote_callbacks(callbacks);
+    fo.remote_callbacks(credentials.create_callbacks());
 
     // Prepare builder.
     let mut builder = git2::build::RepoBuilder::new();
@@ -402,3 +1230,244 @@ impl GitUtils {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use super::*;
+  use crate::{
+    models::diff::FileDiffStatus,
+    utils::test_utils::{commit_documents, temp_repo, temp_synced_repos},
+  };
+
+  /// Paths of the files in the HEAD commit
+  fn head_files(git_utils: &GitUtils) -> Vec<String> {
+    let tree = git_utils.repository.head().unwrap().peel_to_tree().unwrap();
+    let mut files = vec![];
+    tree
+      .walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
+          files.push(format!("{}{}", dir, entry.name().unwrap()));
+        }
+        git2::TreeWalkResult::Ok
+      })
+      .unwrap();
+    files
+  }
+
+  #[test]
+  fn commits_removed_files() {
+    let (root, git_utils) = temp_repo(
+      "git_utils_removed",
+      "https://example.com/mediocre.git",
+      &[("a.md", "# A"), ("notes/b.md", "# B")],
+    );
+    let documents = Path::new("documents");
+    assert!(git_utils
+      .add_commit(vec![documents], |_| "add".to_string())
+      .unwrap());
+    assert_eq!(
+      head_files(&git_utils),
+      vec!["documents/a.md", "documents/notes/b.md"]
+    );
+
+    fs::remove_file(root.join("documents/notes/b.md")).unwrap();
+    let mut committed_changes = vec![];
+    assert!(git_utils
+      .add_commit(vec![documents], |changes| {
+        committed_changes = changes.to_vec();
+        "remove".to_string()
+      })
+      .unwrap());
+    assert_eq!(head_files(&git_utils), vec!["documents/a.md"]);
+    assert_eq!(committed_changes.len(), 1);
+    assert_eq!(committed_changes[0].relative_path, "documents/notes/b.md");
+    assert_eq!(committed_changes[0].status, FileDiffStatus::Deleted);
+
+    // nothing left to stage
+    assert!(!git_utils
+      .add_commit(vec![documents], |_| "empty".to_string())
+      .unwrap());
+  }
+
+  fn read(root: &Path, relative_path: &str) -> String {
+    fs::read_to_string(root.join(relative_path)).unwrap()
+  }
+
+  #[test]
+  fn resolves_merge_conflicts() {
+    let ((root_a, a), (root_b, b)) = temp_synced_repos(
+      "conflicts",
+      &[
+        ("a.md", "# A\n\nbase\n"),
+        ("b.md", "# B\n\nbase\n"),
+        ("c.md", "# C\n\nbase\n"),
+        ("d.md", "# D\n"),
+      ],
+    );
+    fs::write(root_a.join("documents/a.md"), "# A\n\nfrom a\n").unwrap();
+    fs::remove_file(root_a.join("documents/b.md")).unwrap();
+    fs::write(root_a.join("documents/c.md"), "# C\n\nfrom a\n").unwrap();
+    fs::write(root_a.join("documents/d.md"), "# D\n\nfrom a\n").unwrap();
+    assert!(commit_documents(&a));
+    assert_eq!(a.push().unwrap(), PushStatus::Pushed);
+    fs::write(root_b.join("documents/a.md"), "# A\n\nfrom b\n").unwrap();
+    fs::write(root_b.join("documents/b.md"), "# B\n\nfrom b\n").unwrap();
+    fs::write(root_b.join("documents/c.md"), "# C\n\nfrom b\n").unwrap();
+    assert!(commit_documents(&b));
+
+    assert_eq!(b.pull().unwrap(), MergeStatus::Conflicted);
+    assert!(b.is_merging());
+    let conflicts = b.get_conflicts().unwrap();
+    let sides = conflicts
+      .iter()
+      .map(|c| {
+        (
+          c.relative_path.as_str(),
+          c.ancestor.as_deref(),
+          c.ours.as_deref(),
+          c.theirs.as_deref(),
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      sides,
+      vec![
+        (
+          "documents/a.md",
+          Some("# A\n\nbase\n"),
+          Some("# A\n\nfrom b\n"),
+          Some("# A\n\nfrom a\n")
+        ),
+        (
+          "documents/b.md",
+          Some("# B\n\nbase\n"),
+          Some("# B\n\nfrom b\n"),
+          None
+        ),
+        (
+          "documents/c.md",
+          Some("# C\n\nbase\n"),
+          Some("# C\n\nfrom b\n"),
+          Some("# C\n\nfrom a\n")
+        ),
+      ]
+    );
+    // conflicted documents keep the local version, the others are merged
+    assert_eq!(read(&root_b, "documents/a.md"), "# A\n\nfrom b\n");
+    assert_eq!(read(&root_b, "documents/d.md"), "# D\n\nfrom a\n");
+    assert!(b.complete_merge().is_err());
+    assert!(b
+      .resolve_conflict(Path::new("documents/d.md"), ConflictResolution::Ours)
+      .is_err());
+
+    let resolutions = vec![
+      (
+        "documents/a.md",
+        ConflictResolution::Merged("# A\n\nmerged\n".to_string()),
+      ),
+      ("documents/b.md", ConflictResolution::Theirs),
+      ("documents/c.md", ConflictResolution::Ours),
+    ];
+    for (path, resolution) in resolutions {
+      b.resolve_conflict(Path::new(path), resolution).unwrap();
+    }
+    assert!(b.get_conflicts().unwrap().is_empty());
+    assert_eq!(read(&root_b, "documents/a.md"), "# A\n\nmerged\n");
+    assert!(!root_b.join("documents/b.md").exists());
+    assert_eq!(read(&root_b, "documents/c.md"), "# C\n\nfrom b\n");
+
+    b.complete_merge().unwrap();
+    assert!(!b.is_merging());
+    assert!(b.complete_merge().is_err());
+    let head = b.repository.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(head.parent_count(), 2);
+    assert_eq!(
+      head_files(&b),
+      vec!["documents/a.md", "documents/c.md", "documents/d.md"]
+    );
+    assert_eq!(b.push().unwrap(), PushStatus::Pushed);
+    assert_eq!(a.pull().unwrap(), MergeStatus::FastForward);
+    assert_eq!(read(&root_a, "documents/a.md"), "# A\n\nmerged\n");
+    assert_eq!(read(&root_a, "documents/c.md"), "# C\n\nfrom b\n");
+  }
+
+  /// `(path, status)` of the changes
+  fn change_list(changes: &[FileChange]) -> Vec<(&str, FileDiffStatus)> {
+    changes
+      .iter()
+      .map(|change| (change.relative_path.as_str(), change.status))
+      .collect()
+  }
+
+  #[test]
+  fn previews_sync_without_merging() {
+    let ((root_a, a), (root_b, b)) =
+      temp_synced_repos("preview", &[("a.md", "# A\n\nbase\n"), ("b.md", "# B\n")]);
+    let documents = Path::new("documents");
+    fs::write(root_a.join("documents/a.md"), "# A\n\nfrom a\n").unwrap();
+    fs::write(root_a.join("documents/new.md"), "# New\n").unwrap();
+    assert!(commit_documents(&a));
+    assert_eq!(a.push().unwrap(), PushStatus::Pushed);
+
+    // not committed local changes only
+    fs::write(root_b.join("documents/b.md"), "# B\n\nfrom b\n").unwrap();
+    let preview = b.preview_sync(vec![documents]).unwrap();
+    assert_eq!(
+      change_list(&preview.local_changes),
+      vec![("documents/b.md", FileDiffStatus::Modified)]
+    );
+    assert_eq!(
+      change_list(&preview.remote_changes),
+      vec![
+        ("documents/a.md", FileDiffStatus::Modified),
+        ("documents/new.md", FileDiffStatus::Added)
+      ]
+    );
+    assert_eq!((preview.ahead, preview.behind), (0, 1));
+    assert!(!preview.can_fast_forward);
+    assert!(preview.conflicts.is_empty());
+
+    // a committed change conflicting with the remote
+    fs::write(root_b.join("documents/a.md"), "# A\n\nfrom b\n").unwrap();
+    assert!(commit_documents(&b));
+    let head_id = b.repository.head().unwrap().target();
+    fs::write(root_b.join("documents/b.md"), "# B\n\nagain from b\n").unwrap();
+    let preview = b.preview_sync(vec![documents]).unwrap();
+    assert_eq!(
+      change_list(&preview.local_changes),
+      vec![
+        ("documents/a.md", FileDiffStatus::Modified),
+        ("documents/b.md", FileDiffStatus::Modified)
+      ]
+    );
+    assert_eq!((preview.ahead, preview.behind), (1, 1));
+    assert!(!preview.can_fast_forward);
+    assert_eq!(preview.conflicts, vec!["documents/a.md"]);
+    // nothing merged, committed or staged
+    assert_eq!(b.repository.head().unwrap().target(), head_id);
+    assert!(!b.is_merging());
+    assert!(b.staged_changes().unwrap().is_empty());
+    assert_eq!(read(&root_b, "documents/b.md"), "# B\n\nagain from b\n");
+    assert!(!root_b.join("documents/new.md").exists());
+
+    // without local changes the remote ones are fast-forwarded
+    assert_eq!(b.pull().unwrap(), MergeStatus::Conflicted);
+    b.resolve_conflict(Path::new("documents/a.md"), ConflictResolution::Theirs)
+      .unwrap();
+    assert!(b.preview_sync(vec![documents]).is_err());
+    b.complete_merge().unwrap();
+    assert!(commit_documents(&b));
+    assert_eq!(b.push().unwrap(), PushStatus::Pushed);
+    let preview = a.preview_sync(vec![documents]).unwrap();
+    assert!(preview.local_changes.is_empty());
+    assert_eq!(
+      change_list(&preview.remote_changes),
+      vec![("documents/b.md", FileDiffStatus::Modified)]
+    );
+    assert_eq!((preview.ahead, preview.behind), (0, 3));
+    assert!(preview.can_fast_forward);
+    assert!(preview.conflicts.is_empty());
+  }
+}
@@ -0,0 +1,28 @@
+/// # Escape HTML
+///
+/// Escape `text` to be used in HTML (as text or as a quoted attribute value).
+pub fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn escapes_html() {
+    for (text, expected) in &[
+      ("plain text", "plain text"),
+      ("<script>", "&lt;script&gt;"),
+      ("a & b", "a &amp; b"),
+      ("&amp;", "&amp;amp;"),
+      ("\"quoted\" 'single'", "&quot;quoted&quot; 'single'"),
+    ] {
+      assert_eq!(escape_html(text), *expected);
+    }
+  }
+}
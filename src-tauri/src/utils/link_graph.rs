@@ -0,0 +1,963 @@
+use std::{collections::HashMap, ops::Range};
+
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::front_matter::{document_body, parse_front_matter};
+
+/// Kind of a link in a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkKind {
+  /// `[[Note]]`, `[[folder/Note#Heading|label]]`
+  Wiki,
+  /// `![[Note]]`, `![[image.png]]`
+  WikiEmbed,
+  /// `[label](note.md)`
+  Markdown,
+  /// `[label]: note.md` (link reference definition)
+  Reference,
+  /// `![alt](image.png)`
+  Image,
+}
+
+/// A link found in a document (see `parse_links`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+  pub kind: LinkKind,
+  /// Target as written, without the anchor (percent-decoded for markdown links)
+  pub target: String,
+  /// Heading part of the target (`#Heading`)
+  pub anchor: Option<String>,
+  /// Label/alt text of the link
+  pub label: Option<String>,
+  /// Line of the link in the document (1-based)
+  pub line: usize,
+  /// Byte range of the whole link in the document
+  #[serde(skip)]
+  pub range: Range<usize>,
+  /// Byte range of the target (as written, without the anchor) in the document
+  #[serde(skip)]
+  pub target_range: Range<usize>,
+}
+
+impl Link {
+  /// Check if the link points to a (markdown) document, not to an image/attachment
+  pub fn is_document_link(&self) -> bool {
+    match self.kind {
+      LinkKind::Wiki | LinkKind::WikiEmbed => !is_attachment_name(&self.target),
+      LinkKind::Markdown | LinkKind::Reference => has_md_extension(&self.target),
+      LinkKind::Image => false,
+    }
+  }
+
+  /// Move the line relative ranges to the line at `offset`
+  fn at_line(mut self, offset: usize, line: usize) -> Self {
+    self.range = self.range.start + offset..self.range.end + offset;
+    self.target_range = self.target_range.start + offset..self.target_range.end + offset;
+    self.line = line;
+    self
+  }
+}
+
+pub fn has_md_extension(target: &str) -> bool {
+  RelativePath::new(target)
+    .extension()
+    .map_or(false, |extension| extension.eq_ignore_ascii_case("md"))
+}
+
+/// Check if a wiki link target names an attachment (eg. `image.png`)
+/// - `Note v1.2` or `Mr. Smith` are still document names.
+pub fn is_attachment_name(target: &str) -> bool {
+  match RelativePath::new(target).extension() {
+    Some(extension) => {
+      !extension.eq_ignore_ascii_case("md")
+        && extension.len() <= 5
+        && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        && extension.chars().any(|c| c.is_ascii_alphabetic())
+    }
+    None => false,
+  }
+}
+
+/// # Parse Links
+///
+/// Find the wiki links, markdown links/images and link reference definitions in `content`.
+/// - Links in the front matter, fenced code blocks and code spans are ignored.
+/// - External links (`https://..`, `mailto:..`) and anchors in the same
+///   document (`#..`) are skipped.
+pub fn parse_links(content: &str) -> Vec<Link> {
+  let body_start = content.len() - document_body(content).len();
+  let mut links = vec![];
+  let mut fence: Option<&str> = None;
+  let mut offset = 0;
+  for (line_index, line) in content.split_inclusive('\n').enumerate() {
+    let line_offset = offset;
+    offset += line.len();
+    if line_offset < body_start {
+      continue;
+    }
+    let trimmed = line.trim_start();
+    let marker = ["```", "~~~"]
+      .iter()
+      .copied()
+      .find(|marker| trimmed.starts_with(marker));
+    match (fence, marker) {
+      (None, Some(marker)) => fence = Some(marker),
+      (Some(open), Some(marker)) if open == marker => fence = None,
+      (Some(_), _) => {}
+      (None, None) => links.extend(
+        parse_line_links(line)
+          .into_iter()
+          .map(|link| link.at_line(line_offset, line_index + 1)),
+      ),
+    }
+  }
+  links
+}
+
+/// Parse the links of a single line (ranges are relative to the line)
+fn parse_line_links(line: &str) -> Vec<Link> {
+  if let Some(link) = parse_reference_definition(line) {
+    return vec![link];
+  }
+  let bytes = line.as_bytes();
+  let mut links = vec![];
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'\\' => i += 2,
+      b'`' => i = skip_code_span(line, i),
+      b'[' if line[i..].starts_with("[[") => match parse_wiki_link(line, i) {
+        Some(link) => {
+          i = link.range.end;
+          links.push(link);
+        }
+        None => i += 2,
+      },
+      b'[' => {
+        // Continue inside the label (eg. `[![image](a.png)](note.md)`)
+        links.extend(parse_inline_link(line, i));
+        i += 1;
+      }
+      _ => i += 1,
+    }
+  }
+  links
+}
+
+/// Get the index after the code span starting at `start` (or after its opening backticks)
+fn skip_code_span(line: &str, start: usize) -> usize {
+  let backticks = line[start..].bytes().take_while(|b| *b == b'`').count();
+  let mut i = start + backticks;
+  while let Some(position) = line[i..].find('`') {
+    let run_start = i + position;
+    let run = line[run_start..].bytes().take_while(|b| *b == b'`').count();
+    if run == backticks {
+      return run_start + run;
+    }
+    i = run_start + run;
+  }
+  start + backticks
+}
+
+/// Split the `#anchor` from a link target
+fn split_anchor(target: &str) -> (&str, Option<String>) {
+  match target.find('#') {
+    Some(position) => (
+      &target[..position],
+      Some(target[position + 1..].trim().to_string()).filter(|anchor| !anchor.is_empty()),
+    ),
+    None => (target, None),
+  }
+}
+
+/// Parse `[[target#anchor|label]]` (or `![[..]]`) starting at `start`
+fn parse_wiki_link(line: &str, start: usize) -> Option<Link> {
+  let embed = start > 0 && line.as_bytes()[start - 1] == b'!';
+  let inner_start = start + 2;
+  let inner_end = inner_start + line[inner_start..].find("]]")?;
+  let inner = &line[inner_start..inner_end];
+  if inner.trim().is_empty() || inner.contains('[') {
+    return None;
+  }
+  let (target, label) = match inner.find('|') {
+    Some(position) => (&inner[..position], Some(inner[position + 1..].trim())),
+    None => (inner, None),
+  };
+  let (target, anchor) = split_anchor(target);
+  let target_start = inner_start + target.len() - target.trim_start().len();
+  let target = target.trim();
+  Some(Link {
+    kind: if embed {
+      LinkKind::WikiEmbed
+    } else {
+      LinkKind::Wiki
+    },
+    target: target.to_string(),
+    anchor,
+    label: label.filter(|label| !label.is_empty()).map(String::from),
+    line: 0,
+    range: if embed { start - 1 } else { start }..inner_end + 2,
+    target_range: target_start..target_start + target.len(),
+  })
+}
+
+/// Parse `[label](destination "title")` (or `![alt](..)`) starting at `start`
+fn parse_inline_link(line: &str, start: usize) -> Option<Link> {
+  let bytes = line.as_bytes();
+  let image = start > 0 && bytes[start - 1] == b'!';
+  let mut depth = 0;
+  let mut i = start;
+  let label_end = loop {
+    match bytes.get(i)? {
+      b'\\' => i += 1,
+      b'[' => depth += 1,
+      b']' => {
+        depth -= 1;
+        if depth == 0 {
+          break i;
+        }
+      }
+      _ => {}
+    }
+    i += 1;
+  };
+  if bytes.get(label_end + 1) != Some(&b'(') {
+    return None;
+  }
+  let (destination, end) = parse_destination(line, label_end + 2)?;
+  markdown_link(
+    if image {
+      LinkKind::Image
+    } else {
+      LinkKind::Markdown
+    },
+    line,
+    destination,
+    &line[start + 1..label_end],
+    if image { start - 1 } else { start }..end,
+  )
+}
+
+/// Parse the `destination "title")` of an inline link starting at `start`.
+/// Returns the destination range and the index after the closing `)`.
+fn parse_destination(line: &str, start: usize) -> Option<(Range<usize>, usize)> {
+  let bytes = line.as_bytes();
+  let skip_spaces = |mut i: usize| {
+    while bytes.get(i).map_or(false, |b| *b == b' ' || *b == b'\t') {
+      i += 1;
+    }
+    i
+  };
+  let mut i = skip_spaces(start);
+  let destination = if bytes.get(i) == Some(&b'<') {
+    let close = i + 1 + line[i + 1..].find('>')?;
+    let destination = i + 1..close;
+    i = close + 1;
+    destination
+  } else {
+    let destination_start = i;
+    let mut depth = 0;
+    while let Some(b) = bytes.get(i) {
+      match b {
+        b'\\' => i += 1,
+        b'(' => depth += 1,
+        b')' if depth == 0 => break,
+        b')' => depth -= 1,
+        b if b.is_ascii_whitespace() => break,
+        _ => {}
+      }
+      i += 1;
+    }
+    i = i.min(bytes.len());
+    destination_start..i
+  };
+  i = skip_spaces(i);
+  if let Some(quote) = bytes.get(i).filter(|b| matches!(b, b'"' | b'\'' | b'(')) {
+    let close = if *quote == b'(' { ')' } else { *quote as char };
+    i = skip_spaces(i + 1 + line[i + 1..].find(close)? + 1);
+  }
+  if bytes.get(i) != Some(&b')') {
+    return None;
+  }
+  Some((destination, i + 1))
+}
+
+/// Parse a `[label]: destination "title"` line
+fn parse_reference_definition(line: &str) -> Option<Link> {
+  let indent = line.len() - line.trim_start_matches(' ').len();
+  let rest = &line[indent..];
+  if indent > 3 || !rest.starts_with('[') || rest.starts_with("[[") || rest.starts_with("[^") {
+    return None;
+  }
+  let label_end = indent + rest.find("]:")?;
+  let label = &line[indent + 1..label_end];
+  if label.trim().is_empty() || label.contains('[') || label.contains(']') {
+    return None;
+  }
+  let bytes = line.as_bytes();
+  let mut i = label_end + 2;
+  while bytes.get(i).map_or(false, |b| *b == b' ' || *b == b'\t') {
+    i += 1;
+  }
+  let destination = if bytes.get(i) == Some(&b'<') {
+    i + 1..i + 1 + line[i + 1..].find('>')?
+  } else {
+    let length = line[i..]
+      .find(|c: char| c.is_whitespace())
+      .unwrap_or(line.len() - i);
+    i..i + length
+  };
+  markdown_link(
+    LinkKind::Reference,
+    line,
+    destination,
+    label,
+    indent..line.trim_end().len(),
+  )
+}
+
+/// Build a markdown link, `None` for external/same document links
+fn markdown_link(
+  kind: LinkKind,
+  line: &str,
+  destination: Range<usize>,
+  label: &str,
+  range: Range<usize>,
+) -> Option<Link> {
+  let raw = &line[destination.clone()];
+  if raw.is_empty() || raw.starts_with('#') || raw.starts_with("//") || has_url_scheme(raw) {
+    return None;
+  }
+  let path_length = raw.find(|c| c == '#' || c == '?').unwrap_or(raw.len());
+  let anchor = raw
+    .find('#')
+    .map(|position| percent_decode(&raw[position + 1..]))
+    .filter(|anchor| !anchor.is_empty());
+  Some(Link {
+    kind,
+    target: percent_decode(&raw[..path_length]),
+    anchor,
+    label: Some(label.trim().to_string()).filter(|label| !label.is_empty()),
+    line: 0,
+    range,
+    target_range: destination.start..destination.start + path_length,
+  })
+}
+
+/// Check if the link destination starts with a url scheme (eg. `https:`)
+fn has_url_scheme(destination: &str) -> bool {
+  match destination.find(':') {
+    // 1 char schemes are windows drive letters
+    Some(position) if position > 1 => {
+      let scheme = &destination[..position];
+      scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+          .chars()
+          .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+    }
+    _ => false,
+  }
+}
+
+/// Decode `%XX` escapes (eg. `my%20note.md` -> `my note.md`)
+fn percent_decode(text: &str) -> String {
+  let bytes = text.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let escaped = match bytes.get(i + 1..i + 3) {
+      Some(hex) if bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit) => {
+        u8::from_str_radix(&text[i + 1..i + 3], 16).ok()
+      }
+      _ => None,
+    };
+    match escaped {
+      Some(byte) => {
+        decoded.push(byte);
+        i += 3;
+      }
+      None => {
+        decoded.push(bytes[i]);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A document link with the document it resolves to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedLink {
+  /// Relative path of the document the link is in
+  pub source: String,
+  #[serde(flatten)]
+  pub link: Link,
+  /// Relative path of the linked document (`None` if unresolved)
+  pub resolved_path: Option<String>,
+}
+
+/// Links/aliases of an indexed document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentLinks {
+  links: Vec<Link>,
+  /// Front matter `aliases` (wiki links may use them as the target)
+  aliases: Vec<String>,
+}
+
+/// # Link Graph
+///
+/// Links between the markdown documents, kept up to date by `SearchIndex`.
+/// - Links are stored as written and resolved when queried,
+///   so they follow documents being added/removed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkGraph {
+  /// document key -> links
+  documents: HashMap<String, DocumentLinks>,
+}
+
+impl LinkGraph {
+  /// (Re)index the links of the document `key` from its `content`
+  pub fn index_document(&mut self, key: &str, content: &str) {
+    let aliases = match parse_front_matter(content) {
+      Ok(Some(front_matter)) => front_matter.meta().aliases,
+      _ => vec![],
+    };
+    self.documents.insert(
+      key.to_string(),
+      DocumentLinks {
+        links: parse_links(content),
+        aliases,
+      },
+    );
+  }
+
+  pub fn remove_document(&mut self, key: &str) {
+    self.documents.remove(key);
+  }
+
+  pub fn rename_document(&mut self, old_key: &str, new_key: &str) {
+    if let Some(document) = self.documents.remove(old_key) {
+      self.documents.insert(new_key.to_string(), document);
+    }
+  }
+
+  pub fn contains_document(&self, key: &str) -> bool {
+    self.documents.contains_key(key)
+  }
+
+  pub fn resolver(&self) -> LinkResolver {
+    LinkResolver::new(self)
+  }
+
+  /// Document links in the document `key` (resolved or not), in document order
+  pub fn outgoing_links(&self, key: &str) -> Vec<ResolvedLink> {
+    let resolver = self.resolver();
+    match self.documents.get(key) {
+      Some(document) => document
+        .links
+        .iter()
+        .filter(|link| link.is_document_link())
+        .map(|link| resolver.resolved_link(key, link))
+        .collect(),
+      None => vec![],
+    }
+  }
+
+  /// Links to the document `key` from other documents (sorted by document/line)
+  pub fn backlinks(&self, key: &str) -> Vec<ResolvedLink> {
+    let resolver = self.resolver();
+    let mut backlinks: Vec<ResolvedLink> = self
+      .resolved_links(&resolver)
+      .filter(|link| link.source != key && link.resolved_path.as_deref() == Some(key))
+      .collect();
+    backlinks.sort_by(|a, b| (&a.source, a.link.line).cmp(&(&b.source, b.link.line)));
+    backlinks
+  }
+
+  /// Document links that don't resolve to any document (sorted by document/line)
+  pub fn unresolved_links(&self) -> Vec<ResolvedLink> {
+    let resolver = self.resolver();
+    let mut unresolved: Vec<ResolvedLink> = self
+      .resolved_links(&resolver)
+      .filter(|link| link.resolved_path.is_none())
+      .collect();
+    unresolved.sort_by(|a, b| (&a.source, a.link.line).cmp(&(&b.source, b.link.line)));
+    unresolved
+  }
+
+  fn resolved_links<'a>(
+    &'a self,
+    resolver: &'a LinkResolver<'a>,
+  ) -> impl Iterator<Item = ResolvedLink> + 'a {
+    self.documents.iter().flat_map(move |(source, document)| {
+      document
+        .links
+        .iter()
+        .filter(|link| link.is_document_link())
+        .map(move |link| resolver.resolved_link(source, link))
+    })
+  }
+}
+
+/// # Link Resolver
+///
+/// Resolves links to the documents of a `LinkGraph`, or to any files (see `for_files`).
+/// Paths/names are matched case insensitive.
+/// - Markdown links: path relative to the linking document (or the root if it starts with `/`).
+/// - Wiki links: document name anywhere (`[[Note]]`), path (`[[folder/Note]]`)
+///   or front matter alias. With several matches, documents in the same folder
+///   as the linking document are preferred, then the shortest path.
+pub struct LinkResolver<'a> {
+  /// lowercase key -> key
+  paths: HashMap<String, &'a str>,
+  /// link name (see `link_name`) -> keys
+  names: HashMap<String, Vec<&'a str>>,
+  /// lowercase alias -> keys
+  aliases: HashMap<String, Vec<&'a str>>,
+}
+
+impl<'a> LinkResolver<'a> {
+  fn new(graph: &'a LinkGraph) -> Self {
+    Self::from_files(
+      graph
+        .documents
+        .iter()
+        .map(|(key, document)| (key.as_str(), document.aliases.as_slice())),
+    )
+  }
+
+  /// Resolver over all the files (documents and attachments) at the relative paths `keys`
+  pub fn for_files(keys: &'a [String]) -> Self {
+    Self::from_files(keys.iter().map(|key| (key.as_str(), &[] as &[String])))
+  }
+
+  fn from_files<I: Iterator<Item = (&'a str, &'a [String])>>(files: I) -> Self {
+    let mut resolver = LinkResolver {
+      paths: HashMap::new(),
+      names: HashMap::new(),
+      aliases: HashMap::new(),
+    };
+    for (key, aliases) in files {
+      resolver.paths.insert(key.to_lowercase(), key);
+      if let Some(name) = link_name(key) {
+        resolver
+          .names
+          .entry(name)
+          .or_insert_with(Vec::new)
+          .push(key);
+      }
+      for alias in aliases.iter() {
+        resolver
+          .aliases
+          .entry(alias.to_lowercase())
+          .or_insert_with(Vec::new)
+          .push(key);
+      }
+    }
+    resolver
+  }
+
+  /// Resolve the document `link` in the document `source` to the key of the linked document
+  pub fn resolve(&self, source: &str, link: &Link) -> Option<String> {
+    if !link.is_document_link() {
+      return None;
+    }
+    self.resolve_target(source, link)
+  }
+
+  /// Resolve any `link` (also images/attachments) in the document `source` to a key
+  pub fn resolve_target(&self, source: &str, link: &Link) -> Option<String> {
+    if link.target.is_empty() {
+      // `[[#Heading]]` links to a heading of the same document
+      return link.anchor.as_ref().map(|_| source.to_string());
+    }
+    match link.kind {
+      LinkKind::Markdown | LinkKind::Reference | LinkKind::Image => {
+        self.resolve_path(source, &link.target)
+      }
+      LinkKind::Wiki | LinkKind::WikiEmbed => self.resolve_wiki(source, &link.target),
+    }
+  }
+
+  fn resolved_link(&self, source: &str, link: &Link) -> ResolvedLink {
+    ResolvedLink {
+      source: source.to_string(),
+      link: link.clone(),
+      resolved_path: self.resolve(source, link),
+    }
+  }
+
+  fn find_path(&self, key: &RelativePathBuf) -> Option<String> {
+    self
+      .paths
+      .get(&key.as_str().to_lowercase())
+      .map(|key| key.to_string())
+  }
+
+  fn resolve_path(&self, source: &str, target: &str) -> Option<String> {
+    let key = match target.strip_prefix('/') {
+      Some(root_target) => RelativePath::new(root_target).normalize(),
+      None => RelativePath::new(source)
+        .parent()
+        .unwrap_or_else(|| RelativePath::new(""))
+        .join(target)
+        .normalize(),
+    };
+    self.find_path(&key)
+  }
+
+  fn resolve_wiki(&self, source: &str, target: &str) -> Option<String> {
+    let path = if has_md_extension(target) || is_attachment_name(target) {
+      target.to_string()
+    } else {
+      format!("{}.md", target)
+    };
+    if target.contains('/') {
+      let path = path.trim_start_matches('/');
+      if let Some(key) = self.find_path(&RelativePath::new(path).normalize()) {
+        return Some(key);
+      }
+      if let Some(key) = self.resolve_path(source, path) {
+        return Some(key);
+      }
+      let suffix = format!("/{}", path.to_lowercase());
+      let candidates = self
+        .paths
+        .iter()
+        .filter(|(lowercase_key, _)| lowercase_key.ends_with(&suffix))
+        .map(|(_, key)| *key)
+        .collect();
+      return pick_candidate(source, candidates);
+    }
+    let candidates = self
+      .names
+      .get(&link_name(&path)?)
+      .or_else(|| self.aliases.get(&target.to_lowercase()))?;
+    pick_candidate(source, candidates.clone())
+  }
+}
+
+/// Name wiki links use for the file at `path` (lowercase):
+/// the file name, without the `.md` extension for documents
+pub fn link_name(path: &str) -> Option<String> {
+  let path = RelativePath::new(path);
+  let name = if has_md_extension(path.as_str()) {
+    path.file_stem()?
+  } else {
+    path.file_name()?
+  };
+  Some(name.to_lowercase())
+}
+
+/// Pick the document a link in `source` most likely means:
+/// same folder first, then the shortest path
+fn pick_candidate(source: &str, mut candidates: Vec<&str>) -> Option<String> {
+  let source_dir = RelativePath::new(source).parent();
+  candidates.sort_by_key(|key| {
+    (
+      RelativePath::new(key).parent() != source_dir,
+      key.matches('/').count(),
+      key.to_string(),
+    )
+  });
+  candidates.first().map(|key| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `(kind, target, anchor, label)` of the links in `content`
+  fn link_parts(content: &str) -> Vec<(LinkKind, String, Option<String>, Option<String>)> {
+    parse_links(content)
+      .into_iter()
+      .map(|link| (link.kind, link.target, link.anchor, link.label))
+      .collect()
+  }
+
+  fn part(
+    kind: LinkKind,
+    target: &str,
+    anchor: Option<&str>,
+    label: Option<&str>,
+  ) -> (LinkKind, String, Option<String>, Option<String>) {
+    (
+      kind,
+      target.to_string(),
+      anchor.map(String::from),
+      label.map(String::from),
+    )
+  }
+
+  /// Graph of the `(key, content)` documents
+  fn graph(documents: &[(&str, &str)]) -> LinkGraph {
+    let mut graph = LinkGraph::default();
+    for (key, content) in documents {
+      graph.index_document(key, content);
+    }
+    graph
+  }
+
+  #[test]
+  fn parses_links() {
+    use LinkKind::*;
+    for (content, expected) in &[
+      ("[[Note]]", vec![part(Wiki, "Note", None, None)]),
+      (
+        "[[folder/Note#Heading|label]]",
+        vec![part(Wiki, "folder/Note", Some("Heading"), Some("label"))],
+      ),
+      ("[[#Heading]]", vec![part(Wiki, "", Some("Heading"), None)]),
+      (
+        "![[image.png]]",
+        vec![part(WikiEmbed, "image.png", None, None)],
+      ),
+      (
+        "[label](note.md)",
+        vec![part(Markdown, "note.md", None, Some("label"))],
+      ),
+      (
+        "[up](../other/my%20note.md#Some%20Heading)",
+        vec![part(
+          Markdown,
+          "../other/my note.md",
+          Some("Some Heading"),
+          Some("up"),
+        )],
+      ),
+      (
+        "[a](<my note.md#x> \"title\")",
+        vec![part(Markdown, "my note.md", Some("x"), Some("a"))],
+      ),
+      (
+        "[a](note.md?plain=1)",
+        vec![part(Markdown, "note.md", None, Some("a"))],
+      ),
+      (
+        "![alt](image.png)",
+        vec![part(Image, "image.png", None, Some("alt"))],
+      ),
+      (
+        "[![alt](a.png)](note.md)",
+        vec![
+          part(Markdown, "note.md", None, Some("![alt](a.png)")),
+          part(Image, "a.png", None, Some("alt")),
+        ],
+      ),
+      (
+        "  [ref]: ../note.md \"title\"",
+        vec![part(Reference, "../note.md", None, Some("ref"))],
+      ),
+      (
+        "[ref]: <my note.md>",
+        vec![part(Reference, "my note.md", None, Some("ref"))],
+      ),
+      // escaped brackets
+      ("\\[[Note]] \\[a](note.md)", vec![]),
+      (
+        "[a \\] b](note.md)",
+        vec![part(Markdown, "note.md", None, Some("a \\] b"))],
+      ),
+      // code spans
+      ("`[[Note]]` ``[a](`b`.md)``", vec![]),
+      // an unclosed backtick is no code span
+      ("`[x](y.md)", vec![part(Markdown, "y.md", None, Some("x"))]),
+      ("``` [[Note]]", vec![]),
+      ("`code` [[Note]]", vec![part(Wiki, "Note", None, None)]),
+      // external/same document links, footnotes, empty links
+      (
+        "[a](https://a.com/b.md) [b](#heading) [c](mailto:a@b.c) [d](//cdn/e.md) [e]()",
+        vec![],
+      ),
+      ("[^1]: a footnote.md", vec![]),
+      ("[[]] [[ ]] [not a link] [a] (b.md)", vec![]),
+    ] {
+      assert_eq!(&link_parts(content), expected, "{:?}", content);
+    }
+  }
+
+  #[test]
+  fn skips_front_matter_and_code_blocks() {
+    let content = [
+      "---",
+      "see: \"[[Front]]\"",
+      "---",
+      "[[A]]",
+      "```md",
+      "[[B]]",
+      "~~~",
+      "[[C]]",
+      "```",
+      "~~~",
+      "[[D]]",
+      "~~~",
+      "[[E]]",
+    ]
+    .join("\n");
+    let links = parse_links(&content);
+    let targets: Vec<(&str, usize)> = links
+      .iter()
+      .map(|link| (link.target.as_str(), link.line))
+      .collect();
+    assert_eq!(targets, vec![("A", 4), ("E", 13)]);
+  }
+
+  #[test]
+  fn finds_link_ranges() {
+    let content = "# Title\nsee [[ Note#A | label ]] and ![x](<b c.md#y>)\n[ref]: ./d.md\n";
+    for (link, (range, target_range)) in parse_links(content).iter().zip(&[
+      ("[[ Note#A | label ]]", "Note"),
+      ("![x](<b c.md#y>)", "b c.md"),
+      ("[ref]: ./d.md", "./d.md"),
+    ]) {
+      assert_eq!(&content[link.range.clone()], *range);
+      assert_eq!(&content[link.target_range.clone()], *target_range);
+    }
+  }
+
+  #[test]
+  fn picks_candidates() {
+    for (source, candidates, expected) in &[
+      ("notes/a.md", vec!["b.md", "notes/b.md"], Some("notes/b.md")),
+      ("a.md", vec!["x/y/b.md", "x/b.md"], Some("x/b.md")),
+      ("c/a.md", vec!["y/b.md", "x/b.md"], Some("x/b.md")),
+      ("a.md", vec!["x/b.md", "b.md"], Some("b.md")),
+      ("a.md", vec![], None),
+    ] {
+      assert_eq!(
+        pick_candidate(source, candidates.clone()).as_deref(),
+        *expected,
+        "{} {:?}",
+        source,
+        candidates
+      );
+    }
+  }
+
+  #[test]
+  fn resolves_links() {
+    let graph = graph(&[
+      ("a.md", ""),
+      ("notes/a.md", ""),
+      ("notes/deep/a.md", ""),
+      ("notes/c.md", "---\naliases: [Sea, See]\n---\n"),
+      ("other/b.md", ""),
+      ("projects/x/readme.md", ""),
+      ("projects/y/readme.md", ""),
+      ("My Note.md", ""),
+    ]);
+    let resolver = graph.resolver();
+    for (source, content, expected) in &[
+      // ambiguous names: same folder, then the shortest path
+      ("notes/c.md", "[[a]]", Some("notes/a.md")),
+      ("other/b.md", "[[a]]", Some("a.md")),
+      ("other/b.md", "[[readme]]", Some("projects/x/readme.md")),
+      (
+        "projects/y/readme.md",
+        "[[readme]]",
+        Some("projects/y/readme.md"),
+      ),
+      // names are case insensitive, `.md` is optional
+      ("other/b.md", "[[A.md]]", Some("a.md")),
+      ("other/b.md", "[[my note]]", Some("My Note.md")),
+      // paths: from the root, relative to the source or a path suffix
+      ("a.md", "[[notes/a]]", Some("notes/a.md")),
+      ("notes/c.md", "[[deep/a]]", Some("notes/deep/a.md")),
+      ("a.md", "[[x/readme]]", Some("projects/x/readme.md")),
+      ("a.md", "[[/other/b]]", Some("other/b.md")),
+      ("a.md", "[[other/missing]]", None),
+      // aliases and anchors
+      ("a.md", "[[see#Heading]]", Some("notes/c.md")),
+      ("notes/c.md", "[[#Heading]]", Some("notes/c.md")),
+      ("a.md", "[[missing]]", None),
+      // markdown links are relative to the source
+      ("notes/deep/a.md", "[up](../c.md)", Some("notes/c.md")),
+      (
+        "notes/deep/a.md",
+        "[up](../../Other/B.md#x)",
+        Some("other/b.md"),
+      ),
+      ("notes/deep/a.md", "[root](/other/b.md)", Some("other/b.md")),
+      ("notes/c.md", "[here](./a.md)", Some("notes/a.md")),
+      ("notes/c.md", "[ref]: a.md", Some("notes/a.md")),
+      ("a.md", "[my](My%20Note.md)", Some("My Note.md")),
+      ("a.md", "[above](../a.md)", None),
+      ("a.md", "[name](a)", None),
+      // not document links
+      ("a.md", "![[image.png]]", None),
+      ("a.md", "![a](a.md)", None),
+    ] {
+      let link = &parse_links(content)[0];
+      assert_eq!(
+        resolver.resolve(source, link).as_deref(),
+        *expected,
+        "{} {:?}",
+        source,
+        content
+      );
+    }
+  }
+
+  #[test]
+  fn resolves_files() {
+    let keys = vec!["notes/a.md".to_string(), "images/pic.png".to_string()];
+    let resolver = LinkResolver::for_files(&keys);
+    for (content, expected) in &[
+      ("![[pic.png]]", Some("images/pic.png")),
+      ("![pic](../images/pic.png)", Some("images/pic.png")),
+      ("[[a]]", Some("notes/a.md")),
+      ("![[missing.png]]", None),
+    ] {
+      let link = &parse_links(content)[0];
+      assert_eq!(
+        resolver.resolve_target("notes/a.md", link).as_deref(),
+        *expected,
+        "{:?}",
+        content
+      );
+    }
+  }
+
+  #[test]
+  fn lists_backlinks_and_unresolved_links() {
+    let graph = graph(&[
+      ("a.md", "[[b]] [[b#x]] [[missing]]"),
+      ("b.md", "[[b]] [a](a.md)\n![[pic.png]]"),
+      ("notes/c.md", "\n[b](../b.md) [[nope]]"),
+    ]);
+    let backlinks: Vec<(String, usize)> = graph
+      .backlinks("b.md")
+      .into_iter()
+      .map(|link| (link.source, link.link.line))
+      .collect();
+    assert_eq!(
+      backlinks,
+      vec![
+        ("a.md".to_string(), 1),
+        ("a.md".to_string(), 1),
+        ("notes/c.md".to_string(), 2)
+      ]
+    );
+    let unresolved: Vec<String> = graph
+      .unresolved_links()
+      .into_iter()
+      .map(|link| link.link.target)
+      .collect();
+    assert_eq!(unresolved, vec!["missing", "nope"]);
+    let outgoing: Vec<Option<String>> = graph
+      .outgoing_links("b.md")
+      .into_iter()
+      .map(|link| link.resolved_path)
+      .collect();
+    assert_eq!(
+      outgoing,
+      vec![Some("b.md".to_string()), Some("a.md".to_string())]
+    );
+  }
+}
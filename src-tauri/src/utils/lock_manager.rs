@@ -0,0 +1,344 @@
+use std::{
+  fmt,
+  sync::{Arc, Mutex, MutexGuard},
+  thread,
+};
+
+use chrono::{SecondsFormat, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// Lock path of the whole documents dir
+pub const DOCUMENTS_ROOT: &str = "";
+
+/// Read (shared) or Write (exclusive) access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LockMode {
+  Read,
+  Write,
+}
+
+/// What the lock is held for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LockKind {
+  /// Reading/writing documents and folders.
+  FileSystem,
+  /// Syncing the documents with the cloud remote.
+  CloudSync,
+}
+
+/// # Lock Holder
+///
+/// A held lock, reported by `LockManager::holders` for diagnostics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockHolder {
+  pub id: u64,
+  /// Locked document/folder (relative to the documents dir,
+  /// empty for the whole documents dir)
+  pub path: String,
+  pub mode: LockMode,
+  pub kind: LockKind,
+  /// Command/job holding the lock
+  pub owner: String,
+  /// Name of the thread that acquired the lock
+  pub thread: Option<String>,
+  /// Time the lock was acquired (RFC 3339)
+  pub acquired_at: String,
+}
+
+impl LockHolder {
+  /// `true` if `path` is the locked path, inside it or one of its parents
+  fn overlaps(&self, path: &str) -> bool {
+    let contains = |parent: &str, child: &str| {
+      parent == DOCUMENTS_ROOT
+        || child == parent
+        || (child.starts_with(parent) && child[parent.len()..].starts_with('/'))
+    };
+    contains(&self.path, path) || contains(path, &self.path)
+  }
+}
+
+/// # Lock Busy
+///
+/// The lock could not be acquired, `holder` holds a conflicting lock.
+#[derive(Debug)]
+pub struct LockBusy {
+  pub holder: LockHolder,
+}
+
+impl std::error::Error for LockBusy {}
+
+impl fmt::Display for LockBusy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.holder.kind {
+      LockKind::CloudSync => write!(f, "Cloud Sync in progress, Please re-try after some time!"),
+      LockKind::FileSystem => write!(
+        f,
+        "FileSystem sync in progress, Please re-try after some time!"
+      ),
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+struct LockTable {
+  next_id: u64,
+  holders: Vec<LockHolder>,
+}
+
+/// # Lock Manager
+///
+/// Read/write locks on documents and folders (paths relative to the documents dir).
+/// - Locks on a folder cover all its documents, `DOCUMENTS_ROOT` covers everything.
+/// - Many readers or a single writer per path.
+/// - Locks are never waited on: `try_*` fail with `LockBusy` right away
+/// (commands then ask the client to retry).
+/// - Locks are released when their `LockGuard` is dropped, ie. also on
+/// errors/early returns and panics.
+#[derive(Debug, Clone, Default)]
+pub struct LockManager {
+  table: Arc<Mutex<LockTable>>,
+}
+
+impl LockManager {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn table(&self) -> MutexGuard<LockTable> {
+    // the table is consistent after every update, a panicking holder
+    // of the mutex must not block all later commands
+    self
+      .table
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner())
+  }
+
+  /// Shared file system lock on the document/folder `path`
+  pub fn try_read<P: AsRef<str>>(&self, path: P, owner: &str) -> Result<LockGuard, LockBusy> {
+    self.try_lock(path.as_ref(), LockMode::Read, LockKind::FileSystem, owner)
+  }
+
+  /// Exclusive file system lock on the document/folder `path`
+  pub fn try_write<P: AsRef<str>>(&self, path: P, owner: &str) -> Result<LockGuard, LockBusy> {
+    self.try_lock(path.as_ref(), LockMode::Write, LockKind::FileSystem, owner)
+  }
+
+  /// Cloud sync lock on all documents (`Write` while the repository/documents change)
+  pub fn try_cloud_sync(&self, mode: LockMode, owner: &str) -> Result<LockGuard, LockBusy> {
+    self.try_lock(DOCUMENTS_ROOT, mode, LockKind::CloudSync, owner)
+  }
+
+  /// # Try Lock
+  ///
+  /// Acquire the lock on `path` unless a conflicting lock is held
+  /// (any lock overlapping `path` if one of them is a `Write` lock).
+  pub fn try_lock(
+    &self,
+    path: &str,
+    mode: LockMode,
+    kind: LockKind,
+    owner: &str,
+  ) -> Result<LockGuard, LockBusy> {
+    let path = path.trim_matches('/');
+    let mut table = self.table();
+    let conflict = table.holders.iter().find(|holder| {
+      (mode == LockMode::Write || holder.mode == LockMode::Write) && holder.overlaps(path)
+    });
+    if let Some(holder) = conflict {
+      debug!(
+        "lock on `{}` for {} busy, held by {} on `{}`",
+        path, owner, holder.owner, holder.path
+      );
+      return Err(LockBusy {
+        holder: holder.clone(),
+      });
+    }
+    table.next_id += 1;
+    let id = table.next_id;
+    table.holders.push(LockHolder {
+      id,
+      path: path.to_string(),
+      mode,
+      kind,
+      owner: owner.to_string(),
+      thread: thread::current().name().map(str::to_string),
+      acquired_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+    });
+    Ok(LockGuard {
+      table: self.table.clone(),
+      id,
+    })
+  }
+
+  /// # Holders
+  ///
+  /// Get all the held locks (oldest first).
+  pub fn holders(&self) -> Vec<LockHolder> {
+    self.table().holders.clone()
+  }
+}
+
+/// # Lock Guard
+///
+/// A held lock, released on drop.
+#[derive(Debug)]
+pub struct LockGuard {
+  table: Arc<Mutex<LockTable>>,
+  id: u64,
+}
+
+impl Drop for LockGuard {
+  fn drop(&mut self) {
+    let mut table = self
+      .table
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner());
+    table.holders.retain(|holder| holder.id != self.id);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn overlaps_parents_and_children() {
+    let holder = |path: &str| LockHolder {
+      id: 1,
+      path: path.to_string(),
+      mode: LockMode::Write,
+      kind: LockKind::FileSystem,
+      owner: "test".to_string(),
+      thread: None,
+      acquired_at: String::new(),
+    };
+    for (locked, path, expected) in &[
+      ("notes/a.md", "notes/a.md", true),
+      ("notes", "notes/a.md", true),
+      ("notes", "notes/sub/a.md", true),
+      ("notes/a.md", "notes", true),
+      ("notes", "notes-old/a.md", false),
+      ("notes/a.md", "notes/a.md.bak", false),
+      ("notes/a.md", "notes/b.md", false),
+      ("notes", "other", false),
+      (DOCUMENTS_ROOT, "notes/a.md", true),
+      ("notes/a.md", DOCUMENTS_ROOT, true),
+    ] {
+      assert_eq!(
+        holder(locked).overlaps(path),
+        *expected,
+        "`{}` locked, `{}`",
+        locked,
+        path
+      );
+    }
+  }
+
+  #[test]
+  fn shares_reads_and_excludes_writes() {
+    let locks = LockManager::new();
+    let read = locks.try_read("notes/a.md", "read").unwrap();
+    let _other_read = locks.try_read("notes/a.md", "other_read").unwrap();
+    let busy = locks.try_write("notes/a.md", "write").unwrap_err();
+    assert_eq!(busy.holder.owner, "read");
+    assert_eq!(busy.holder.mode, LockMode::Read);
+    // a write on the parent folder conflicts with the reads of its documents
+    assert!(locks.try_write("notes", "write_folder").is_err());
+    assert!(locks.try_write("notes/b.md", "write_other").is_ok());
+    drop(read);
+    assert_eq!(locks.holders().len(), 1);
+    assert!(locks.try_write("notes/a.md", "write").is_err());
+  }
+
+  #[test]
+  fn excludes_overlapping_writes() {
+    let locks = LockManager::new();
+    let write = locks.try_write("/notes/", "rename_folder").unwrap();
+    assert_eq!(locks.holders()[0].path, "notes");
+    for path in &["notes", "notes/a.md", "notes/sub/b.md", DOCUMENTS_ROOT] {
+      let busy = locks.try_read(path, "read").unwrap_err();
+      assert_eq!(busy.holder.owner, "rename_folder", "{}", path);
+      assert!(locks.try_write(path, "write").is_err(), "{}", path);
+    }
+    assert!(locks.try_write("notes-old/a.md", "write").is_ok());
+    drop(write);
+    assert!(locks.try_write("notes/a.md", "write").is_ok());
+  }
+
+  #[test]
+  fn excludes_documents_while_syncing() {
+    let locks = LockManager::new();
+    let sync = locks
+      .try_cloud_sync(LockMode::Write, "sync_to_git_cloud")
+      .unwrap();
+    let busy = locks.try_write("notes/a.md", "write_document").unwrap_err();
+    assert_eq!(busy.holder.kind, LockKind::CloudSync);
+    assert_eq!(
+      busy.to_string(),
+      "Cloud Sync in progress, Please re-try after some time!"
+    );
+    assert!(locks.try_read("notes/a.md", "read_document").is_err());
+    assert!(locks
+      .try_cloud_sync(LockMode::Read, "preview_cloud_sync")
+      .is_err());
+    drop(sync);
+
+    // a preview only reads: documents can be read, not written
+    let _preview = locks
+      .try_cloud_sync(LockMode::Read, "preview_cloud_sync")
+      .unwrap();
+    assert!(locks.try_read("notes/a.md", "read_document").is_ok());
+    assert!(locks.try_write("notes/a.md", "write_document").is_err());
+    let _read = locks.try_read("notes/b.md", "read_document").unwrap();
+    let busy = locks
+      .try_cloud_sync(LockMode::Write, "sync_to_git_cloud")
+      .unwrap_err();
+    assert_eq!(busy.holder.owner, "preview_cloud_sync");
+  }
+
+  #[test]
+  fn releases_on_drop() {
+    let locks = LockManager::new();
+    {
+      let _write = locks.try_write("notes/a.md", "write").unwrap();
+      let _read = locks.try_read("notes/b.md", "read").unwrap();
+      let owners = locks
+        .holders()
+        .into_iter()
+        .map(|holder| holder.owner)
+        .collect::<Vec<_>>();
+      assert_eq!(owners, vec!["write", "read"]);
+    }
+    assert!(locks.holders().is_empty());
+    // also released when the holder fails/returns early
+    let failing = || -> Result<(), LockBusy> {
+      let _write = locks.try_write("notes/a.md", "write")?;
+      locks.try_write("notes", "rename_folder")?;
+      Ok(())
+    };
+    assert!(failing().is_err());
+    assert!(locks.holders().is_empty());
+  }
+
+  #[test]
+  fn recovers_from_a_poisoned_table() {
+    let locks = LockManager::new();
+    let write = locks.try_write("notes/a.md", "write").unwrap();
+    let table = locks.table.clone();
+    let panicked = thread::spawn(move || {
+      let _table = table.lock().unwrap();
+      panic!("panic while holding the lock table");
+    })
+    .join();
+    assert!(panicked.is_err());
+    assert!(locks.table.is_poisoned());
+    assert!(locks.try_write("notes/a.md", "write").is_err());
+    drop(write);
+    assert!(locks.holders().is_empty());
+    assert!(locks.try_write("notes/a.md", "write").is_ok());
+  }
+}
@@ -1,6 +1,21 @@
+pub mod code_highlighter;
+pub mod commit_message;
+pub mod document_path;
+pub mod front_matter;
+pub mod fs_watcher;
 pub mod fsutils;
+pub mod git_credentials;
+pub mod git_diff;
 pub mod git_utils;
-pub mod window_event_manager;
-pub mod error;
+pub mod html;
+pub mod link_graph;
+pub mod lock_manager;
 pub mod logger;
-pub mod sync_state_manager;
+pub mod outline;
+pub mod search_index;
+pub mod sync_jobs;
+pub mod sync_progress_reporter;
+pub mod sync_scheduler;
+#[cfg(test)]
+pub mod test_utils;
+pub mod window_event_manager;
@@ -0,0 +1,265 @@
+use comrak::{
+  nodes::{AstNode, NodeCode, NodeValue},
+  parse_document, Anchorizer, Arena, ComrakOptions,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::front_matter::document_body;
+
+/// Marker (on its own line) replaced by the table of contents
+const TOC_MARKER: &str = "[TOC]";
+
+/// # Heading
+///
+/// Heading of a document outline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heading {
+  /// 1 to 6
+  pub level: u32,
+  /// Plain text of the heading (without markdown)
+  pub text: String,
+  /// Id of the heading in the rendered HTML (see `heading_slug`)
+  pub slug: String,
+  /// Line of the heading in the document (starts at 1, front matter included)
+  pub line: usize,
+  /// Headings of a deeper level in the section of this heading
+  pub children: Vec<Heading>,
+}
+
+/// # Outline
+///
+/// Headings of a markdown document, in the order (and with the ids)
+/// comrak renders them with `header_ids`.
+#[derive(Debug, Clone, Default)]
+pub struct Outline {
+  /// Top level headings (sub headings are their `children`)
+  pub headings: Vec<Heading>,
+  /// Lines of the `[TOC]` markers
+  pub toc_lines: Vec<usize>,
+}
+
+impl Outline {
+  /// Parse the outline of the document `content` (its front matter is skipped).
+  /// - `options` should be the ones used to render the document.
+  pub fn parse(content: &str, options: &ComrakOptions) -> Self {
+    let body = document_body(content);
+    let line_offset = content[..content.len() - body.len()].matches('\n').count();
+    let arena = Arena::new();
+    let root = parse_document(&arena, body, options);
+    let mut anchorizer = Anchorizer::new();
+    let mut headings = vec![];
+    let mut toc_lines = vec![];
+    for node in root.descendants() {
+      let ast = node.data.borrow();
+      let line = ast.start_line as usize + line_offset;
+      match ast.value {
+        NodeValue::Heading(ref heading) => {
+          let text = collect_text(node);
+          headings.push(Heading {
+            level: heading.level,
+            text: text.trim().to_string(),
+            slug: anchorizer.anchorize(text),
+            line,
+            children: vec![],
+          })
+        }
+        NodeValue::Paragraph if is_toc_marker(root, node) => toc_lines.push(line),
+        _ => {}
+      }
+    }
+    Self {
+      headings: nest_headings(&mut headings.into_iter().peekable(), 0),
+      toc_lines,
+    }
+  }
+}
+
+/// # Heading Slug
+///
+/// Id comrak gives to a heading with the `text` (eg. `Heading Anchors!` -> `heading-anchors`).
+/// - Lowercase, only letters, numbers, `_`, `-` are kept and spaces become `-`.
+/// - Later headings with the same slug in a document get a `-1`, `-2`, ... suffix
+///   (see `Outline`), links to a heading by its text point to the first one.
+pub fn heading_slug(text: &str) -> String {
+  Anchorizer::new().anchorize(text.to_string())
+}
+
+/// Text of the `node` (the same comrak makes the heading ids from)
+fn collect_text<'a>(node: &'a AstNode<'a>) -> String {
+  let mut text = vec![];
+  collect_text_bytes(node, &mut text);
+  String::from_utf8_lossy(&text).to_string()
+}
+
+fn collect_text_bytes<'a>(node: &'a AstNode<'a>, text: &mut Vec<u8>) {
+  match node.data.borrow().value {
+    NodeValue::Text(ref literal) | NodeValue::Code(NodeCode { ref literal, .. }) => {
+      text.extend_from_slice(literal)
+    }
+    NodeValue::LineBreak | NodeValue::SoftBreak => text.push(b' '),
+    _ => {
+      for child in node.children() {
+        collect_text_bytes(child, text);
+      }
+    }
+  }
+}
+
+/// `true` if the paragraph `node` is a top level `[TOC]` line
+fn is_toc_marker<'a>(root: &'a AstNode<'a>, node: &'a AstNode<'a>) -> bool {
+  let is_top_level = node
+    .parent()
+    .map_or(false, |parent| std::ptr::eq(parent, root));
+  let is_text = node
+    .children()
+    .all(|child| matches!(child.data.borrow().value, NodeValue::Text(_)));
+  is_top_level && is_text && collect_text(node).trim().eq_ignore_ascii_case(TOC_MARKER)
+}
+
+/// Nest the (ordered) `headings` deeper than `parent_level` under their section heading
+fn nest_headings<I: Iterator<Item = Heading>>(
+  headings: &mut std::iter::Peekable<I>,
+  parent_level: u32,
+) -> Vec<Heading> {
+  let mut nested = vec![];
+  while let Some(mut heading) = headings.next_if(|heading| heading.level > parent_level) {
+    heading.children = nest_headings(headings, heading.level);
+    nested.push(heading);
+  }
+  nested
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `(level, text, slug, line)` of the `headings` in document order (depth first)
+  fn flatten(headings: &[Heading]) -> Vec<(u32, String, String, usize)> {
+    let mut flat = vec![];
+    for heading in headings {
+      flat.push((
+        heading.level,
+        heading.text.clone(),
+        heading.slug.clone(),
+        heading.line,
+      ));
+      flat.extend(flatten(&heading.children));
+    }
+    flat
+  }
+
+  fn heading(level: u32, text: &str, slug: &str, line: usize) -> (u32, String, String, usize) {
+    (level, text.to_string(), slug.to_string(), line)
+  }
+
+  /// Nesting of the `headings` as `text(children..)`
+  fn tree(headings: &[Heading]) -> String {
+    headings
+      .iter()
+      .map(|heading| match heading.children.as_slice() {
+        [] => heading.text.clone(),
+        children => format!("{}({})", heading.text, tree(children)),
+      })
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+
+  #[test]
+  fn makes_heading_slugs() {
+    for (text, expected) in &[
+      ("Heading", "heading"),
+      ("Heading Anchors!", "heading-anchors"),
+      ("Hello, World", "hello-world"),
+      ("snake_case and-dash", "snake_case-and-dash"),
+      ("C++ & Rust", "c--rust"),
+      ("v1.2 Release", "v12-release"),
+      ("Ünïcödé 日本語", "ünïcödé-日本語"),
+      ("", ""),
+    ] {
+      assert_eq!(heading_slug(text), *expected, "{:?}", text);
+    }
+  }
+
+  #[test]
+  fn parses_headings() {
+    let content = [
+      "---",
+      "title: \"# Not a heading\"",
+      "---",
+      "# Title",
+      "",
+      "## With `code` and *emphasis*",
+      "",
+      "```",
+      "# Not a heading",
+      "```",
+      "",
+      "Setext Heading",
+      "--------------",
+      "",
+      "### Heading Anchors!",
+    ]
+    .join("\n");
+    let outline = Outline::parse(&content, &ComrakOptions::default());
+    assert_eq!(
+      flatten(&outline.headings),
+      vec![
+        heading(1, "Title", "title", 4),
+        heading(2, "With code and emphasis", "with-code-and-emphasis", 6),
+        heading(2, "Setext Heading", "setext-heading", 12),
+        heading(3, "Heading Anchors!", "heading-anchors", 15),
+      ]
+    );
+  }
+
+  #[test]
+  fn numbers_duplicate_slugs() {
+    let content = "# Notes\n## Notes\n# Notes-1\n## Notes\n### notes";
+    let outline = Outline::parse(content, &ComrakOptions::default());
+    let slugs: Vec<String> = flatten(&outline.headings)
+      .into_iter()
+      .map(|(_, _, slug, _)| slug)
+      .collect();
+    assert_eq!(
+      slugs,
+      vec!["notes", "notes-1", "notes-1-1", "notes-2", "notes-3"]
+    );
+  }
+
+  #[test]
+  fn nests_headings() {
+    for (content, expected) in &[
+      ("# A\n## B\n### C\n## D\n# E", "A(B(C) D) E"),
+      ("## A\n# B\n## C", "A B(C)"),
+      ("# A\n### B\n## C\n#### D", "A(B C(D))"),
+      ("### A\n### B", "A B"),
+      ("No headings", ""),
+    ] {
+      let outline = Outline::parse(content, &ComrakOptions::default());
+      assert_eq!(tree(&outline.headings), *expected, "{:?}", content);
+    }
+  }
+
+  #[test]
+  fn finds_toc_markers() {
+    let content = [
+      "[TOC]",
+      "",
+      "# A",
+      "",
+      "  [toc]",
+      "",
+      "> [TOC]",
+      "",
+      "- [TOC]",
+      "",
+      "Text [TOC]",
+      "",
+      "`[TOC]`",
+    ]
+    .join("\n");
+    let outline = Outline::parse(&content, &ComrakOptions::default());
+    assert_eq!(outline.toc_lines, vec![1, 5]);
+  }
+}
@@ -0,0 +1,938 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  thread, time,
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use log::{error, warn};
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+  models::server_error::ServerError,
+  utils::{fsutils, html::escape_html, link_graph::LinkGraph},
+};
+
+/// Max number of snippets returned per search result
+const MAX_SNIPPETS: usize = 3;
+/// Number of bytes of context shown around a hit in a snippet
+const SNIPPET_CONTEXT_LEN: usize = 60;
+/// BM25 term frequency saturation
+const BM25_K1: f64 = 1.2;
+/// BM25 document length normalization
+const BM25_B: f64 = 0.75;
+/// Interval between saves of the updated index (see `start_search_index_saver`)
+const SAVE_INTERVAL: time::Duration = time::Duration::from_secs(5);
+
+/// A word of a document (lowercased) with its byte range in the content
+struct Token {
+  term: String,
+  start: usize,
+  end: usize,
+}
+
+/// Split `content` into lowercased alphanumeric words
+fn tokenize(content: &str) -> Vec<Token> {
+  let mut tokens = vec![];
+  let mut token_start = None;
+  for (i, c) in content.char_indices() {
+    if c.is_alphanumeric() {
+      if token_start.is_none() {
+        token_start = Some(i);
+      }
+    } else if let Some(start) = token_start.take() {
+      tokens.push(Token {
+        term: content[start..i].to_lowercase(),
+        start,
+        end: i,
+      });
+    }
+  }
+  if let Some(start) = token_start {
+    tokens.push(Token {
+      term: content[start..].to_lowercase(),
+      start,
+      end: content.len(),
+    });
+  }
+  tokens
+}
+
+/// Get the (byte offset, text) of all markdown ATX headings in `content`
+/// (ignoring fenced code blocks)
+fn headings(content: &str) -> Vec<(usize, String)> {
+  let mut headings = vec![];
+  let mut in_code_block = false;
+  let mut offset = 0;
+  for line in content.split_inclusive('\n') {
+    let trimmed = line.trim();
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+      in_code_block = !in_code_block;
+    } else if !in_code_block && trimmed.starts_with('#') {
+      let text = trimmed.trim_start_matches('#');
+      if text.is_empty() || text.starts_with(' ') {
+        headings.push((offset, text.trim().trim_end_matches('#').trim().to_string()));
+      }
+    }
+    offset += line.len();
+  }
+  headings
+}
+
+/// Normalized index key of a document relative path
+fn document_key(relative_path: &str) -> String {
+  RelativePath::new(relative_path).normalize().to_string()
+}
+
+/// Move `index` back to the nearest char boundary of `content`
+fn floor_char_boundary(content: &str, mut index: usize) -> usize {
+  while !content.is_char_boundary(index) {
+    index -= 1;
+  }
+  index
+}
+
+/// Move `index` forward to the nearest char boundary of `content`
+fn ceil_char_boundary(content: &str, mut index: usize) -> usize {
+  while !content.is_char_boundary(index) {
+    index += 1;
+  }
+  index
+}
+
+/// A single clause of a search query (all clauses must match)
+#[derive(Debug, Clone)]
+enum QueryClause {
+  /// Exact word, eg. `note`
+  Term(String),
+  /// Word prefix, eg. `not*`
+  Prefix(String),
+  /// Consecutive words, eg. `"my note"`
+  Phrase(Vec<String>),
+}
+
+impl QueryClause {
+  /// Number of tokens matched by the clause starting at `tokens[i]` (if it matches)
+  fn match_len(&self, tokens: &[Token], i: usize) -> Option<usize> {
+    match self {
+      QueryClause::Term(term) if tokens[i].term == *term => Some(1),
+      QueryClause::Prefix(prefix) if tokens[i].term.starts_with(prefix.as_str()) => Some(1),
+      QueryClause::Phrase(terms)
+        if tokens.len() >= i + terms.len()
+          && terms
+            .iter()
+            .enumerate()
+            .all(|(j, t)| tokens[i + j].term == *t) =>
+      {
+        Some(terms.len())
+      }
+      _ => None,
+    }
+  }
+}
+
+/// # Search Query
+///
+/// Parsed search query.
+/// - Words are matched exactly (case insensitive): `note`
+/// - Prefixes end with `*`: `not*`
+/// - Phrases are quoted: `"my note"`
+/// - Directory filter: `dir:projects/work`
+/// - Modified date filters: `modified:>2021-01-31`, `modified:<=2021-02-01`
+/// or `modified:2021-01-31` (modified on that day)
+#[derive(Debug, Default)]
+struct SearchQuery {
+  clauses: Vec<QueryClause>,
+  dir: Option<String>,
+  modified_from: Option<NaiveDateTime>,
+  modified_until: Option<NaiveDateTime>,
+}
+
+impl SearchQuery {
+  fn parse(query: &str) -> Result<Self> {
+    let mut search_query = SearchQuery::default();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+      if c.is_whitespace() {
+        continue;
+      }
+      if c == '"' {
+        let phrase: String = chars.by_ref().take_while(|c| *c != '"').collect();
+        let terms: Vec<String> = tokenize(&phrase).into_iter().map(|t| t.term).collect();
+        if !terms.is_empty() {
+          search_query.clauses.push(QueryClause::Phrase(terms));
+        }
+        continue;
+      }
+      let mut word = c.to_string();
+      while let Some(c) = chars.peek() {
+        if c.is_whitespace() {
+          break;
+        }
+        word.push(*c);
+        chars.next();
+      }
+      search_query.push_word(&word)?;
+    }
+    Ok(search_query)
+  }
+
+  fn push_word(&mut self, word: &str) -> Result<()> {
+    if let Some(dir) = word.strip_prefix("dir:") {
+      self.dir = Some(document_key(dir.trim_matches('/')));
+    } else if let Some(filter) = word.strip_prefix("modified:") {
+      self.push_modified_filter(filter)?;
+    } else if let Some(prefix) = word.strip_suffix('*') {
+      if let Some(token) = tokenize(prefix).into_iter().next() {
+        self.clauses.push(QueryClause::Prefix(token.term));
+      }
+    } else {
+      let mut terms: Vec<String> = tokenize(word).into_iter().map(|t| t.term).collect();
+      match terms.len() {
+        0 => {}
+        1 => self.clauses.push(QueryClause::Term(terms.remove(0))),
+        _ => self.clauses.push(QueryClause::Phrase(terms)), // eg. `foo-bar`
+      }
+    }
+    Ok(())
+  }
+
+  fn push_modified_filter(&mut self, filter: &str) -> Result<()> {
+    let (op, date) = match filter {
+      f if f.starts_with(">=") || f.starts_with("<=") => f.split_at(2),
+      f if f.starts_with('>') || f.starts_with('<') || f.starts_with('=') => f.split_at(1),
+      f => ("=", f),
+    };
+    // an invalid query is the user's error (`INVALID_INPUT` for the frontend)
+    let invalid_date = || ServerError::UserError {
+      message: format!("invalid date: {}, expected format: YYYY-MM-DD", date),
+    };
+    let day_start = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+      .map_err(|_| invalid_date())?
+      .and_hms_opt(0, 0, 0)
+      .ok_or_else(invalid_date)?;
+    let next_day_start = day_start + Duration::days(1);
+    match op {
+      ">" => self.modified_from = Some(next_day_start),
+      ">=" => self.modified_from = Some(day_start),
+      "<" => self.modified_until = Some(day_start),
+      "<=" => self.modified_until = Some(next_day_start),
+      _ => {
+        self.modified_from = Some(day_start);
+        self.modified_until = Some(next_day_start);
+      }
+    }
+    Ok(())
+  }
+
+  /// Check if a document passes the dir/modified filters
+  fn filter(&self, key: &str, document: &IndexedDocument) -> bool {
+    if let Some(dir) = &self.dir {
+      if !dir.is_empty() && !key.starts_with(&format!("{}/", dir)) {
+        return false;
+      }
+    }
+    if self.modified_from.is_none() && self.modified_until.is_none() {
+      return true;
+    }
+    let modified = match document
+      .modified
+      .as_ref()
+      .and_then(|m| DateTime::parse_from_rfc3339(m).ok())
+    {
+      Some(m) => m.naive_utc(),
+      None => return false,
+    };
+    self.modified_from.map_or(true, |from| modified >= from)
+      && self.modified_until.map_or(true, |until| modified < until)
+  }
+}
+
+/// Highlighted part of a document matching the query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSnippet {
+  /// Heading of the section where the hit occurs
+  heading: Option<String>,
+  /// HTML escaped snippet text, hits are wrapped in `<mark>`
+  html: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+  /// Document path relative to the documents dir
+  relative_path: String,
+  score: f64,
+  /// Heading of the section where the first hit occurs
+  heading: Option<String>,
+  snippets: Vec<SearchSnippet>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexedDocument {
+  /// Modified time of the document when indexed (RFC 3339)
+  modified: Option<String>,
+  /// Number of words in the document
+  token_count: u32,
+}
+
+/// # Search Index
+///
+/// Persistent inverted index over all markdown documents.
+/// - Maps each word to the documents (and word positions) it occurs in.
+/// - Also keeps the links between the documents (see `LinkGraph`).
+/// - Saved as JSON under the db dir (in the background, see `start_search_index_saver`).
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchIndex {
+  #[serde(skip)]
+  index_path: PathBuf,
+  documents: HashMap<String, IndexedDocument>,
+  /// word -> document key -> word positions
+  postings: HashMap<String, HashMap<String, Vec<u32>>>,
+  /// document key -> words of the document (to update `postings`), rebuilt on load
+  #[serde(skip)]
+  document_terms: HashMap<String, Vec<String>>,
+  #[serde(default)]
+  links: LinkGraph,
+  /// `true` if changed since the last save
+  #[serde(skip)]
+  is_dirty: bool,
+}
+
+impl SearchIndex {
+  /// Load the index saved at `index_path` (or start with an empty index)
+  pub fn load(index_path: &Path) -> Self {
+    let index = fs::read_to_string(index_path)
+      .map_err(anyhow::Error::from)
+      .and_then(|content| Ok(serde_json::from_str::<SearchIndex>(&content)?));
+    let mut index = match index {
+      Ok(index) => index,
+      Err(e) => {
+        warn!(
+          "search index not loaded, starting with an empty index: {}",
+          e
+        );
+        SearchIndex::default()
+      }
+    };
+    index.index_path = index_path.to_path_buf();
+    for (term, documents) in index.postings.iter() {
+      for key in documents.keys() {
+        index
+          .document_terms
+          .entry(key.clone())
+          .or_insert_with(Vec::new)
+          .push(term.clone());
+      }
+    }
+    index
+  }
+
+  /// Save the index to disk
+  pub fn save(&mut self) -> Result<()> {
+    fsutils::write_to_path(self.index_path.as_path(), serde_json::to_string(self)?)?;
+    self.is_dirty = false;
+    Ok(())
+  }
+
+  /// Links between the indexed documents
+  pub fn links(&self) -> &LinkGraph {
+    &self.links
+  }
+
+  /// Check if the document on `relative_path` should be indexed (markdown only)
+  fn is_indexable(relative_path: &str) -> bool {
+    RelativePath::new(relative_path).extension() == Some("md")
+  }
+
+  /// # Index File
+  ///
+  /// (Re)index the document on `relative_path` from disk.
+  pub fn index_file(&mut self, documents_dir: &Path, relative_path: &str) -> Result<()> {
+    let key = document_key(relative_path);
+    self.remove_document(&key);
+    if !Self::is_indexable(&key) {
+      return Ok(());
+    }
+    let file_path = RelativePath::new(&key).to_path(documents_dir);
+    let content = fs::read_to_string(&file_path)?;
+    let modified = fs::metadata(&file_path)?.modified().ok().map(|t| {
+      let system_time: DateTime<Utc> = t.into();
+      system_time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    });
+    self.links.index_document(&key, &content);
+    let tokens = tokenize(&content);
+    let mut terms = vec![];
+    for (position, token) in tokens.iter().enumerate() {
+      let positions = self
+        .postings
+        .entry(token.term.clone())
+        .or_insert_with(HashMap::new)
+        .entry(key.clone())
+        .or_insert_with(Vec::new);
+      if positions.is_empty() {
+        terms.push(token.term.clone());
+      }
+      positions.push(position as u32);
+    }
+    self.document_terms.insert(key.clone(), terms);
+    self.documents.insert(
+      key,
+      IndexedDocument {
+        modified,
+        token_count: tokens.len() as u32,
+      },
+    );
+    Ok(())
+  }
+
+  /// Remove the document on `relative_path` from the index
+  pub fn remove_document(&mut self, relative_path: &str) {
+    let key = document_key(relative_path);
+    self.links.remove_document(&key);
+    self.documents.remove(&key);
+    for term in self.document_terms.remove(&key).unwrap_or_default() {
+      if let Some(documents) = self.postings.get_mut(&term) {
+        documents.remove(&key);
+        if documents.is_empty() {
+          self.postings.remove(&term);
+        }
+      }
+    }
+  }
+
+  /// Move the indexed document from `old_relative_path` to `new_relative_path`
+  pub fn rename_document(&mut self, old_relative_path: &str, new_relative_path: &str) {
+    let old_key = document_key(old_relative_path);
+    let new_key = document_key(new_relative_path);
+    if !Self::is_indexable(&new_key) {
+      self.remove_document(&old_key);
+      return;
+    }
+    if new_key != old_key {
+      self.remove_document(&new_key); // replaced by the moved document
+    }
+    self.links.rename_document(&old_key, &new_key);
+    if let Some(document) = self.documents.remove(&old_key) {
+      self.documents.insert(new_key.clone(), document);
+      let terms = self.document_terms.remove(&old_key).unwrap_or_default();
+      for term in terms.iter() {
+        if let Some(documents) = self.postings.get_mut(term) {
+          if let Some(positions) = documents.remove(&old_key) {
+            documents.insert(new_key.clone(), positions);
+          }
+        }
+      }
+      self.document_terms.insert(new_key, terms);
+    }
+  }
+
+  /// Remove all indexed documents in the folder at `relative_path`
+  pub fn remove_folder(&mut self, relative_path: &str) {
+    for key in self.folder_document_keys(relative_path) {
+      self.remove_document(&key);
+    }
+  }
+
+  /// Move all indexed documents in the folder at `old_relative_path` to `new_relative_path`
+  pub fn rename_folder(&mut self, old_relative_path: &str, new_relative_path: &str) {
+    let old_prefix = document_key(old_relative_path);
+    let new_prefix = document_key(new_relative_path);
+    for key in self.folder_document_keys(old_relative_path) {
+      let new_key = format!("{}{}", new_prefix, &key[old_prefix.len()..]);
+      self.rename_document(&key, &new_key);
+    }
+  }
+
+  /// Keys of the indexed documents in the folder at `relative_path` (recursive)
+  fn folder_document_keys(&self, relative_path: &str) -> Vec<String> {
+    let prefix = format!("{}/", document_key(relative_path));
+    self
+      .documents
+      .keys()
+      .filter(|key| key.starts_with(&prefix))
+      .cloned()
+      .collect()
+  }
+
+  /// # Refresh
+  ///
+  /// Sync the index with the documents on disk.
+  /// - (Re)indexes new/modified documents and drops removed ones.
+  pub fn refresh(&mut self, documents_dir: &Path) -> Result<()> {
+    let mut on_disk = HashSet::new();
+    for entry in WalkDir::new(documents_dir)
+      .into_iter()
+      .filter_map(|e| e.ok())
+      .filter(|e| e.file_type().is_file())
+    {
+      let relative_path = match entry
+        .path()
+        .strip_prefix(documents_dir)
+        .map_err(anyhow::Error::from)
+        .and_then(|p| Ok(RelativePathBuf::from_path(p)?))
+      {
+        Ok(p) => p.normalize().to_string(),
+        Err(e) => {
+          warn!("{}", e);
+          continue;
+        }
+      };
+      if !Self::is_indexable(&relative_path) {
+        continue;
+      }
+      let modified = entry
+        .metadata()
+        .ok()
+        .and_then(|m| m.modified().ok())
+        .map(|t| {
+          let system_time: DateTime<Utc> = t.into();
+          system_time.to_rfc3339_opts(SecondsFormat::Millis, true)
+        });
+      // Indexes saved before the link graph existed have no links yet
+      let is_stale = match self.documents.get(&relative_path) {
+        Some(document) => {
+          document.modified != modified || !self.links.contains_document(&relative_path)
+        }
+        None => true,
+      };
+      if is_stale {
+        if let Err(e) = self.index_file(documents_dir, &relative_path) {
+          warn!("failed to index {}: {}", relative_path, e);
+        }
+      }
+      on_disk.insert(relative_path);
+    }
+    let removed: Vec<String> = self
+      .documents
+      .keys()
+      .filter(|key| !on_disk.contains(*key))
+      .cloned()
+      .collect();
+    for key in removed {
+      self.remove_document(&key);
+    }
+    Ok(())
+  }
+
+  /// Get document key -> number of hits for a query clause
+  fn clause_hits(&self, clause: &QueryClause) -> HashMap<String, usize> {
+    let mut hits = HashMap::new();
+    match clause {
+      QueryClause::Term(term) => {
+        if let Some(documents) = self.postings.get(term) {
+          for (key, positions) in documents {
+            hits.insert(key.clone(), positions.len());
+          }
+        }
+      }
+      QueryClause::Prefix(prefix) => {
+        for (_, documents) in self
+          .postings
+          .iter()
+          .filter(|(term, _)| term.starts_with(prefix.as_str()))
+        {
+          for (key, positions) in documents {
+            *hits.entry(key.clone()).or_insert(0) += positions.len();
+          }
+        }
+      }
+      QueryClause::Phrase(terms) => {
+        let postings: Vec<&HashMap<String, Vec<u32>>> =
+          match terms.iter().map(|t| self.postings.get(t)).collect() {
+            Some(postings) => postings,
+            None => return hits, // some word is not indexed at all
+          };
+        for (key, first_positions) in postings[0] {
+          let count = first_positions
+            .iter()
+            .filter(|p| {
+              postings.iter().enumerate().skip(1).all(|(i, documents)| {
+                documents
+                  .get(key)
+                  .map_or(false, |positions| positions.contains(&(**p + i as u32)))
+              })
+            })
+            .count();
+          if count > 0 {
+            hits.insert(key.clone(), count);
+          }
+        }
+      }
+    }
+    hits
+  }
+
+  /// # Search
+  ///
+  /// Search the indexed documents, results are sorted by score.
+  /// - See `SearchQuery` for the query syntax.
+  pub fn search(
+    &self,
+    documents_dir: &Path,
+    query: &str,
+    limit: usize,
+  ) -> Result<Vec<SearchResult>> {
+    let search_query = SearchQuery::parse(query)?;
+    let document_count = self.documents.len() as f64;
+    let avg_token_count = self
+      .documents
+      .values()
+      .map(|d| d.token_count as f64)
+      .sum::<f64>()
+      / document_count.max(1.0);
+    // document key -> score
+    let mut scores: HashMap<String, f64> = self
+      .documents
+      .iter()
+      .filter(|(key, document)| search_query.filter(key, document))
+      .map(|(key, _)| (key.clone(), 0.0))
+      .collect();
+    for clause in search_query.clauses.iter() {
+      let hits = self.clause_hits(clause);
+      let idf = (1.0 + (document_count - hits.len() as f64 + 0.5) / (hits.len() as f64 + 0.5)).ln();
+      scores.retain(|key, _| hits.contains_key(key));
+      for (key, score) in scores.iter_mut() {
+        let tf = hits[key] as f64;
+        let token_count = self.documents[key].token_count as f64;
+        *score += idf * (tf * (BM25_K1 + 1.0))
+          / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * token_count / avg_token_count.max(1.0)));
+      }
+    }
+    let mut ranked: Vec<(String, f64)> = scores.into_iter().collect();
+    if search_query.clauses.is_empty() {
+      // Only filters, show the recently modified documents first
+      ranked.sort_by(|a, b| {
+        self.documents[&b.0]
+          .modified
+          .cmp(&self.documents[&a.0].modified)
+      });
+    } else {
+      ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    }
+    let mut results = vec![];
+    for (key, score) in ranked.into_iter().take(limit) {
+      let snippets = match fs::read_to_string(RelativePath::new(&key).to_path(documents_dir)) {
+        Ok(content) => Self::snippets(&content, &search_query.clauses),
+        Err(e) => {
+          error!("failed to read {} for snippets: {}", key, e);
+          vec![]
+        }
+      };
+      results.push(SearchResult {
+        heading: snippets.first().and_then(|s| s.heading.clone()),
+        relative_path: key,
+        score,
+        snippets,
+      });
+    }
+    Ok(results)
+  }
+
+  /// Build highlighted snippets of the hits of `clauses` in `content`
+  fn snippets(content: &str, clauses: &[QueryClause]) -> Vec<SearchSnippet> {
+    let tokens = tokenize(content);
+    // byte ranges of all hits
+    let mut hits = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+      match clauses.iter().filter_map(|c| c.match_len(&tokens, i)).max() {
+        Some(len) => {
+          hits.push((tokens[i].start, tokens[i + len - 1].end));
+          i += len;
+        }
+        None => i += 1,
+      }
+    }
+    let headings = headings(content);
+    let mut snippets = vec![];
+    let mut snippet_end = 0;
+    for (hit_start, hit_end) in hits.iter().cloned() {
+      if snippets.len() >= MAX_SNIPPETS {
+        break;
+      }
+      if hit_start < snippet_end {
+        continue; // already part of the previous snippet
+      }
+      let start = floor_char_boundary(content, hit_start.saturating_sub(SNIPPET_CONTEXT_LEN));
+      let end = ceil_char_boundary(content, (hit_end + SNIPPET_CONTEXT_LEN).min(content.len()));
+      let mut html = String::new();
+      let mut cursor = start;
+      for (s, e) in hits.iter().filter(|(s, e)| *s >= start && *e <= end) {
+        html.push_str(&escape_html(&content[cursor..*s]));
+        html.push_str(&format!("<mark>{}</mark>", escape_html(&content[*s..*e])));
+        cursor = *e;
+      }
+      html.push_str(&escape_html(&content[cursor..end]));
+      let heading = headings
+        .iter()
+        .take_while(|(offset, _)| *offset <= hit_start)
+        .last()
+        .map(|(_, text)| text.clone());
+      snippets.push(SearchSnippet {
+        heading,
+        html: html.trim().replace('\n', " "),
+      });
+      snippet_end = end;
+    }
+    snippets
+  }
+}
+
+/// # Update Search Index
+///
+/// Apply `update` to the shared search index (saved later by the saver thread).
+/// - Errors are only logged as the index can always be rebuilt from the documents.
+pub fn update_search_index<F>(search_index: &Arc<Mutex<SearchIndex>>, update: F)
+where
+  F: FnOnce(&mut SearchIndex) -> Result<()>,
+{
+  match search_index.lock() {
+    Ok(mut index) => {
+      index.is_dirty = true;
+      if let Err(e) = update(&mut index) {
+        error!("search index update failed: {:?}", e);
+      }
+    }
+    Err(e) => error!("search index lock error: {}", e),
+  }
+}
+
+/// # Start Search Index Saver
+///
+/// Save the updated search index every `SAVE_INTERVAL` (in a background thread),
+/// so that bursts of document updates are written to disk once.
+/// - Updates not saved yet when the app exits are caught up by `refresh` on the next start.
+pub fn start_search_index_saver(search_index: Arc<Mutex<SearchIndex>>) {
+  thread::spawn(move || loop {
+    thread::sleep(SAVE_INTERVAL);
+    match search_index.lock() {
+      Ok(mut index) if index.is_dirty => {
+        if let Err(e) = index.save() {
+          error!("search index save failed: {:?}", e);
+        }
+      }
+      Ok(_) => {}
+      Err(e) => error!("search index lock error: {}", e),
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::test_utils::temp_documents_dir;
+
+  /// Create a fresh documents dir with the `documents` (relative path, content) indexed
+  fn setup(name: &str, documents: &[(&str, &str)]) -> (PathBuf, SearchIndex) {
+    let documents_dir = temp_documents_dir(&format!("search_index_{}", name), documents);
+    let mut index = SearchIndex::load(&documents_dir.with_file_name("search_index.json"));
+    for (relative_path, _) in documents {
+      index.index_file(&documents_dir, relative_path).unwrap();
+    }
+    (documents_dir, index)
+  }
+
+  fn result_paths(results: &[SearchResult]) -> Vec<&str> {
+    results.iter().map(|r| r.relative_path.as_str()).collect()
+  }
+
+  fn search_paths(index: &SearchIndex, documents_dir: &Path, query: &str) -> Vec<String> {
+    let results = index.search(documents_dir, query, 10).unwrap();
+    result_paths(&results)
+      .into_iter()
+      .map(String::from)
+      .collect()
+  }
+
+  #[test]
+  fn parses_query_clauses() {
+    for (query, expected) in &[
+      ("note", "[Term(\"note\")]"),
+      ("Note  IDEAS", "[Term(\"note\"), Term(\"ideas\")]"),
+      ("not*", "[Prefix(\"not\")]"),
+      ("\"My Note\"", "[Phrase([\"my\", \"note\"])]"),
+      ("\"unclosed phrase", "[Phrase([\"unclosed\", \"phrase\"])]"),
+      ("foo-bar", "[Phrase([\"foo\", \"bar\"])]"),
+      ("\"\" * -", "[]"),
+    ] {
+      let search_query = SearchQuery::parse(query).unwrap();
+      assert_eq!(
+        &format!("{:?}", search_query.clauses),
+        expected,
+        "{}",
+        query
+      );
+    }
+  }
+
+  #[test]
+  fn parses_query_filters() {
+    let search_query = SearchQuery::parse("dir:/projects/work/ note").unwrap();
+    assert_eq!(search_query.dir.as_deref(), Some("projects/work"));
+    assert_eq!(search_query.clauses.len(), 1);
+
+    let day = |date: &str| {
+      NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+    };
+    for (query, from, until) in &[
+      ("modified:2021-01-31", "2021-01-31", Some("2021-02-01")),
+      ("modified:=2021-01-31", "2021-01-31", Some("2021-02-01")),
+      ("modified:>2021-01-31", "2021-02-01", None),
+      ("modified:>=2021-01-31", "2021-01-31", None),
+    ] {
+      let search_query = SearchQuery::parse(query).unwrap();
+      assert_eq!(search_query.modified_from, day(from), "{}", query);
+      assert_eq!(
+        search_query.modified_until,
+        until.and_then(day),
+        "{}",
+        query
+      );
+    }
+    for (query, until) in &[
+      ("modified:<2021-01-31", "2021-01-31"),
+      ("modified:<=2021-01-31", "2021-02-01"),
+    ] {
+      let search_query = SearchQuery::parse(query).unwrap();
+      assert_eq!(search_query.modified_from, None, "{}", query);
+      assert_eq!(search_query.modified_until, day(until), "{}", query);
+    }
+    for query in &["modified:yesterday", "modified:>2021-13-01", "modified:"] {
+      assert!(SearchQuery::parse(query).is_err(), "{}", query);
+    }
+  }
+
+  #[test]
+  fn ranks_by_bm25() {
+    let (documents_dir, index) = setup(
+      "ranking",
+      &[
+        ("once.md", "rust is a language with many words around it"),
+        ("twice.md", "rust and more rust with many words around it"),
+        ("short.md", "rust once"),
+        ("none.md", "nothing to see here"),
+      ],
+    );
+    let paths = search_paths(&index, &documents_dir, "rust");
+    assert_eq!(paths, vec!["short.md", "twice.md", "once.md"]);
+    // rarer words weigh more
+    let results = index.search(&documents_dir, "rust words", 10).unwrap();
+    assert_eq!(result_paths(&results), vec!["twice.md", "once.md"]);
+    let results = index.search(&documents_dir, "language", 10).unwrap();
+    assert!(results[0].score > index.search(&documents_dir, "rust", 10).unwrap()[2].score);
+  }
+
+  #[test]
+  fn matches_all_clauses() {
+    let (documents_dir, index) = setup(
+      "clauses",
+      &[
+        ("a.md", "my note about ideas"),
+        ("b.md", "note my ideas"),
+        ("projects/c.md", "notebook of my ideas"),
+      ],
+    );
+    for (query, expected) in &[
+      ("\"my note\"", vec!["a.md"]),
+      ("note ideas", vec!["a.md", "b.md"]),
+      ("note*", vec!["a.md", "b.md", "projects/c.md"]),
+      ("note* dir:projects", vec!["projects/c.md"]),
+      ("missing", vec![]),
+      ("\"my missing\"", vec![]),
+    ] {
+      let mut paths = search_paths(&index, &documents_dir, query);
+      paths.sort();
+      assert_eq!(&paths, expected, "{}", query);
+    }
+    // only filters, all documents in the dir
+    assert_eq!(
+      search_paths(&index, &documents_dir, "dir:projects"),
+      vec!["projects/c.md"]
+    );
+  }
+
+  #[test]
+  fn builds_highlighted_snippets() {
+    let content = "# Intro\nSome <b> text\n\n## Details\nThe rust notes & more rust.\n";
+    let clauses = SearchQuery::parse("rust").unwrap().clauses;
+    let snippets = SearchIndex::snippets(content, &clauses);
+    assert_eq!(snippets.len(), 1);
+    assert_eq!(snippets[0].heading.as_deref(), Some("Details"));
+    assert!(snippets[0]
+      .html
+      .contains("The <mark>rust</mark> notes &amp; more <mark>rust</mark>."));
+    assert!(snippets[0].html.contains("Some &lt;b&gt; text"));
+
+    let clauses = SearchQuery::parse("\"rust notes\"").unwrap().clauses;
+    let snippets = SearchIndex::snippets(content, &clauses);
+    assert!(snippets[0].html.contains("<mark>rust notes</mark>"));
+
+    // far apart hits get their own snippets (at most `MAX_SNIPPETS`)
+    let content = vec!["rust"; 10].join(&" filler ".repeat(20));
+    let clauses = SearchQuery::parse("rust").unwrap().clauses;
+    assert_eq!(
+      SearchIndex::snippets(&content, &clauses).len(),
+      MAX_SNIPPETS
+    );
+  }
+
+  #[test]
+  fn removes_and_renames_documents() {
+    let (documents_dir, mut index) = setup(
+      "remove_rename",
+      &[
+        ("a.md", "shared onlya"),
+        ("notes/b.md", "shared onlyb"),
+        ("notes/c.md", "shared onlyc"),
+      ],
+    );
+    index.remove_document("a.md");
+    assert!(!index.postings.contains_key("onlya"));
+    assert_eq!(index.postings["shared"].len(), 2);
+    assert!(!index.document_terms.contains_key("a.md"));
+
+    fs::create_dir_all(documents_dir.join("archive")).unwrap();
+    fs::rename(
+      documents_dir.join("notes/b.md"),
+      documents_dir.join("archive/b.md"),
+    )
+    .unwrap();
+    index.rename_document("notes/b.md", "archive/b.md");
+    assert_eq!(
+      search_paths(&index, &documents_dir, "onlyb"),
+      vec!["archive/b.md"]
+    );
+    assert!(index.postings["shared"].contains_key("archive/b.md"));
+    assert!(!index.postings["shared"].contains_key("notes/b.md"));
+
+    index.remove_folder("notes");
+    assert_eq!(
+      search_paths(&index, &documents_dir, "shared"),
+      vec!["archive/b.md"]
+    );
+    assert_eq!(index.postings.len(), 2); // `shared`, `onlyb`
+  }
+
+  #[test]
+  fn rebuilds_document_terms_on_load() {
+    let (documents_dir, mut index) =
+      setup("load", &[("a.md", "alpha beta"), ("b.md", "beta gamma")]);
+    index.save().unwrap();
+    let mut index = SearchIndex::load(&documents_dir.with_file_name("search_index.json"));
+    assert_eq!(search_paths(&index, &documents_dir, "beta").len(), 2);
+    index.remove_document("b.md");
+    assert!(!index.postings.contains_key("gamma"));
+    assert_eq!(search_paths(&index, &documents_dir, "beta"), vec!["a.md"]);
+  }
+}
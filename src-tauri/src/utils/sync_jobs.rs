@@ -0,0 +1,266 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  panic::{self, AssertUnwindSafe},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, MutexGuard,
+  },
+  thread,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{SecondsFormat, Utc};
+use log::{error, info, warn};
+
+use crate::{
+  models::{
+    app_state::AppState,
+    cloud_sync::{CloudSync, CloudSyncStatus},
+    command_error::CommandError,
+    sync_job::{SyncJob, SyncJobKind, SyncJobOutput, SyncJobStatus},
+  },
+  utils::{
+    git_utils::SyncCancelled,
+    lock_manager::LockGuard,
+    window_event_manager::{WindowEvent, WindowEventManager, WindowEventType},
+  },
+};
+
+/// Number of finished jobs kept (for `get_sync_job_status`)
+const MAX_FINISHED_JOBS: usize = 20;
+
+#[derive(Debug, Default)]
+struct SyncJobsInner {
+  next_id: u64,
+  /// Jobs, most recent last
+  jobs: VecDeque<SyncJob>,
+  /// Cancel flags of the running jobs
+  cancel_flags: HashMap<String, Arc<AtomicBool>>,
+}
+
+/// # Sync Jobs
+///
+/// Runs cloud syncs (and other network operations, eg. sync previews)
+/// as background jobs that can be polled and cancelled by id.
+/// - The job holds the cloud sync `lock` until it finishes (also if it panics),
+/// which keeps other syncs from running meanwhile.
+/// - A `cloud_sync_job` window event is sent with the `SyncJob` when it finishes.
+#[derive(Debug, Clone, Default)]
+pub struct SyncJobs {
+  inner: Arc<Mutex<SyncJobsInner>>,
+}
+
+impl SyncJobs {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn lock(&self) -> Result<MutexGuard<SyncJobsInner>> {
+    self.inner.lock().map_err(|e| anyhow!(e.to_string()))
+  }
+
+  /// # Spawn
+  ///
+  /// Run `job` on a new thread, returns the id of the job.
+  /// - `job` gets the flag to abort it with (see `CloudSync::with_cancel_flag`).
+  /// - Its output (sync status, preview, ...) is kept in the finished `SyncJob`.
+  pub fn spawn<F, T>(
+    &self,
+    state: AppState,
+    window: tauri::Window,
+    lock: LockGuard,
+    kind: SyncJobKind,
+    job: F,
+  ) -> Result<String>
+  where
+    F: FnOnce(&WindowEventManager, Arc<AtomicBool>) -> Result<T> + Send + 'static,
+    T: Into<SyncJobOutput>,
+  {
+    let (id, cancel_flag) = self.start(kind)?;
+    let sync_jobs = self.clone();
+    let job_state = state.clone();
+    let job_id = id.clone();
+    let spawned = thread::Builder::new()
+      .name("cloud_sync_job".to_string())
+      .spawn(move || {
+        let wem = WindowEventManager::new(&window);
+        let result: Result<SyncJobOutput> = catch_panic(|| job(&wem, cancel_flag)).map(Into::into);
+        sync_jobs.finish(&job_state, &wem, &job_id, result.as_ref());
+        drop(lock);
+      });
+    if let Err(e) = spawned {
+      self.finish_job(&state, &id, Err(&anyhow!(e.to_string())))?;
+      return Err(anyhow!("Failed to start the sync job: {}", e));
+    }
+    Ok(id)
+  }
+
+  /// # Run
+  ///
+  /// Run `job` on the current thread as a (cancellable) job.
+  pub fn run<F>(
+    &self,
+    state: &AppState,
+    wem: &WindowEventManager,
+    lock: LockGuard,
+    kind: SyncJobKind,
+    job: F,
+  ) -> Result<CloudSyncStatus>
+  where
+    F: FnOnce(&WindowEventManager, Arc<AtomicBool>) -> Result<CloudSyncStatus>,
+  {
+    let (id, cancel_flag) = self.start(kind)?;
+    let result = catch_panic(|| job(wem, cancel_flag));
+    let output = result.as_ref().map(|status| SyncJobOutput::Status(*status));
+    self.finish(state, wem, &id, output.as_ref().map_err(|e| *e));
+    drop(lock);
+    result
+  }
+
+  /// # Cancel
+  ///
+  /// Request cancelling the job `id` (the running job if `None`).
+  /// - The job stops at its next checkpoint (network transfers are aborted),
+  /// poll `get` for its final status.
+  /// - Returns `None` if there is no such running job.
+  pub fn cancel(&self, id: Option<&str>) -> Result<Option<SyncJob>> {
+    let mut inner = self.lock()?;
+    let job = inner
+      .jobs
+      .iter_mut()
+      .rev()
+      .filter(|job| job.is_running())
+      .find(|job| id.map_or(true, |id| job.id == id));
+    let job = match job {
+      Some(job) => job,
+      None => return Ok(None),
+    };
+    info!("Cancelling sync job {}...", job.id);
+    job.cancel_requested = true;
+    let job = job.clone();
+    if let Some(cancel_flag) = inner.cancel_flags.get(&job.id) {
+      cancel_flag.store(true, Ordering::SeqCst);
+    }
+    Ok(Some(job))
+  }
+
+  /// # Get
+  ///
+  /// Get the job `id` (the most recent job if `None`).
+  pub fn get(&self, id: Option<&str>) -> Result<Option<SyncJob>> {
+    let inner = self.lock()?;
+    let job = match id {
+      Some(id) => inner.jobs.iter().find(|job| job.id == id),
+      None => inner.jobs.back(),
+    };
+    Ok(job.cloned())
+  }
+
+  /// Register a new running job
+  fn start(&self, kind: SyncJobKind) -> Result<(String, Arc<AtomicBool>)> {
+    let mut inner = self.lock()?;
+    inner.next_id += 1;
+    let id = format!("sync-{}", inner.next_id);
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    inner.cancel_flags.insert(id.clone(), cancel_flag.clone());
+    inner.jobs.push_back(SyncJob {
+      id: id.clone(),
+      kind,
+      status: SyncJobStatus::Running,
+      outcome: None,
+      preview: None,
+      config: None,
+      conflicts: None,
+      error: None,
+      cancel_requested: false,
+      started_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+      finished_at: None,
+    });
+    while inner.jobs.len() > MAX_FINISHED_JOBS + 1 {
+      inner.jobs.pop_front();
+    }
+    Ok((id, cancel_flag))
+  }
+
+  /// Record the `result` of the job and notify the window
+  fn finish(
+    &self,
+    state: &AppState,
+    wem: &WindowEventManager,
+    id: &str,
+    result: Result<&SyncJobOutput, &anyhow::Error>,
+  ) {
+    let job = match self.finish_job(state, id, result) {
+      Ok(job) => job,
+      Err(e) => {
+        error!("failed to finish sync job {}: {:?}", id, e);
+        return;
+      }
+    };
+    let typ = match job.status {
+      SyncJobStatus::Failed => WindowEventType::ERROR,
+      _ => WindowEventType::INFO,
+    };
+    if let Err(e) = wem.send(WindowEvent {
+      name: "cloud_sync_job",
+      typ,
+      data: job,
+    }) {
+      warn!("failed to send sync job event: {:?}", e);
+    }
+  }
+
+  fn finish_job(
+    &self,
+    state: &AppState,
+    id: &str,
+    result: Result<&SyncJobOutput, &anyhow::Error>,
+  ) -> Result<SyncJob> {
+    let conflicts = match result {
+      Ok(SyncJobOutput::Status(CloudSyncStatus::Conflicted)) => CloudSync::conflicts(state.clone())
+        .map_err(|e| warn!("failed to get sync conflicts: {:?}", e))
+        .ok(),
+      _ => None,
+    };
+    let mut inner = self.lock()?;
+    inner.cancel_flags.remove(id);
+    let job = inner
+      .jobs
+      .iter_mut()
+      .find(|job| job.id == id)
+      .ok_or_else(|| anyhow!("sync job {} not found!", id))?;
+    match result {
+      Ok(output) => {
+        job.status = SyncJobStatus::Completed;
+        match output {
+          SyncJobOutput::Status(status) => job.outcome = Some(*status),
+          SyncJobOutput::Preview(preview) => job.preview = Some(preview.clone()),
+          SyncJobOutput::Config(config) => job.config = Some(config.clone()),
+        }
+      }
+      Err(e) if e.is::<SyncCancelled>() => job.status = SyncJobStatus::Cancelled,
+      Err(e) => {
+        job.status = SyncJobStatus::Failed;
+        job.error = Some(CommandError::from(e));
+      }
+    }
+    job.conflicts = conflicts;
+    job.finished_at = Some(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true));
+    Ok(job.clone())
+  }
+}
+
+/// Run `job`, turning a panic into an error (the job is then `Failed`)
+fn catch_panic<F, T>(job: F) -> Result<T>
+where
+  F: FnOnce() -> Result<T>,
+{
+  panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|e| {
+    let reason = e
+      .downcast_ref::<&str>()
+      .map(|reason| reason.to_string())
+      .or_else(|| e.downcast_ref::<String>().cloned())
+      .unwrap_or_default();
+    Err(anyhow!("Sync job panicked! {}", reason))
+  })
+}
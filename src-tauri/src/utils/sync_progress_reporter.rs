@@ -0,0 +1,53 @@
+use std::{
+  cell::RefCell,
+  time::{Duration, Instant},
+};
+
+use crate::models::sync_progress::SyncProgress;
+
+/// Min delay between two progress reports of the same phase (at most 10/s)
+const MIN_REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// # Sync Progress Reporter
+///
+/// Forwards `SyncProgress` to a callback, throttled to `MIN_REPORT_INTERVAL`.
+/// - Phase changes and completed phases are always reported (once).
+pub struct SyncProgressReporter {
+  callback: Box<dyn Fn(&SyncProgress)>,
+  /// Last reported progress and when it was reported
+  last_report: RefCell<Option<(Instant, SyncProgress)>>,
+}
+
+impl SyncProgressReporter {
+  pub fn new<F>(callback: F) -> Self
+  where
+    F: Fn(&SyncProgress) + 'static,
+  {
+    Self {
+      callback: Box::new(callback),
+      last_report: RefCell::new(None),
+    }
+  }
+
+  /// Report `progress` (dropped if too soon after the last report)
+  pub fn report(&self, progress: SyncProgress) {
+    let now = Instant::now();
+    {
+      let mut last_report = self.last_report.borrow_mut();
+      let is_due = match &*last_report {
+        Some((_, last_progress)) if *last_progress == progress => false,
+        Some((reported_at, last_progress)) => {
+          last_progress.phase != progress.phase
+            || progress.is_complete()
+            || now.duration_since(*reported_at) >= MIN_REPORT_INTERVAL
+        }
+        None => true,
+      };
+      if !is_due {
+        return;
+      }
+      *last_report = Some((now, progress.clone()));
+    }
+    (self.callback)(&progress);
+  }
+}
@@ -0,0 +1,319 @@
+use std::{
+  cmp,
+  sync::{
+    mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    Arc, Mutex,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use log::{debug, error, info};
+use pickledb::PickleDb;
+use serde::Serialize;
+
+use crate::{
+  models::{
+    app_state::AppState,
+    cloud_sync::{CloudSync, CloudSyncStatus},
+    cloud_sync_config::CloudSyncConfig,
+    sync_job::SyncJobKind,
+  },
+  utils::{
+    git_utils::SyncCancelled,
+    lock_manager::LockMode,
+    window_event_manager::{WindowEvent, WindowEventManager, WindowEventType},
+  },
+};
+
+/// How often the scheduler checks if a sync is due.
+const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+/// Delay before retrying after the first failed sync (doubles on each failure).
+const BACKOFF_BASE_SECS: u64 = 30;
+/// Max delay between retries of failed syncs (1 hour).
+const BACKOFF_MAX_SECS: u64 = 3600;
+
+/// Messages sent to the scheduler thread.
+pub enum SyncSchedulerMessage {
+  /// A document was written/removed/renamed.
+  DocumentChanged,
+  /// Window used to run syncs and send events to.
+  AttachWindow(tauri::Window),
+}
+
+/// What triggered an auto-sync run.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AutoSyncTrigger {
+  Interval,
+  DocumentChange,
+}
+
+/// Result of an auto-sync run.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AutoSyncRunStatus {
+  Synced,
+  Conflicted,
+  /// The remote kept rejecting the push.
+  Rejected,
+  Failed,
+  /// Cancelled via `cancel_cloud_sync`.
+  Cancelled,
+  /// Another (cloud/fs) sync was in progress.
+  Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoSyncPayload {
+  pub trigger: AutoSyncTrigger,
+  pub status: AutoSyncRunStatus,
+  pub message: String,
+  /// Number of failed runs in a row (used for backoff).
+  pub consecutive_failures: u32,
+}
+
+/// # Sync Scheduler Handle
+///
+/// Used to notify the scheduler thread (eg. of document changes).
+#[derive(Debug, Clone)]
+pub struct SyncSchedulerHandle {
+  sender: Arc<Mutex<Sender<SyncSchedulerMessage>>>,
+}
+
+impl SyncSchedulerHandle {
+  /// Create a new handle and the receiver to start the `SyncScheduler` with.
+  pub fn new() -> (Self, Receiver<SyncSchedulerMessage>) {
+    let (sender, receiver) = mpsc::channel();
+    (
+      Self {
+        sender: Arc::new(Mutex::new(sender)),
+      },
+      receiver,
+    )
+  }
+
+  /// Notify that a document changed (debounced auto-sync).
+  /// - Never fails, errors are only logged.
+  pub fn notify_document_changed(&self) {
+    self.send(SyncSchedulerMessage::DocumentChanged);
+  }
+
+  /// Attach the window used to run syncs.
+  pub fn attach_window(&self, window: tauri::Window) {
+    self.send(SyncSchedulerMessage::AttachWindow(window));
+  }
+
+  fn send(&self, message: SyncSchedulerMessage) {
+    match self.sender.lock() {
+      Ok(sender) => {
+        if let Err(e) = sender.send(message) {
+          error!("sync scheduler is not running: {}", e);
+        }
+      }
+      Err(e) => error!("sync scheduler sender lock error: {}", e),
+    }
+  }
+}
+
+/// # Sync Scheduler
+///
+/// Runs `CloudSync::sync` in the background when auto-sync is enabled:
+/// - On the configured interval.
+/// - After the debounce delay following the last document change.
+/// - Failed runs are retried with an exponential backoff.
+pub struct SyncScheduler {
+  state: AppState,
+  db: Arc<Mutex<PickleDb>>,
+  receiver: Receiver<SyncSchedulerMessage>,
+  window: Option<tauri::Window>,
+  /// Time of the last document change not synced yet.
+  pending_change_at: Option<Instant>,
+  last_run_at: Instant,
+  consecutive_failures: u32,
+}
+
+impl SyncScheduler {
+  /// Start the scheduler on a new thread
+  pub fn start(
+    receiver: Receiver<SyncSchedulerMessage>,
+    state: AppState,
+    db: Arc<Mutex<PickleDb>>,
+  ) -> Result<()> {
+    let scheduler = SyncScheduler {
+      state,
+      db,
+      receiver,
+      window: None,
+      pending_change_at: None,
+      last_run_at: Instant::now(),
+      consecutive_failures: 0,
+    };
+    thread::Builder::new()
+      .name("sync_scheduler".to_string())
+      .spawn(move || scheduler.run())?;
+    Ok(())
+  }
+
+  fn run(mut self) {
+    info!("Sync scheduler started...");
+    loop {
+      match self.receiver.recv_timeout(SCHEDULER_TICK) {
+        Ok(SyncSchedulerMessage::DocumentChanged) => self.pending_change_at = Some(Instant::now()),
+        Ok(SyncSchedulerMessage::AttachWindow(window)) => self.window = Some(window),
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => break,
+      }
+      if let Some(trigger) = self.due_trigger() {
+        self.run_sync(trigger);
+      }
+    }
+    info!("Sync scheduler stopped!");
+  }
+
+  /// Get the trigger of the auto-sync run due now (if any)
+  fn due_trigger(&self) -> Option<AutoSyncTrigger> {
+    let config = match self.db.lock() {
+      Ok(db) => CloudSyncConfig::load(&db)?,
+      Err(e) => {
+        error!("sync scheduler db lock error: {}", e);
+        return None;
+      }
+    };
+    if !config.auto_sync_enabled {
+      return None;
+    }
+    let now = Instant::now();
+    if self.consecutive_failures > 0 && now < self.last_run_at + self.backoff() {
+      return None;
+    }
+    match self.pending_change_at {
+      Some(changed_at)
+        if now >= changed_at + Duration::from_secs(config.auto_sync_debounce_secs) =>
+      {
+        Some(AutoSyncTrigger::DocumentChange)
+      }
+      _ if now >= self.last_run_at + Duration::from_secs(config.auto_sync_interval_secs) => {
+        Some(AutoSyncTrigger::Interval)
+      }
+      _ => None,
+    }
+  }
+
+  /// Delay before retrying a failed sync
+  fn backoff(&self) -> Duration {
+    let exponent = cmp::min(self.consecutive_failures.saturating_sub(1), 16);
+    let secs = BACKOFF_BASE_SECS.saturating_mul(1 << exponent);
+    Duration::from_secs(cmp::min(secs, BACKOFF_MAX_SECS))
+  }
+
+  fn run_sync(&mut self, trigger: AutoSyncTrigger) {
+    let window = match &self.window {
+      Some(window) => window.clone(),
+      None => return, // window not ready yet
+    };
+    let wem = WindowEventManager::new(&window);
+    let lock = match self
+      .state
+      .locks
+      .try_cloud_sync(LockMode::Write, "auto_sync")
+    {
+      Ok(lock) => lock,
+      Err(busy) => {
+        debug!("{:?}, skipping auto-sync...", busy.holder);
+        self.report(
+          &wem,
+          trigger,
+          AutoSyncRunStatus::Skipped,
+          "Sync in progress!",
+        );
+        // check again after the next interval/debounce delay
+        self.last_run_at = Instant::now();
+        if self.pending_change_at.is_some() {
+          self.pending_change_at = Some(Instant::now());
+        }
+        return;
+      }
+    };
+    info!("Running auto-sync ({:?})...", trigger);
+    self.pending_change_at = None;
+    self.last_run_at = Instant::now();
+    // run as a job so it can be cancelled like manual syncs
+    let result = self.state.sync_jobs.run(
+      &self.state,
+      &wem,
+      lock,
+      SyncJobKind::AutoSync,
+      |wem, cancel_flag| {
+        CloudSync::new(wem)?
+          .with_cancel_flag(cancel_flag)
+          .sync(self.state.clone(), &self.db)
+      },
+    );
+    match result {
+      Ok(CloudSyncStatus::Pushed) | Ok(CloudSyncStatus::UpToDate) => {
+        self.consecutive_failures = 0;
+        self.report(&wem, trigger, AutoSyncRunStatus::Synced, "Success!");
+      }
+      Ok(CloudSyncStatus::Rejected) => {
+        self.consecutive_failures += 1;
+        self.report(
+          &wem,
+          trigger,
+          AutoSyncRunStatus::Rejected,
+          "Push rejected by the remote!",
+        );
+      }
+      Ok(CloudSyncStatus::Conflicted) => {
+        self.consecutive_failures += 1;
+        self.report(
+          &wem,
+          trigger,
+          AutoSyncRunStatus::Conflicted,
+          "Merge conflicts detected, resolve them to complete the sync!",
+        );
+      }
+      Err(e) if e.is::<SyncCancelled>() => {
+        info!("auto-sync cancelled");
+        self.report(
+          &wem,
+          trigger,
+          AutoSyncRunStatus::Cancelled,
+          "Sync cancelled!",
+        );
+      }
+      Err(e) => {
+        error!("auto-sync failed: {:?}", e);
+        self.consecutive_failures += 1;
+        self.report(&wem, trigger, AutoSyncRunStatus::Failed, &e.to_string());
+      }
+    }
+  }
+
+  fn report(
+    &self,
+    wem: &WindowEventManager,
+    trigger: AutoSyncTrigger,
+    status: AutoSyncRunStatus,
+    message: &str,
+  ) {
+    let event = WindowEvent {
+      name: "auto_cloud_sync",
+      typ: match status {
+        AutoSyncRunStatus::Failed | AutoSyncRunStatus::Conflicted => WindowEventType::ERROR,
+        _ => WindowEventType::INFO,
+      },
+      data: AutoSyncPayload {
+        trigger,
+        status,
+        message: message.to_string(),
+        consecutive_failures: self.consecutive_failures,
+      },
+    };
+    if let Err(e) = wem.send(event) {
+      error!("{:?}", e);
+    }
+  }
+}
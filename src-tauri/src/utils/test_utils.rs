@@ -0,0 +1,148 @@
+use std::{
+  env, fs,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+};
+
+use git2::Repository;
+use pickledb::{PickleDb, PickleDbDumpPolicy, SerializationMethod};
+
+use crate::{
+  models::{app_dir_paths::AppDirPaths, app_state::AppState},
+  utils::{
+    git_credentials::GitCredentialProvider,
+    git_utils::{GitUtils, MergeStatus, PushStatus, DEFAULT_BRANCH_NAME, DEFAULT_REMOTE_NAME},
+    lock_manager::LockManager,
+    search_index::SearchIndex,
+    sync_jobs::SyncJobs,
+    sync_scheduler::SyncSchedulerHandle,
+  },
+};
+
+/// # Temp Documents Dir
+///
+/// Create a fresh documents dir for a test with the `(relative path, content)`
+/// documents (their folders are created too).
+/// - The dir is `<temp dir>/mediocre_<name>/documents`, its parent is the root dir
+///   of the test (eg. for the repository or the trash).
+/// - `name` must be unique across all tests (they run in parallel).
+pub fn temp_documents_dir(name: &str, documents: &[(&str, &str)]) -> PathBuf {
+  let root = env::temp_dir().join(format!("mediocre_{}", name));
+  let _ = fs::remove_dir_all(&root);
+  let documents_dir = root.join("documents");
+  fs::create_dir_all(&documents_dir).unwrap();
+  for (relative_path, content) in documents {
+    let path = documents_dir.join(relative_path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+  }
+  documents_dir
+}
+
+/// # Temp Remote
+///
+/// Create a fresh bare repository for a test, to be used as the remote of `temp_repo`s.
+pub fn temp_remote(name: &str) -> PathBuf {
+  let path = env::temp_dir().join(format!("mediocre_{}.git", name));
+  let _ = fs::remove_dir_all(&path);
+  Repository::init_bare(&path).unwrap();
+  path
+}
+
+/// # Temp Repo
+///
+/// Create a fresh repository around a `temp_documents_dir` (nothing committed yet)
+/// syncing the default branch with `remote_url`.
+/// - Returns the repo root dir (ie. the parent of the documents dir).
+pub fn temp_repo(name: &str, remote_url: &str, documents: &[(&str, &str)]) -> (PathBuf, GitUtils) {
+  let root = temp_documents_dir(name, documents)
+    .parent()
+    .unwrap()
+    .to_path_buf();
+  let git_utils = init_repo(&root, remote_url);
+  (root, git_utils)
+}
+
+/// # Init Repo
+///
+/// Init the repository at `root` syncing the default branch with `remote_url`.
+pub fn init_repo(root: &Path, remote_url: &str) -> GitUtils {
+  GitUtils::new(
+    remote_url,
+    root,
+    DEFAULT_REMOTE_NAME,
+    DEFAULT_BRANCH_NAME,
+    "mediocre",
+    "mediocre@example.com",
+    GitCredentialProvider::default(),
+  )
+  .unwrap()
+}
+
+/// # Temp Synced Repos
+///
+/// Create two `temp_repo`s (`<name>_a` and `<name>_b`, eg. two devices) syncing with
+/// the same `temp_remote`, both at the pushed commit of the `documents`.
+pub fn temp_synced_repos(
+  name: &str,
+  documents: &[(&str, &str)],
+) -> ((PathBuf, GitUtils), (PathBuf, GitUtils)) {
+  let remote = temp_remote(name);
+  let remote_url = remote.to_str().unwrap();
+  let (root_a, a) = temp_repo(&format!("{}_a", name), remote_url, documents);
+  assert!(commit_documents(&a));
+  assert_eq!(a.push().unwrap(), PushStatus::Pushed);
+  let (root_b, b) = temp_repo(&format!("{}_b", name), remote_url, &[]);
+  assert_eq!(b.pull().unwrap(), MergeStatus::FastForward);
+  ((root_a, a), (root_b, b))
+}
+
+/// # Commit Documents
+///
+/// Commit all the documents of a `temp_repo`, `false` if nothing changed.
+pub fn commit_documents(git_utils: &GitUtils) -> bool {
+  git_utils
+    .add_commit(vec![Path::new("documents")], |_| "sync".to_string())
+    .unwrap()
+}
+
+/// # Temp App Dirs
+///
+/// App dirs in a fresh root dir for a test (the documents dir is created
+/// as by `temp_documents_dir`) and a DB that is only kept in memory.
+pub fn temp_app_dirs(name: &str, documents: &[(&str, &str)]) -> (AppDirPaths, PickleDb) {
+  let documents = temp_documents_dir(name, documents);
+  let root = documents.parent().unwrap().to_path_buf();
+  let dir_paths = AppDirPaths {
+    db: root.join("db"),
+    logs: root.join("logs"),
+    trash: root.join(".trash"),
+    documents,
+    root,
+  };
+  fs::create_dir_all(&dir_paths.trash).unwrap();
+  let db = PickleDb::new(
+    dir_paths.db.join("mediocre.db"),
+    PickleDbDumpPolicy::NeverDump,
+    SerializationMethod::Json,
+  );
+  (dir_paths, db)
+}
+
+/// # Temp App State
+///
+/// App state of fresh `temp_app_dirs` with a cloud sync repository
+/// initialized in the root dir (nothing committed yet).
+pub fn temp_app_state(name: &str, documents: &[(&str, &str)]) -> (AppState, GitUtils) {
+  let (dir_paths, _) = temp_app_dirs(name, documents);
+  let git_utils = init_repo(&dir_paths.root, "https://example.com/mediocre.git");
+  let state = AppState {
+    dir_paths,
+    locks: LockManager::new(),
+    git_auth_secret: Arc::new(Mutex::new(None)),
+    sync_scheduler: SyncSchedulerHandle::new().0,
+    search_index: Arc::new(Mutex::new(SearchIndex::default())),
+    sync_jobs: SyncJobs::new(),
+  };
+  (state, git_utils)
+}
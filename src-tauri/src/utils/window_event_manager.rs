@@ -48,7 +48,7 @@ pub struct WindowEventManager<'w> {
   pub window: &'w tauri::Window,
 }
 
-impl <'w> WindowEventManager<'w> {
+impl<'w> WindowEventManager<'w> {
   pub fn new(window: &'w tauri::Window) -> Self {
     Self { window }
   }
@@ -1,6 +1,5 @@
-import { tauri } from '@tauri-apps/api'
 import isTauri from 'src/utils/isTauri'
-import { RetryError } from 'src/utils/retry'
+import { invokeCommand } from './commandError'
 
 /**
  * ### Test Git Clone SSH (Tauri Command)
@@ -9,11 +8,7 @@ import { RetryError } from 'src/utils/retry'
  */
 export const testGitCloneSSH = async () => {
   if (isTauri()) {
-    const invokeRes: {
-      status: boolean
-      message: string
-    } = await tauri.invoke('test_git_clone_ssh', {})
-    return invokeRes
+    await invokeCommand<void>('test_git_clone_ssh')
   }
 }
 
@@ -21,6 +16,7 @@ export const testGitCloneSSH = async () => {
  * ### Store Git repository SSH URL
  *
  * Tauri command to store git repo url(ssh), for sync.
+ * - Returns the id of the background sync job.
  */
 export const setupGitCloudSync = async (
   gitSyncRepoUrl: string,
@@ -28,19 +24,12 @@ export const setupGitCloudSync = async (
   gitSyncUserEmail: string
 ) => {
   if (isTauri()) {
-    const invokeRes: {
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('setup_git_cloud_sync', {
+    const jobId = await invokeCommand<string>('setup_git_cloud_sync', {
       gitSyncRepoUrl,
       gitSyncUserName,
       gitSyncUserEmail,
     })
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(`setupGitCloudSync failed: ${invokeRes.message}`)
-    return invokeRes
+    return jobId
   }
 }
 
@@ -48,17 +37,11 @@ export const setupGitCloudSync = async (
  * ### Sync to Git Cloud
  *
  * Tauri command to normally sync to git cloud.
+ * - Returns the id of the background sync job.
  */
 export const syncToGitCloud = async () => {
   if (isTauri()) {
-    const invokeRes: {
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('sync_to_git_cloud', {})
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(`syncToGitCloud failed: ${invokeRes.message}`)
-    return invokeRes
+    const jobId = await invokeCommand<string>('sync_to_git_cloud')
+    return jobId
   }
 }
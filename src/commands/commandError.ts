@@ -0,0 +1,82 @@
+/**
+ * Errors of the Tauri commands
+ */
+
+import { tauri } from '@tauri-apps/api'
+
+/** Stable error codes (see `ErrorCode` in `command_error.rs`) */
+export type CommandErrorCode =
+  | 'INVALID_INPUT'
+  | 'NOT_FOUND'
+  | 'ALREADY_EXISTS'
+  | 'WRITE_CONFLICT'
+  | 'LOCK_BUSY'
+  | 'SYNC_CANCELLED'
+  | 'AUTH_FAILED'
+  | 'NETWORK_ERROR'
+  | 'INTERNAL'
+
+/** Who/what the error is caused by (see `ErrorCategory` in `command_error.rs`) */
+export type CommandErrorCategory =
+  | 'user'
+  | 'internal'
+  | 'conflict'
+  | 'auth'
+  | 'network'
+  | 'busy'
+
+/** Error as serialized by the Tauri backend */
+type CommandErrorPayload = {
+  code: CommandErrorCode
+  category: CommandErrorCategory
+  message: string
+  retryable: boolean
+  details?: unknown
+}
+
+const isCommandErrorPayload = (error: unknown): error is CommandErrorPayload =>
+  typeof error === 'object' &&
+  error !== null &&
+  typeof (error as CommandErrorPayload).code === 'string' &&
+  typeof (error as CommandErrorPayload).message === 'string'
+
+/**
+ * Command Error Class
+ *
+ * Thrown when a Tauri command fails, match on `code` (or `retryable`).
+ */
+export class CommandError extends Error {
+  code: CommandErrorCode
+  category: CommandErrorCategory
+  /** `true` if the same request may succeed later */
+  retryable: boolean
+  /** Error specific data (eg. the lock holder or the conflicting content) */
+  details?: unknown
+
+  constructor(error: CommandErrorPayload) {
+    super(error.message)
+    this.name = this.constructor.name
+    this.code = error.code
+    this.category = error.category
+    this.retryable = error.retryable
+    this.details = error.details
+  }
+}
+
+/**
+ * Invoke the Tauri command `cmd` with `args`
+ * - Rejects with a `CommandError` if the command fails.
+ */
+export const invokeCommand = async <T>(
+  cmd: string,
+  args: Record<string, unknown> = {}
+) => {
+  try {
+    return await tauri.invoke<T>(cmd, args)
+  } catch (error) {
+    if (isCommandErrorPayload(error)) throw new CommandError(error)
+    throw error
+  }
+}
+
+export default null
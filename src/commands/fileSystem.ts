@@ -2,12 +2,11 @@
  * All File System Functions here
  */
 
-import { tauri } from '@tauri-apps/api'
 import { open } from '@tauri-apps/api/dialog'
 import { homeDir } from '@tauri-apps/api/path'
-import { RetryError } from 'src/utils/retry'
 import { IsoDatetime } from '../commonTypes'
 import isTauri from '../utils/isTauri'
+import { invokeCommand } from './commandError'
 
 const appDirName =
   process.env.NODE_ENV === 'production' ? '.mediocre' : '.mediocredev'
@@ -38,6 +37,41 @@ export const openFileSelectionDialog = async (options?: {
   return openResult
 }
 
+/** Document meta info (`FileMetaInfo` in `fsutils.rs`) */
+export type DocumentMetaInfo = {
+  fileName: string
+  filePath: string
+  fileRelativePath?: string
+  fileDir?: string
+  fileType?: 'markdown'
+  modified?: IsoDatetime
+  /** Title from the front matter */
+  title?: string
+  tags: string[]
+  aliases: string[]
+  created?: IsoDatetime
+}
+
+/** Trashed document (`TrashEntry` in `trash.rs`) */
+export type TrashEntry = {
+  id: string
+  originalRelativePath: string
+  deletedAt: IsoDatetime
+  isFolder: boolean
+}
+
+/** What a rename/move changed (`RelocationReport` in `relocation.rs`) */
+export type RelocationReport = {
+  /** Id to undo the relocation with */
+  undoId?: string
+  oldRelativePath: string
+  newRelativePath: string
+  isFolder: boolean
+  applied: boolean
+  changedFiles: { relativePath: string; edits: unknown[] }[]
+  skippedFiles: string[]
+}
+
 /**
  * Save File to a custom directory path supplied
  */
@@ -48,14 +82,10 @@ export const saveFileToCustomPath = async (
 ) => {
   if (isTauri()) {
     if (!(dirPath && fileName)) throw new Error(`dirPath/fileName invalid!`)
-    const invokeRes: {
-      status: boolean
-      message: string
-    } = await tauri.invoke('save_file_to', {
+    await invokeCommand<void>('save_file_to', {
       savePath: `${dirPath}/${fileName}`,
       fileData,
     })
-    return invokeRes
   }
 }
 
@@ -64,29 +94,10 @@ export const saveFileToCustomPath = async (
  */
 export const fetchDocumentMetaData = async (relativePath: string) => {
   if (isTauri()) {
-    const invokeRes: {
-      fileMetaInfo?: {
-        fileName: string
-        filePath: string
-        fileRelativePath?: string
-        fileDir?: string
-        fileType?: 'markdown'
-        modified?: IsoDatetime
-      }
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('fetch_doc_info', { relativePath })
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(`fetchDocumentMetaData failed: ${invokeRes.message}`)
-    const homeDirPath = await homeDir()
-    if (!homeDirPath) throw new Error('Path to home dir invalid!')
-    if (!invokeRes.fileMetaInfo)
-      throw new Error(`invokeRes.fileMetaInfo invalid!`)
-    const fileMetaInfo = {
-      ...invokeRes.fileMetaInfo,
-    }
+    const fileMetaInfo = await invokeCommand<DocumentMetaInfo>(
+      'fetch_doc_info',
+      { relativePath }
+    )
     return fileMetaInfo
   }
 }
@@ -96,29 +107,9 @@ export const fetchDocumentMetaData = async (relativePath: string) => {
  */
 export const fetchAllDocumentsMetadata = async () => {
   if (isTauri()) {
-    const invokeRes: {
-      filesMetaInfo?: {
-        fileName: string
-        filePath: string
-        fileRelativePath?: string
-        fileDir?: string
-        fileType?: 'markdown'
-        modified?: IsoDatetime
-      }[]
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('fetch_all_docs_info', {})
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(`fetchAllDocumentsMetadata failed: ${invokeRes.message}`)
-    const homeDirPath = await homeDir()
-    if (!homeDirPath) throw new Error('Path to home dir invalid!')
-    if (!invokeRes.filesMetaInfo)
-      throw new Error(`invokeRes.filesMetaInfo invalid!`)
-    const filesMetaInfo = invokeRes.filesMetaInfo.map((docMeta) => ({
-      ...docMeta,
-    }))
+    const filesMetaInfo = await invokeCommand<DocumentMetaInfo[]>(
+      'fetch_all_docs_info'
+    )
     return filesMetaInfo
   }
 }
@@ -128,18 +119,12 @@ export const fetchAllDocumentsMetadata = async () => {
  */
 export const readDocumentFromRelativePath = async (relativePath: string) => {
   if (isTauri()) {
-    const invokeRes: {
+    const document = await invokeCommand<{
       content: string
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('read_document', { relativePath })
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(
-        `readDocumentFromRelativePath failed: ${invokeRes.message}`
-      )
-    return invokeRes.content
+      modified?: IsoDatetime
+      contentHash: string
+    }>('read_document', { relativePath })
+    return document.content
   }
 }
 
@@ -151,36 +136,24 @@ export const writeDocumentToRelativePath = async (
   content: string
 ) => {
   if (isTauri()) {
-    const invokeRes: {
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('write_document', { relativePath, content })
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(
-        `writeDocumentToRelativePath failed: ${invokeRes.message}`
-      )
-    return invokeRes
+    const written = await invokeCommand<{
+      modified?: IsoDatetime
+      contentHash: string
+    }>('write_document', { relativePath, content })
+    return written
   }
 }
 
 /**
  * Remove document on a specified relative path
+ * - The document is moved to the trash, returns its trash entry.
  */
 export const removeDocumentFromRelativePath = async (relativePath: string) => {
   if (isTauri()) {
-    const invokeRes: {
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('remove_document', { relativePath })
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(
-        `removeDocumentFromRelativePath failed: ${invokeRes.message}`
-      )
-    return invokeRes
+    const trashEntry = await invokeCommand<TrashEntry>('remove_document', {
+      relativePath,
+    })
+    return trashEntry
   }
 }
 
@@ -192,17 +165,11 @@ export const renameDocumentAtRelativePath = async (
   newDocumentName: string
 ) => {
   if (isTauri()) {
-    const invokeRes: {
-      status: boolean
-      retry: boolean
-      message: string
-    } = await tauri.invoke('rename_document', { relativePath, newDocumentName })
-    if (invokeRes.retry) throw new RetryError(invokeRes.message)
-    if (!invokeRes.status)
-      throw new Error(
-        `renameDocumentAtRelativePath failed: ${invokeRes.message}`
-      )
-    return invokeRes
+    const report = await invokeCommand<RelocationReport>('rename_document', {
+      relativePath,
+      newDocumentName,
+    })
+    return report
   }
 }
 
@@ -80,7 +80,7 @@ export const globalSetupGitCloudSync = createAsyncThunk<
       async () => setupGitCloudSync(repoUrl, configUserName, configUserEmail),
       3000 // each with 3 sec timeout
     )
-    if (!response?.status) throw new Error(response?.message)
+    if (!response) throw new Error(`setupGitCloudSync failed!`)
     dispatch(globalAllDocumentsListFetch()) // Update documents list
     return { repoUrl, configUserName, configUserEmail }
   }
@@ -104,9 +104,9 @@ export const globalSyncToGitCloud = createAsyncThunk<boolean, void>(
       async () => syncToGitCloud(),
       3000 // each with 3 sec timeout
     )
-    if (!response?.status) throw new Error(response?.message)
+    if (!response) throw new Error(`syncToGitCloud failed!`)
     dispatch(globalAllDocumentsListFetch()) // Update documents list
-    return response.status
+    return true
   }
 )
 
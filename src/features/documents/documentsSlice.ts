@@ -9,6 +9,7 @@ import {
 } from '@reduxjs/toolkit'
 import { History } from 'history'
 import debounce from 'lodash/debounce'
+import { CommandError } from 'src/commands/commandError'
 import retry from 'src/utils/retry'
 import {
   fetchAllDocumentsMetadata,
@@ -176,7 +177,7 @@ export const globalDocumentSave = createAsyncThunk<string, void>(
 
     /** dispatch for updating doc meta info into the store */
     await dispatch(globalDocumentInfoFetch({ relativePath })).unwrap()
-    if (!response?.status) throw new Error('Response status is invalid!')
+    if (!response) throw new Error('Response is invalid!')
     dispatch(updateRawText(updatedContent)) // Update the md raw text as well
     debouncedDispatchGlobalSyncToGitCloud(dispatch)
     return updatedContent
@@ -199,7 +200,7 @@ export const globalDocumentAdd = createAsyncThunk<
     async () => writeDocumentToRelativePath(relativePath, ''),
     3000 // each with 3 sec timeout
   )
-  if (!response?.status) throw new Error(`Response status is invalid!`)
+  if (!response) throw new Error(`Response is invalid!`)
   /** fetch the doc info */
   const documentInfo = await dispatch(
     globalDocumentInfoFetch({ relativePath })
@@ -230,7 +231,7 @@ export const globalDocumentDelete = createAsyncThunk<
     async () => removeDocumentFromRelativePath(relativePath),
     3000 // each with 3 sec timeout
   )
-  if (!result?.status) throw new Error(`delete failed due to some reason`)
+  if (!result) throw new Error(`delete failed due to some reason`)
   /** Reset editor text */
   dispatch(updateRawText(''))
   history.push('/app')
@@ -251,13 +252,20 @@ export const globalDocumentRename = createAsyncThunk<
     throw new Error(`document invalid! document with documentId not available`)
   const { relativePath } = document
   if (!relativePath) throw new Error(`relativePath invalid!`)
-  const response = await retry(
-    5, // retry 5 times
-    async () =>
-      await renameDocumentAtRelativePath(relativePath, newDocumentName),
-    3000 // each with 3 sec timeout
-  )
-  if (!response?.status) throw new Error(`Response status is invalid!`)
+  let response
+  try {
+    response = await retry(
+      5, // retry 5 times
+      async () =>
+        await renameDocumentAtRelativePath(relativePath, newDocumentName),
+      3000 // each with 3 sec timeout
+    )
+  } catch (error) {
+    if (error instanceof CommandError && error.code === 'ALREADY_EXISTS')
+      throw new Error(`A document named ${newDocumentName} already exists!`)
+    throw error
+  }
+  if (!response) throw new Error(`Response is invalid!`)
   /** fetch the doc info */
   await dispatch(globalAllDocumentsListFetch()).unwrap()
 })
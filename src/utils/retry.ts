@@ -1,3 +1,4 @@
+import { CommandError } from 'src/commands/commandError'
 import sleep from './sleep'
 
 /**
@@ -13,6 +14,7 @@ export class RetryError extends Error {
 /**
  * Retry wrapper for calling `fn` for `maxRetries`
  * each after a certain `timeout`
+ * - Retries on a `RetryError` or a `retryable` `CommandError` (eg. `LOCK_BUSY`)
  * @param maxRetries
  * @param fn
  */
@@ -25,7 +27,10 @@ const retry = async <T = void>(
     try {
       return await fn()
     } catch (error) {
-      if (error instanceof RetryError) {
+      if (
+        error instanceof RetryError ||
+        (error instanceof CommandError && error.retryable)
+      ) {
         if (timeout) await sleep(timeout)
         continue
       } else {